@@ -0,0 +1,458 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use actix_web::dev::{Payload, ServiceRequest};
+use actix_web::HttpMessage;
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose as b64, Engine as _};
+use bytes::{Bytes, BytesMut};
+use clap::ValueEnum;
+use futures_util::StreamExt;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::time::get_now_ms;
+
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+// Requests waiting to be written, further ones are dropped when the writer can't keep up
+const QUEUE_SIZE: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum CaptureFormat {
+    // One JSON object per line, with the body encoded in base64
+    Ndjson,
+    // The request as JSON and its raw body, both prefixed with their length as a big-endian u32
+    LengthPrefixed,
+}
+
+impl CaptureFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            CaptureFormat::Ndjson => "ndjson",
+            CaptureFormat::LengthPrefixed => "bin",
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "ndjson" => Some(CaptureFormat::Ndjson),
+            "bin" => Some(CaptureFormat::LengthPrefixed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct CapturedRequest {
+    pub timestamp: u64, // milliseconds epoch timestamp of when the request was received
+    pub method: String,
+    pub path: String, // with the query string
+    pub headers: Vec<(String, String)>,
+    #[serde(skip)]
+    pub body: Bytes, // as received, i.e. still compressed
+}
+
+#[derive(Deserialize, Serialize)]
+struct NdjsonRecord {
+    #[serde(flatten)]
+    request: CapturedRequest,
+    body: String,
+}
+
+fn encode(request: &CapturedRequest, format: CaptureFormat) -> Result<Vec<u8>> {
+    match format {
+        CaptureFormat::Ndjson => {
+            let record = NdjsonRecord {
+                request: request.clone(),
+                body: b64::STANDARD.encode(&request.body),
+            };
+            let mut content = serde_json::to_vec(&record)?;
+            content.push(b'\n');
+            Ok(content)
+        }
+        CaptureFormat::LengthPrefixed => {
+            let head = serde_json::to_vec(request)?;
+            let mut content = Vec::with_capacity(8 + head.len() + request.body.len());
+            for part in [head.as_slice(), &request.body] {
+                let length =
+                    u32::try_from(part.len()).map_err(|_| anyhow!("Record too large: {} bytes", part.len()))?;
+                content.extend_from_slice(&length.to_be_bytes());
+                content.extend_from_slice(part);
+            }
+            Ok(content)
+        }
+    }
+}
+
+// Writes the captured requests to files in a directory, starting a new file when the current one would exceed
+// the maximum size. With max_files, the oldest files written by the mock are removed.
+pub struct CaptureWriter {
+    dir: PathBuf,
+    format: CaptureFormat,
+    max_file_size: u64,
+    max_files: Option<usize>,
+    // Files of different runs don't overwrite each other, and sorting by name keeps them in order
+    prefix: String,
+    files: VecDeque<PathBuf>,
+    file_index: u64,
+    file: Option<BufWriter<File>>,
+    file_size: u64,
+}
+
+impl CaptureWriter {
+    pub fn new(dir: &Path, format: CaptureFormat, max_file_size: u64, max_files: Option<usize>) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            format,
+            max_file_size,
+            max_files,
+            prefix: format!("capture-{}", get_now_ms()),
+            files: VecDeque::new(),
+            file_index: 0,
+            file: None,
+            file_size: 0,
+        })
+    }
+
+    pub fn write(&mut self, request: &CapturedRequest) -> Result<()> {
+        let record = encode(request, self.format)?;
+        // A record larger than the maximum size gets a file of its own
+        if self.file.is_none() || (self.file_size > 0 && self.file_size + record.len() as u64 > self.max_file_size) {
+            self.rotate()?;
+        }
+
+        let file = self.file.as_mut().unwrap();
+        file.write_all(&record)?;
+        // Flushed on every request, so a killed mock doesn't leave half of a record behind
+        file.flush()?;
+        self.file_size += record.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let path = self.dir.join(format!(
+            "{}-{:06}.{}",
+            self.prefix,
+            self.file_index,
+            self.format.extension()
+        ));
+        let file = File::create(&path).with_context(|| format!("Unable to create {}", path.display()))?;
+        info!("Capturing requests to {}", path.display());
+        self.file = Some(BufWriter::new(file));
+        self.file_size = 0;
+        self.file_index += 1;
+        self.files.push_back(path);
+
+        if let Some(max_files) = self.max_files {
+            while self.files.len() > max_files {
+                let path = self.files.pop_front().unwrap();
+                if let Err(e) = fs::remove_file(&path) {
+                    error!("Unable to remove {}: {}", path.display(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CaptureWriter {
+    // Move the writer to its own thread, so the workers handling the requests don't wait on the disk.
+    // The thread ends when all the senders are dropped.
+    pub fn spawn(mut self) -> (CaptureSender, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::sync_channel::<CapturedRequest>(QUEUE_SIZE);
+        let handle = thread::spawn(move || {
+            for request in receiver {
+                if let Err(e) = self.write(&request) {
+                    error!("Unable to capture the request: {:#}", e);
+                }
+            }
+        });
+        (CaptureSender { sender }, handle)
+    }
+}
+
+#[derive(Clone)]
+pub struct CaptureSender {
+    sender: SyncSender<CapturedRequest>,
+}
+
+impl CaptureSender {
+    pub fn send(&self, request: CapturedRequest) {
+        match self.sender.try_send(request) {
+            Ok(()) => {}
+            Err(TrySendError::Full(request)) => {
+                error!(
+                    "Unable to capture the request to {}, too many requests are waiting to be written",
+                    request.path
+                );
+            }
+            Err(TrySendError::Disconnected(_)) => error!("Unable to capture the request, the writer has stopped"),
+        }
+    }
+}
+
+// Request being captured, its body is copied while the handler reads it
+pub struct PendingCapture {
+    request: CapturedRequest,
+    body: Rc<RefCell<BytesMut>>,
+}
+
+impl PendingCapture {
+    pub fn finish(mut self) -> CapturedRequest {
+        self.request.body = self.body.take().freeze();
+        self.request
+    }
+}
+
+// Start capturing the request. Used by the middleware in run_app, which only knows if it's an ingest request
+// after the routing, so the body is copied for all of them.
+pub fn start_capture(req: &mut ServiceRequest) -> PendingCapture {
+    let body = Rc::new(RefCell::new(BytesMut::new()));
+    let payload_body = body.clone();
+    let payload = req.take_payload().inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+            payload_body.borrow_mut().extend_from_slice(chunk);
+        }
+    });
+    req.set_payload(Payload::from(payload.boxed_local()));
+
+    let request = CapturedRequest {
+        timestamp: get_now_ms(),
+        method: req.method().to_string(),
+        path: req
+            .uri()
+            .path_and_query()
+            .map_or_else(|| req.path().to_string(), |path| path.to_string()),
+        headers: req
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).to_string(),
+                )
+            })
+            .collect(),
+        body: Bytes::new(),
+    };
+    PendingCapture { request, body }
+}
+
+// Read the captured requests from a capture file or a directory with capture files, ordered by their timestamps
+pub fn read_capture(path: &Path) -> Result<Vec<CapturedRequest>> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        for entry in fs::read_dir(path).with_context(|| format!("Unable to read {}", path.display()))? {
+            let file_path = entry?.path();
+            if let Some(format) = CaptureFormat::from_path(&file_path) {
+                files.push((file_path, format));
+            }
+        }
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    } else {
+        let format = CaptureFormat::from_path(path).ok_or_else(|| {
+            anyhow!(
+                "Unknown capture format of {}, expected .ndjson or .bin",
+                path.display()
+            )
+        })?;
+        files.push((PathBuf::from(path), format));
+    }
+
+    let mut requests = Vec::new();
+    for (file_path, format) in files {
+        let file = File::open(&file_path).with_context(|| format!("Unable to open {}", file_path.display()))?;
+        let reader = BufReader::new(file);
+        let file_requests = match format {
+            CaptureFormat::Ndjson => read_ndjson(reader),
+            CaptureFormat::LengthPrefixed => read_length_prefixed(reader),
+        };
+        requests.extend(file_requests.with_context(|| format!("Unable to read {}", file_path.display()))?);
+    }
+    // stable, so requests received at the same time keep their order
+    requests.sort_by_key(|request| request.timestamp);
+    Ok(requests)
+}
+
+fn read_ndjson(reader: impl BufRead) -> Result<Vec<CapturedRequest>> {
+    let mut requests = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record: NdjsonRecord = serde_json::from_str(&line)?;
+        let mut request = record.request;
+        request.body = Bytes::from(b64::STANDARD.decode(record.body)?);
+        requests.push(request);
+    }
+    Ok(requests)
+}
+
+fn read_length_prefixed(mut reader: impl Read) -> Result<Vec<CapturedRequest>> {
+    let mut requests = Vec::new();
+    loop {
+        let mut length = [0; 4];
+        match reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let mut head = vec![0; u32::from_be_bytes(length) as usize];
+        reader.read_exact(&mut head).context("Truncated record")?;
+        let mut request: CapturedRequest = serde_json::from_slice(&head)?;
+
+        reader.read_exact(&mut length).context("Truncated record")?;
+        let mut body = vec![0; u32::from_be_bytes(length) as usize];
+        reader.read_exact(&mut body).context("Truncated record")?;
+        request.body = Bytes::from(body);
+        requests.push(request);
+    }
+    Ok(requests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::Service;
+    use actix_web::{web, App, HttpResponse};
+    use std::sync::{Arc, Mutex};
+
+    fn get_request(timestamp: u64, body: &'static [u8]) -> CapturedRequest {
+        CapturedRequest {
+            timestamp,
+            method: String::from("POST"),
+            path: String::from("/receiver/v1/logs?source=test"),
+            headers: vec![(
+                String::from("content-type"),
+                String::from("application/x-protobuf"),
+            )],
+            body: Bytes::from_static(body),
+        }
+    }
+
+    fn get_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "sumologic-mock-capture-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_capture_formats() {
+        for format in [CaptureFormat::Ndjson, CaptureFormat::LengthPrefixed] {
+            let dir = get_dir(format.extension());
+            let requests = [get_request(2000, b"\x00\x01binary\n"), get_request(1000, b"")];
+            let mut writer = CaptureWriter::new(&dir, format, DEFAULT_MAX_FILE_SIZE, None).unwrap();
+            for request in requests.iter() {
+                writer.write(request).unwrap();
+            }
+
+            let file = writer.files[0].clone();
+            let result = read_capture(&file);
+            fs::remove_dir_all(&dir).unwrap();
+            // ordered by the timestamps
+            assert_eq!(result.unwrap(), vec![requests[1].clone(), requests[0].clone()]);
+        }
+    }
+
+    #[test]
+    fn test_capture_rotation() {
+        let dir = get_dir("rotation");
+        let record_size = encode(&get_request(0, b"body"), CaptureFormat::Ndjson)
+            .unwrap()
+            .len() as u64;
+        // two records per file, at most two files
+        let mut writer = CaptureWriter::new(&dir, CaptureFormat::Ndjson, 2 * record_size, Some(2)).unwrap();
+        for timestamp in 1..=5 {
+            writer.write(&get_request(timestamp, b"body")).unwrap();
+        }
+
+        let files = fs::read_dir(&dir).unwrap().count();
+        let result = read_capture(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, 2);
+        let timestamps: Vec<u64> = result
+            .unwrap()
+            .iter()
+            .map(|request| request.timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_capture_thread() {
+        let dir = get_dir("thread");
+        let writer = CaptureWriter::new(&dir, CaptureFormat::Ndjson, DEFAULT_MAX_FILE_SIZE, None).unwrap();
+        let (sender, handle) = writer.spawn();
+        for timestamp in 1..=3 {
+            sender.clone().send(get_request(timestamp, b"body"));
+        }
+        // the thread writes everything sent before stopping
+        drop(sender);
+        handle.join().unwrap();
+
+        let result = read_capture(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_read_truncated_capture() {
+        let mut content = encode(&get_request(0, b"body"), CaptureFormat::LengthPrefixed).unwrap();
+        content.truncate(content.len() - 1);
+        assert!(read_length_prefixed(content.as_slice()).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_start_capture() {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let app_captured = captured.clone();
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap_fn(move |mut req, srv| {
+                    let capture = start_capture(&mut req);
+                    let captured = app_captured.clone();
+                    let response = srv.call(req);
+                    async move {
+                        let response = response.await?;
+                        captured.lock().unwrap().push(capture.finish());
+                        Ok(response)
+                    }
+                })
+                .route(
+                    "/receiver",
+                    web::post().to(|body: web::Bytes| async move { HttpResponse::Ok().body(body) }),
+                ),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::post()
+            .uri("/receiver?source=test")
+            .insert_header(("X-Sumo-Category", "test"))
+            .set_payload("log line")
+            .to_request();
+        let body = actix_web::test::call_and_read_body(&app, req).await;
+        // the handler still gets the whole body
+        assert_eq!(body, "log line");
+
+        let captured = captured.lock().unwrap();
+        assert_eq!(captured.len(), 1);
+        assert_eq!(captured[0].method, "POST");
+        assert_eq!(captured[0].path, "/receiver?source=test");
+        assert!(captured[0]
+            .headers
+            .contains(&(String::from("x-sumo-category"), String::from("test"))));
+        assert_eq!(captured[0].body, "log line");
+    }
+}
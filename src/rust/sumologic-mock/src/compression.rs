@@ -0,0 +1,320 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+
+// Content encodings accepted on the ingest endpoints
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+    Zstd,
+    Snappy,
+    SnappyFramed,
+}
+
+impl ContentEncoding {
+    // Parse the value of the Content-Encoding header. An empty value means no encoding.
+    pub fn from_header_value(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "identity" => Ok(ContentEncoding::Identity),
+            "gzip" | "x-gzip" => Ok(ContentEncoding::Gzip),
+            "deflate" => Ok(ContentEncoding::Deflate),
+            "zstd" => Ok(ContentEncoding::Zstd),
+            "snappy" => Ok(ContentEncoding::Snappy),
+            "x-snappy-framed" => Ok(ContentEncoding::SnappyFramed),
+            other => Err(anyhow!("Unsupported Content-Encoding: {}", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Identity => "identity",
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+            ContentEncoding::Zstd => "zstd",
+            ContentEncoding::Snappy => "snappy",
+            ContentEncoding::SnappyFramed => "x-snappy-framed",
+        }
+    }
+}
+
+// Decompressed bodies are limited to the size of the received payloads (200 MiB)
+pub const MAX_DECOMPRESSED_SIZE: usize = 200 << 20;
+
+// Returned by decompress when the body would be larger than the limit
+#[derive(Debug)]
+pub struct LimitExceeded {
+    pub limit: usize,
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "decompressed body is larger than {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+// Decompress the body, reading at most one byte over the limit to detect bodies which are too large
+pub fn decompress(encoding: ContentEncoding, body: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let take_limit = limit as u64 + 1;
+    let mut decompressed = Vec::new();
+    match encoding {
+        ContentEncoding::Identity => decompressed.extend_from_slice(body),
+        ContentEncoding::Gzip => {
+            flate2::read::MultiGzDecoder::new(body)
+                .take(take_limit)
+                .read_to_end(&mut decompressed)?;
+        }
+        ContentEncoding::Deflate => {
+            // HTTP deflate is zlib-wrapped, but some clients send a raw deflate stream
+            if flate2::read::ZlibDecoder::new(body)
+                .take(take_limit)
+                .read_to_end(&mut decompressed)
+                .is_err()
+            {
+                decompressed.clear();
+                flate2::read::DeflateDecoder::new(body)
+                    .take(take_limit)
+                    .read_to_end(&mut decompressed)?;
+            }
+        }
+        ContentEncoding::Zstd => {
+            zstd::stream::read::Decoder::new(body)?
+                .take(take_limit)
+                .read_to_end(&mut decompressed)?;
+        }
+        ContentEncoding::Snappy => {
+            // the block format starts with the decompressed length, so check it before allocating
+            if snap::raw::decompress_len(body)? > limit {
+                return Err(LimitExceeded { limit }.into());
+            }
+            decompressed = snap::raw::Decoder::new().decompress_vec(body)?;
+        }
+        ContentEncoding::SnappyFramed => {
+            snap::read::FrameDecoder::new(body)
+                .take(take_limit)
+                .read_to_end(&mut decompressed)?;
+        }
+    }
+    if decompressed.len() > limit {
+        return Err(LimitExceeded { limit }.into());
+    }
+    Ok(decompressed)
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct EncodingStats {
+    pub request_count: u64,
+    pub compressed_byte_count: u64,
+    pub uncompressed_byte_count: u64,
+}
+
+impl EncodingStats {
+    fn new() -> Self {
+        Self {
+            request_count: 0,
+            compressed_byte_count: 0,
+            uncompressed_byte_count: 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct EncodingStatsRepository {
+    pub total: EncodingStats,
+    pub encodings: HashMap<String, EncodingStats>,
+}
+
+impl EncodingStatsRepository {
+    pub fn new() -> Self {
+        Self {
+            total: EncodingStats::new(),
+            encodings: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, encoding: ContentEncoding, compressed_byte_count: u64, uncompressed_byte_count: u64) {
+        // update total stats
+        self.total.request_count += 1;
+        self.total.compressed_byte_count += compressed_byte_count;
+        self.total.uncompressed_byte_count += uncompressed_byte_count;
+
+        // update per encoding stats
+        let stats = self
+            .encodings
+            .entry(encoding.as_str().to_string())
+            .or_insert(EncodingStats::new());
+        stats.request_count += 1;
+        stats.compressed_byte_count += compressed_byte_count;
+        stats.uncompressed_byte_count += uncompressed_byte_count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const BODY: &[u8] = b"first log line\nsecond log line\nthird log line";
+
+    #[test]
+    fn test_from_header_value() {
+        assert_eq!(
+            ContentEncoding::from_header_value("").unwrap(),
+            ContentEncoding::Identity
+        );
+        assert_eq!(
+            ContentEncoding::from_header_value("GZIP").unwrap(),
+            ContentEncoding::Gzip
+        );
+        assert_eq!(
+            ContentEncoding::from_header_value(" deflate ").unwrap(),
+            ContentEncoding::Deflate
+        );
+        assert_eq!(
+            ContentEncoding::from_header_value("zstd").unwrap(),
+            ContentEncoding::Zstd
+        );
+        assert_eq!(
+            ContentEncoding::from_header_value("snappy").unwrap(),
+            ContentEncoding::Snappy
+        );
+        assert!(ContentEncoding::from_header_value("br").is_err());
+    }
+
+    #[test]
+    fn test_decompress() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(BODY).unwrap();
+        assert_eq!(
+            decompress(
+                ContentEncoding::Gzip,
+                &gzip.finish().unwrap(),
+                MAX_DECOMPRESSED_SIZE
+            )
+            .unwrap(),
+            BODY
+        );
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(BODY).unwrap();
+        assert_eq!(
+            decompress(
+                ContentEncoding::Deflate,
+                &zlib.finish().unwrap(),
+                MAX_DECOMPRESSED_SIZE
+            )
+            .unwrap(),
+            BODY
+        );
+
+        let mut raw_deflate = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        raw_deflate.write_all(BODY).unwrap();
+        assert_eq!(
+            decompress(
+                ContentEncoding::Deflate,
+                &raw_deflate.finish().unwrap(),
+                MAX_DECOMPRESSED_SIZE
+            )
+            .unwrap(),
+            BODY
+        );
+
+        let zstd_body = zstd::stream::encode_all(BODY, 0).unwrap();
+        assert_eq!(
+            decompress(ContentEncoding::Zstd, &zstd_body, MAX_DECOMPRESSED_SIZE).unwrap(),
+            BODY
+        );
+
+        let snappy_body = snap::raw::Encoder::new().compress_vec(BODY).unwrap();
+        assert_eq!(
+            decompress(ContentEncoding::Snappy, &snappy_body, MAX_DECOMPRESSED_SIZE).unwrap(),
+            BODY
+        );
+
+        let mut snappy_framed = snap::write::FrameEncoder::new(Vec::new());
+        snappy_framed.write_all(BODY).unwrap();
+        assert_eq!(
+            decompress(
+                ContentEncoding::SnappyFramed,
+                &snappy_framed.into_inner().unwrap(),
+                MAX_DECOMPRESSED_SIZE
+            )
+            .unwrap(),
+            BODY
+        );
+
+        assert_eq!(
+            decompress(ContentEncoding::Identity, BODY, MAX_DECOMPRESSED_SIZE).unwrap(),
+            BODY
+        );
+        assert!(decompress(ContentEncoding::Gzip, BODY, MAX_DECOMPRESSED_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        let is_limit_exceeded = |result: Result<Vec<u8>>| result.is_err_and(|e| e.is::<LimitExceeded>());
+        let limit = BODY.len() - 1;
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(BODY).unwrap();
+        let gzip_body = gzip.finish().unwrap();
+        assert!(is_limit_exceeded(decompress(
+            ContentEncoding::Gzip,
+            &gzip_body,
+            limit
+        )));
+        assert_eq!(
+            decompress(ContentEncoding::Gzip, &gzip_body, BODY.len()).unwrap(),
+            BODY
+        );
+
+        let zstd_body = zstd::stream::encode_all(BODY, 0).unwrap();
+        assert!(is_limit_exceeded(decompress(
+            ContentEncoding::Zstd,
+            &zstd_body,
+            limit
+        )));
+
+        let snappy_body = snap::raw::Encoder::new().compress_vec(BODY).unwrap();
+        assert!(is_limit_exceeded(decompress(
+            ContentEncoding::Snappy,
+            &snappy_body,
+            limit
+        )));
+
+        assert!(is_limit_exceeded(decompress(
+            ContentEncoding::Identity,
+            BODY,
+            limit
+        )));
+    }
+
+    #[test]
+    fn test_encoding_stats_repository() {
+        let mut stats = EncodingStatsRepository::new();
+        stats.update(ContentEncoding::Gzip, 10, 100);
+        stats.update(ContentEncoding::Gzip, 20, 200);
+        stats.update(ContentEncoding::Identity, 5, 5);
+
+        assert_eq!(
+            stats.total,
+            EncodingStats {
+                request_count: 3,
+                compressed_byte_count: 35,
+                uncompressed_byte_count: 305,
+            }
+        );
+        assert_eq!(
+            stats.encodings["gzip"],
+            EncodingStats {
+                request_count: 2,
+                compressed_byte_count: 30,
+                uncompressed_byte_count: 300,
+            }
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use std::fs;
+use std::path::Path;
+
+use actix_web::http::StatusCode;
+use anyhow::{anyhow, Context, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::request_stats::normalize_content_type;
+
+// Rule injecting an error response for matching requests. All the given conditions have to be met.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct FaultRule {
+    // Route as in the request stats, e.g. /receiver/v1/logs or default. Any route when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    // Compared without parameters like charset, case-insensitively. Any content type when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub status: u16,
+    // Value of the Retry-After header in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    // Probability of failing a request, 1 when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+    // Number of matching requests let through before failing any
+    #[serde(default)]
+    pub after: u64,
+    // Number of matching requests failed after that, all of them when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    // Time window in seconds since the profile was loaded, the end is exclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<u64>,
+}
+
+impl FaultRule {
+    // Validate the rule and get its status code
+    fn validate(&self) -> Result<StatusCode> {
+        let status = StatusCode::from_u16(self.status)
+            .ok()
+            .filter(|status| status.is_client_error() || status.is_server_error())
+            .ok_or_else(|| anyhow!("Invalid status {}, expected 4xx or 5xx", self.status))?;
+        if let Some(probability) = self.probability {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(anyhow!(
+                    "Invalid probability {}, expected a number from 0 to 1",
+                    probability
+                ));
+            }
+        }
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if start >= end {
+                return Err(anyhow!(
+                    "Invalid time window, start {} is not before end {}",
+                    start,
+                    end
+                ));
+            }
+        }
+        Ok(status)
+    }
+
+    fn matches(&self, route: &str, content_type: &str) -> bool {
+        let content_type = normalize_content_type(content_type);
+        self.route.as_ref().is_none_or(|rule_route| rule_route == route)
+            && self
+                .content_type
+                .as_ref()
+                .is_none_or(|rule_content_type| rule_content_type.eq_ignore_ascii_case(&content_type))
+    }
+
+    fn is_active(&self, elapsed_seconds: u64) -> bool {
+        self.start.is_none_or(|start| elapsed_seconds >= start) && self.end.is_none_or(|end| elapsed_seconds < end)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct FaultProfileConfig {
+    pub rules: Vec<FaultRule>,
+}
+
+impl FaultProfileConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read(path).with_context(|| format!("Unable to read {}", path.display()))?;
+        serde_json::from_slice(&content).with_context(|| format!("Unable to parse {}", path.display()))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Fault {
+    pub status: StatusCode,
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct FaultRuleStatus {
+    #[serde(flatten)]
+    pub rule: FaultRule,
+    // Requests matching the route and the content type within the time window
+    pub matched: u64,
+    pub injected: u64,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct FaultProfileStatus {
+    pub elapsed_seconds: u64,
+    pub rules: Vec<FaultRuleStatus>,
+}
+
+struct ActiveRule {
+    rule: FaultRule,
+    status: StatusCode, // validated status of the rule
+    matched: u64,
+    injected: u64,
+}
+
+// Fault profile with the state of its rules. Time windows and sequences start when the profile is loaded.
+#[derive(Default)]
+pub struct FaultProfile {
+    rules: Vec<ActiveRule>,
+    loaded_timestamp: u64, // milliseconds epoch timestamp
+}
+
+impl FaultProfile {
+    pub fn new(config: FaultProfileConfig, now_ms: u64) -> Result<Self> {
+        let mut rules = Vec::with_capacity(config.rules.len());
+        for (i, rule) in config.rules.into_iter().enumerate() {
+            let status = rule
+                .validate()
+                .with_context(|| format!("Invalid fault rule {}", i))?;
+            rules.push(ActiveRule {
+                rule,
+                status,
+                matched: 0,
+                injected: 0,
+            });
+        }
+        Ok(Self {
+            rules,
+            loaded_timestamp: now_ms,
+        })
+    }
+
+    // Get the fault to inject for the request, if any. Rules are checked in order and the first one
+    // failing the request wins, the following ones don't see it.
+    pub fn get_fault(&mut self, route: &str, content_type: &str, now_ms: u64, rng: &mut impl Rng) -> Option<Fault> {
+        let elapsed_seconds = now_ms.saturating_sub(self.loaded_timestamp) / 1000;
+        for active in self.rules.iter_mut() {
+            let rule = &active.rule;
+            if !rule.matches(route, content_type) || !rule.is_active(elapsed_seconds) {
+                continue;
+            }
+
+            active.matched += 1;
+            if active.matched <= rule.after
+                || rule
+                    .count
+                    .is_some_and(|count| active.matched > rule.after.saturating_add(count))
+            {
+                continue;
+            }
+            if rule
+                .probability
+                .is_some_and(|probability| !rng.random_bool(probability))
+            {
+                continue;
+            }
+
+            active.injected += 1;
+            return Some(Fault {
+                status: active.status,
+                retry_after: rule.retry_after,
+            });
+        }
+        None
+    }
+
+    pub fn status(&self, now_ms: u64) -> FaultProfileStatus {
+        FaultProfileStatus {
+            elapsed_seconds: now_ms.saturating_sub(self.loaded_timestamp) / 1000,
+            rules: self
+                .rules
+                .iter()
+                .map(|active| FaultRuleStatus {
+                    rule: active.rule.clone(),
+                    matched: active.matched,
+                    injected: active.injected,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTE: &str = "/receiver/v1/logs";
+    const CONTENT_TYPE: &str = "application/x-protobuf";
+
+    fn get_profile(rules: Vec<FaultRule>) -> FaultProfile {
+        FaultProfile::new(FaultProfileConfig { rules }, 0).unwrap()
+    }
+
+    fn get_statuses(profile: &mut FaultProfile, count: usize, now_ms: u64) -> Vec<Option<u16>> {
+        (0..count)
+            .map(|_| {
+                profile
+                    .get_fault(ROUTE, CONTENT_TYPE, now_ms, &mut rand::rng())
+                    .map(|fault| fault.status.as_u16())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fault_rule_matches() {
+        let rule = FaultRule {
+            route: Some(String::from(ROUTE)),
+            content_type: Some(String::from("Application/JSON")),
+            status: 503,
+            ..Default::default()
+        };
+        assert!(rule.matches(ROUTE, "application/json; charset=utf-8"));
+        assert!(!rule.matches(ROUTE, CONTENT_TYPE));
+        assert!(!rule.matches("default", "application/json"));
+        assert!(FaultRule::default().matches("default", ""));
+    }
+
+    #[test]
+    fn test_fault_sequence() {
+        // let two requests through, fail the next three with 429 and the rest with 503
+        let mut profile = get_profile(vec![
+            FaultRule {
+                status: 429,
+                retry_after: Some(10),
+                after: 2,
+                count: Some(3),
+                ..Default::default()
+            },
+            // requests failed by an earlier rule aren't counted here
+            FaultRule {
+                status: 503,
+                after: 2,
+                ..Default::default()
+            },
+        ]);
+        assert_eq!(
+            get_statuses(&mut profile, 7, 0),
+            vec![None, None, Some(429), Some(429), Some(429), Some(503), Some(503)]
+        );
+        assert_eq!(
+            profile.get_fault(ROUTE, CONTENT_TYPE, 0, &mut rand::rng()),
+            Some(Fault {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: None,
+            })
+        );
+
+        let status = profile.status(0);
+        assert_eq!((status.rules[0].matched, status.rules[0].injected), (8, 3));
+        assert_eq!((status.rules[1].matched, status.rules[1].injected), (5, 3));
+    }
+
+    #[test]
+    fn test_fault_large_count() {
+        let mut profile = get_profile(vec![FaultRule {
+            status: 503,
+            after: 1,
+            count: Some(u64::MAX),
+            ..Default::default()
+        }]);
+        assert_eq!(
+            get_statuses(&mut profile, 3, 0),
+            vec![None, Some(503), Some(503)]
+        );
+    }
+
+    #[test]
+    fn test_fault_window_and_probability() {
+        let mut profile = get_profile(vec![
+            FaultRule {
+                status: 503,
+                start: Some(60),
+                end: Some(120),
+                ..Default::default()
+            },
+            FaultRule {
+                status: 401,
+                probability: Some(0.0),
+                ..Default::default()
+            },
+        ]);
+        assert_eq!(get_statuses(&mut profile, 1, 59_999), vec![None]);
+        assert_eq!(
+            get_statuses(&mut profile, 2, 60_000),
+            vec![Some(503), Some(503)]
+        );
+        assert_eq!(get_statuses(&mut profile, 1, 120_000), vec![None]);
+        assert_eq!(profile.status(120_000).rules[1].matched, 2);
+    }
+
+    #[test]
+    fn test_fault_profile_validation() {
+        for rule in [
+            FaultRule {
+                status: 200,
+                ..Default::default()
+            },
+            FaultRule {
+                status: 1000,
+                ..Default::default()
+            },
+            FaultRule {
+                status: 503,
+                probability: Some(1.5),
+                ..Default::default()
+            },
+            FaultRule {
+                status: 503,
+                start: Some(10),
+                end: Some(10),
+                ..Default::default()
+            },
+        ] {
+            assert!(FaultProfile::new(FaultProfileConfig { rules: vec![rule] }, 0).is_err());
+        }
+    }
+}
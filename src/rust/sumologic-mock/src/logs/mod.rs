@@ -1,20 +1,27 @@
 use crate::time;
 use anyhow::Result;
 use fancy_regex::Regex;
-use log::warn;
+use log::debug;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::{BTreeMap, HashMap};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::net::IpAddr;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex};
 
 use crate::metadata::Metadata;
+use crate::options::RetentionLimits;
+use timestamp::{get_timestamp_from_body, TimestampField, RECEIVE_TIME_SOURCE};
 
-#[derive(Clone, Debug, PartialEq)]
+pub mod timestamp;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LogStats {
     pub message_count: u64,
     pub byte_count: u64,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct LogStatsRepository {
     pub total: LogStats,
     pub ipaddr: HashMap<IpAddr, LogStats>,
@@ -58,102 +65,441 @@ impl LogStatsRepository {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LogMessage {
-    // This structure is intended to house more data as we add APIs requiring it
-    metadata: Metadata,
+    pub body: String,
+    pub metadata: Metadata,
+    pub timestamp: u64,           // milliseconds epoch timestamp the message is indexed by
+    pub timestamp_source: String, // how the timestamp was found, e.g. `json:log.time`, `klog` or `receive_time`
+    pub receive_timestamp: u64,   // milliseconds epoch timestamp of when the message was received
+    pub source_ip: IpAddr,
+}
+
+impl LogMessage {
+    pub fn byte_count(&self) -> u64 {
+        let metadata_byte_count: usize = self
+            .metadata
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+        (self.body.len() + metadata_byte_count) as u64
+    }
+}
+
+// Log which has been received, but not stored yet.
+// The timestamp is extracted from the body if it's not known upfront, e.g. from an OTLP log record.
+pub struct ReceivedLog {
+    pub body: String,
+    pub metadata: Metadata,
+    pub timestamp: Option<(u64, String)>, // milliseconds epoch timestamp and its source
+}
+
+// Predicates on the log body. All the provided predicates have to match.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct BodyQuery {
+    pub body_contains: Option<String>,
+    pub body_icontains: Option<String>, // case-insensitive substring
+    pub body_regex: Option<String>,     // matches anywhere in the body
+}
+
+// Body predicates prepared once per query
+struct BodyMatcher<'a> {
+    contains: Option<&'a str>,
+    icontains: Option<String>,
+    regex: Option<Arc<Regex>>,
+}
+
+impl BodyMatcher<'_> {
+    fn matches(&self, body: &str) -> Result<bool> {
+        if let Some(contains) = self.contains {
+            if !body.contains(contains) {
+                return Ok(false);
+            }
+        }
+        if let Some(icontains) = &self.icontains {
+            if !body.to_lowercase().contains(icontains.as_str()) {
+                return Ok(false);
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(body)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+const BODY_PATH_PREFIX: &str = "body.";
+
+// Get the value at a dot separated path in a JSON value. Array elements are selected by their index.
+fn get_json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, segment| match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(array) => array.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+// Number of regexes kept in a cache, the least recently used ones are dropped first
+const REGEX_CACHE_CAPACITY: usize = 1000;
+
+// Least recently used cache
+struct LruCache<V> {
+    entries: HashMap<String, (V, u64)>, // values with the tick of their last use
+    usage: BTreeMap<u64, String>,       // keys by the tick of their last use
+    tick: u64,
+    capacity: usize,
+}
+
+impl<V: Clone> LruCache<V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            usage: BTreeMap::new(),
+            tick: 0,
+            capacity,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<V> {
+        let tick = self.tick + 1;
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.usage.remove(last_used);
+        *last_used = tick;
+        self.usage.insert(tick, key.to_string());
+        self.tick = tick;
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: V) {
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.usage.remove(&last_used);
+        }
+        self.usage.insert(self.tick, key);
+        while self.entries.len() > self.capacity {
+            let Some((_, key)) = self.usage.pop_first() else {
+                break;
+            };
+            self.entries.remove(&key);
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[cfg(test)]
+    fn peek(&self, key: &str) -> Option<&V> {
+        self.entries.get(key).map(|(value, _)| value)
+    }
 }
 
 #[derive(Clone)]
 struct RegexCache {
-    cache: Arc<RwLock<HashMap<String, Arc<Regex>>>>,
+    cache: Arc<Mutex<LruCache<Arc<Regex>>>>,
     enabled: bool,
+    anchored: bool, // whether the regexes have to match the whole string
 }
 
 impl RegexCache {
-    pub fn new(enabled: bool) -> RegexCache {
+    pub fn new(enabled: bool, anchored: bool) -> RegexCache {
         RegexCache {
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache: Arc::new(Mutex::new(LruCache::new(REGEX_CACHE_CAPACITY))),
             enabled,
+            anchored,
         }
     }
 
     pub fn get(&self, value: &str) -> Result<Arc<Regex>> {
-        let map = self.cache.read().unwrap();
-        if let Some(regex) = map.get(value) {
-            return Ok(regex.clone());
+        if let Some(regex) = self.cache.lock().unwrap().get(value) {
+            return Ok(regex);
         }
-        // Drop the read lock manually to avoid deadlocks when accessing the write lock.
-        drop(map);
-        let regex = Arc::new(Regex::new(&format!("^{}$", value))?);
-        let mut map = self.cache.write().unwrap();
-        map.insert(value.to_string(), regex.clone());
+        // The lock isn't held while compiling, so other queries aren't blocked
+        let regex = if self.anchored {
+            Arc::new(Regex::new(&format!("^{}$", value))?)
+        } else {
+            Arc::new(Regex::new(value)?)
+        };
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(value.to_string(), regex.clone());
         Ok(regex)
     }
 }
 
 #[derive(Clone)]
 pub struct LogRepository {
-    pub messages_by_ts: BTreeMap<u64, Vec<LogMessage>>, // indexed by timestamp to make range queries possible
+    pub messages_by_ts: BTreeMap<u64, VecDeque<LogMessage>>, // indexed by timestamp to make range queries possible
+    // Timestamps the logs are indexed by, in the order of receiving. Messages with the same timestamp
+    // are kept in the order of receiving too, so the first one is always the oldest.
+    receive_order: VecDeque<u64>,
+    byte_count: u64,
+    pub evicted_count: u64,
     regex_cache: RegexCache,
+    body_regex_cache: RegexCache,
 }
 
 impl LogRepository {
     pub fn new() -> Self {
         return Self {
             messages_by_ts: BTreeMap::new(),
-            regex_cache: RegexCache::new(true),
+            receive_order: VecDeque::new(),
+            byte_count: 0,
+            evicted_count: 0,
+            regex_cache: RegexCache::new(true, true),
+            body_regex_cache: RegexCache::new(true, false),
         };
     }
 
+    pub fn len(&self) -> usize {
+        self.receive_order.len()
+    }
+
+    // Approximate size of the stored logs, their bodies and metadata
+    pub fn byte_count(&self) -> u64 {
+        self.byte_count
+    }
+
     // This function is a helper to make repository creation in tests easier
     #[cfg(test)]
     pub fn from_raw_logs(raw_logs: Vec<(String, Metadata)>) -> Result<Self, anyhow::Error> {
         let mut repository = Self::new();
         for (body, metadata) in raw_logs {
-            repository.add_log_message(body, metadata)
+            repository.add_log_message(body, metadata, IpAddr::from([127, 0, 0, 1]), &[])
         }
         return Ok(repository);
     }
 
-    pub fn add_log_message(&mut self, body: String, metadata: Metadata) {
+    pub fn add_log_message(
+        &mut self,
+        body: String,
+        metadata: Metadata,
+        source_ip: IpAddr,
+        timestamp_fields: &[TimestampField],
+    ) {
+        let log = ReceivedLog {
+            body,
+            metadata,
+            timestamp: None,
+        };
+        self.add_received_log(log, source_ip, timestamp_fields)
+    }
+
+    pub fn add_received_log(&mut self, log: ReceivedLog, source_ip: IpAddr, timestamp_fields: &[TimestampField]) {
+        let receive_timestamp = time::get_now_ms();
         // add the log message to the time index
-        let timestamp = match get_timestamp_from_body(&body) {
+        let (timestamp, timestamp_source) = match log
+            .timestamp
+            .or_else(|| get_timestamp_from_body(&log.body, timestamp_fields))
+        {
             Some(ts) => ts,
             None => {
-                warn!("Couldn't find timestamp in log line {}", body);
-                time::get_now_ms() // use current system time if no timestamp found
+                debug!("Couldn't find timestamp in log line {}", log.body);
+                // use current system time if no timestamp found
+                (receive_timestamp, String::from(RECEIVE_TIME_SOURCE))
             }
         };
-        let messages = self.messages_by_ts.entry(timestamp).or_insert(Vec::new());
-        messages.push(LogMessage { metadata });
+        self.insert(LogMessage {
+            body: log.body,
+            metadata: log.metadata,
+            timestamp,
+            timestamp_source,
+            receive_timestamp,
+            source_ip,
+        });
+    }
+
+    fn insert(&mut self, message: LogMessage) {
+        self.receive_order.push_back(message.timestamp);
+        self.byte_count += message.byte_count();
+        self.messages_by_ts
+            .entry(message.timestamp)
+            .or_default()
+            .push_back(message);
     }
 
-    // Count logs with timestamps in the provided range, with the provided metadata. Empty values
+    // Evict the oldest logs until the limits are met. Returns the number of evicted logs.
+    pub fn evict(&mut self, limits: &RetentionLimits, now_ms: u64) -> u64 {
+        let mut evicted_count = 0;
+        while let Some(&timestamp) = self.receive_order.front() {
+            let Some(messages) = self.messages_by_ts.get_mut(&timestamp) else {
+                self.receive_order.pop_front();
+                continue;
+            };
+            let Some(oldest) = messages.front() else {
+                self.messages_by_ts.remove(&timestamp);
+                continue;
+            };
+            if !limits.is_exceeded(self.receive_order.len(), self.byte_count)
+                && !limits.is_expired(oldest.receive_timestamp, now_ms)
+            {
+                break;
+            }
+
+            let message = messages.pop_front().unwrap();
+            if messages.is_empty() {
+                self.messages_by_ts.remove(&timestamp);
+            }
+            self.receive_order.pop_front();
+            self.byte_count -= message.byte_count();
+            evicted_count += 1;
+        }
+        self.evicted_count += evicted_count;
+        evicted_count
+    }
+
+    // Replace the stored logs, e.g. with the ones restored from a snapshot
+    pub fn restore(&mut self, mut logs: Vec<LogMessage>) {
+        self.messages_by_ts.clear();
+        self.receive_order.clear();
+        self.byte_count = 0;
+        // stable, so logs received at the same time keep their order
+        logs.sort_by_key(|log| log.receive_timestamp);
+        for log in logs {
+            self.insert(log);
+        }
+    }
+
+    // Rebuild the receive order and the size after removing logs
+    fn rebuild_index(&mut self) {
+        let mut order: Vec<(u64, u64)> = self
+            .messages_by_ts
+            .iter()
+            .flat_map(|(&timestamp, messages)| {
+                messages
+                    .iter()
+                    .map(move |message| (message.receive_timestamp, timestamp))
+            })
+            .collect();
+        order.sort_by_key(|(receive_timestamp, _)| *receive_timestamp);
+        self.receive_order = order.into_iter().map(|(_, timestamp)| timestamp).collect();
+        self.byte_count = self.iter().map(|message| message.byte_count()).sum();
+    }
+
+    // All the stored logs, in the same order as in get_messages
+    pub fn iter(&self) -> impl Iterator<Item = &LogMessage> {
+        self.messages_by_ts.values().flatten()
+    }
+
+    // Count logs with timestamps in the provided range, with the provided metadata and body. Empty values
     // in the metadata map mean we just check if the key is there.
-    pub fn get_message_count(&self, from_ts: u64, to_ts: u64, metadata_query: HashMap<&str, &str>) -> Result<usize> {
-        let mut count = 0;
+    pub fn get_message_count(
+        &self,
+        from_ts: u64,
+        to_ts: u64,
+        metadata_query: HashMap<&str, &str>,
+        body_query: &BodyQuery,
+    ) -> Result<usize> {
+        Ok(self
+            .get_messages(from_ts, to_ts, metadata_query, body_query)?
+            .len())
+    }
+
+    // Get logs with timestamps in the provided range, with the provided metadata and body, ordered by timestamp.
+    // Messages with the same timestamp are in the order they were received.
+    pub fn get_messages(
+        &self,
+        from_ts: u64,
+        to_ts: u64,
+        metadata_query: HashMap<&str, &str>,
+        body_query: &BodyQuery,
+    ) -> Result<Vec<&LogMessage>> {
+        let body_matcher = self.get_body_matcher(body_query)?;
+
+        let mut result = Vec::new();
         let entries = self.messages_by_ts.range(from_ts..to_ts);
         for (_, messages) in entries {
             for message in messages {
-                if self.metadata_matches(&metadata_query, &message.metadata)? {
-                    count += 1
+                if self.metadata_matches(&metadata_query, message)? && body_matcher.matches(&message.body)? {
+                    result.push(message)
                 }
             }
         }
-        return Ok(count);
+        Ok(result)
+    }
+
+    // Remove logs matching the same conditions as in get_messages. Returns the number of removed logs.
+    // Nothing is removed if matching fails, e.g. because of an invalid regex.
+    pub fn remove_messages(
+        &mut self,
+        from_ts: u64,
+        to_ts: u64,
+        metadata_query: HashMap<&str, &str>,
+        body_query: &BodyQuery,
+    ) -> Result<usize> {
+        let body_matcher = self.get_body_matcher(body_query)?;
+
+        let mut matches: Vec<(u64, Vec<bool>)> = Vec::new();
+        for (&timestamp, messages) in self.messages_by_ts.range(from_ts..to_ts) {
+            let mut messages_matches = Vec::with_capacity(messages.len());
+            for message in messages {
+                messages_matches
+                    .push(self.metadata_matches(&metadata_query, message)? && body_matcher.matches(&message.body)?);
+            }
+            matches.push((timestamp, messages_matches));
+        }
+
+        let mut removed_count = 0;
+        for (timestamp, messages_matches) in matches {
+            let Some(messages) = self.messages_by_ts.get_mut(&timestamp) else {
+                continue;
+            };
+            removed_count += messages_matches.iter().filter(|&&matched| matched).count();
+            let mut messages_matches = messages_matches.into_iter();
+            messages.retain(|_| !messages_matches.next().unwrap_or(false));
+            if messages.is_empty() {
+                self.messages_by_ts.remove(&timestamp);
+            }
+        }
+        if removed_count > 0 {
+            self.rebuild_index();
+        }
+        Ok(removed_count)
+    }
+
+    fn get_body_matcher<'a>(&self, body_query: &'a BodyQuery) -> Result<BodyMatcher<'a>> {
+        Ok(BodyMatcher {
+            contains: body_query.body_contains.as_deref(),
+            icontains: body_query
+                .body_icontains
+                .as_ref()
+                .map(|value| value.to_lowercase()),
+            regex: match &body_query.body_regex {
+                Some(value) if self.body_regex_cache.enabled => Some(self.body_regex_cache.get(value)?),
+                Some(value) => Some(Arc::new(Regex::new(value)?)),
+                None => None,
+            },
+        })
     }
 
     // Check if log metadata matches a query in the form of a map of string to string.
     // There's a match if the metadata contains the same keys and values as the query.
     // The query value of an empty string has special meaning, it matches anything.
-    fn metadata_matches(&self, query: &HashMap<&str, &str>, target: &Metadata) -> Result<bool> {
+    // Keys starting with `body.` which aren't in the metadata are paths in the JSON body, e.g. `body.log.level`.
+    fn metadata_matches(&self, query: &HashMap<&str, &str>, message: &LogMessage) -> Result<bool> {
+        // the body is parsed only if it's queried, and at most once
+        let mut parsed_body: Option<Option<Value>> = None;
         for (key, value) in query.iter() {
-            let target_value = match target.get(*key) {
+            let target_value = match message.metadata.get(*key) {
                 // get the value from the target
-                Some(v) => v,
-                None => return Ok(false), // key not present, no match
+                Some(v) => Cow::Borrowed(v.as_str()),
+                None => match key.strip_prefix(BODY_PATH_PREFIX) {
+                    Some(path) => {
+                        let body = parsed_body.get_or_insert_with(|| serde_json::from_str(&message.body).ok());
+                        match body.as_ref().and_then(|body| get_json_path(body, path)) {
+                            Some(Value::String(v)) => Cow::Borrowed(v.as_str()),
+                            Some(v) => Cow::Owned(v.to_string()),
+                            None => return Ok(false), // path not present, no match
+                        }
+                    }
+                    None => return Ok(false), // key not present, no match
+                },
             };
+            let target_value = target_value.as_ref();
             if value.len() > 0 {
                 // TODO: regex support is available, so we can remove support for "", but it will break the API
                 // always match if query value is ""
@@ -178,18 +524,6 @@ impl LogRepository {
     }
 }
 
-// Try to get the timestamp from the log body
-// We only handle the case where the log is a JSON string representing a map with "timestamp" as a
-// top-level key.
-fn get_timestamp_from_body(body: &str) -> Option<u64> {
-    let parsed_body: Value = match serde_json::from_str(body) {
-        Ok(result) => result,
-        Err(_) => return None,
-    };
-    let timestamp = &parsed_body["timestamp"];
-    return timestamp.as_u64();
-}
-
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -228,9 +562,20 @@ mod tests {
         let mut repository = LogRepository::new();
         let body = r#"{"log": "Log message", "timestamp": 1}"#;
 
-        repository.add_log_message(body.to_string(), Metadata::new());
+        repository.add_log_message(
+            body.to_string(),
+            Metadata::new(),
+            IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)),
+            &[],
+        );
 
         assert_eq!(repository.messages_by_ts.len(), 1);
+        let message = &repository.messages_by_ts[&1][0];
+        assert_eq!(message.body, body);
+        assert_eq!(message.timestamp, 1);
+        assert_eq!(message.timestamp_source, "json:timestamp");
+        assert_eq!(message.source_ip, IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)));
+        assert!(message.receive_timestamp > 0);
     }
 
     #[test]
@@ -238,9 +583,62 @@ mod tests {
         let mut repository = LogRepository::new();
         let body_without_ts = r#"{"log": "Log message"}"#;
 
-        repository.add_log_message(body_without_ts.to_string(), Metadata::new());
+        repository.add_log_message(
+            body_without_ts.to_string(),
+            Metadata::new(),
+            IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)),
+            &[],
+        );
 
         assert_eq!(repository.messages_by_ts.len(), 1);
+        let message = repository.messages_by_ts.values().next().unwrap()[0].clone();
+        assert_eq!(message.timestamp, message.receive_timestamp);
+        assert_eq!(message.timestamp_source, RECEIVE_TIME_SOURCE);
+    }
+
+    #[test]
+    fn test_repo_add_received_log() {
+        let mut repository = LogRepository::new();
+        let log = ReceivedLog {
+            body: r#"{"log": "Log message", "timestamp": 1}"#.to_string(),
+            metadata: Metadata::new(),
+            timestamp: Some((5, String::from("otlp:time_unix_nano"))),
+        };
+
+        repository.add_received_log(log, IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), &[]);
+
+        // the provided timestamp is used instead of the one from the body
+        let message = &repository.messages_by_ts[&5][0];
+        assert_eq!(message.timestamp_source, "otlp:time_unix_nano");
+    }
+
+    #[test]
+    fn test_repo_body_path_query() {
+        let raw_logs = vec![
+            (
+                r#"{"log": {"level": "error", "code": 500}, "tags": ["a", "b"]}"#.to_string(),
+                Metadata::new(),
+            ),
+            (r#"{"log": {"level": "info"}}"#.to_string(), Metadata::new()),
+            ("body.log.level=error".to_string(), Metadata::new()),
+            (
+                r#"{"log": {"level": "info"}}"#.to_string(),
+                Metadata::from([("body.log.level".to_string(), "error".to_string())]),
+            ),
+        ];
+        let repository = LogRepository::from_raw_logs(raw_logs).unwrap();
+        let count = |query: Vec<(&str, &str)>| {
+            repository
+                .get_message_count(0, u64::MAX, HashMap::from_iter(query), &BodyQuery::default())
+                .unwrap()
+        };
+
+        // metadata takes precedence over the body
+        assert_eq!(count(vec![("body.log.level", "error")]), 2);
+        assert_eq!(count(vec![("body.log.level", "")]), 3);
+        assert_eq!(count(vec![("body.log.code", "5.*")]), 1);
+        assert_eq!(count(vec![("body.tags.1", "b")]), 1);
+        assert_eq!(count(vec![("body.log.missing", "")]), 0);
     }
 
     #[test]
@@ -253,9 +651,166 @@ mod tests {
             .collect();
         let repository = LogRepository::from_raw_logs(raw_logs).unwrap();
 
-        assert_eq!(repository.get_message_count(1, 6, HashMap::new()).unwrap(), 2);
-        assert_eq!(repository.get_message_count(0, 10, HashMap::new()).unwrap(), 3);
-        assert_eq!(repository.get_message_count(2, 3, HashMap::new()).unwrap(), 0);
+        assert_eq!(
+            repository
+                .get_message_count(1, 6, HashMap::new(), &BodyQuery::default())
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            repository
+                .get_message_count(0, 10, HashMap::new(), &BodyQuery::default())
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            repository
+                .get_message_count(2, 3, HashMap::new(), &BodyQuery::default())
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_repo_get_messages() {
+        let raw_logs = vec![
+            (
+                r#"{"log": "third", "timestamp": 8}"#.to_string(),
+                Metadata::new(),
+            ),
+            (
+                r#"{"log": "first", "timestamp": 1}"#.to_string(),
+                Metadata::new(),
+            ),
+            (
+                r#"{"log": "second", "timestamp": 1}"#.to_string(),
+                Metadata::new(),
+            ),
+        ];
+        let repository = LogRepository::from_raw_logs(raw_logs).unwrap();
+
+        let bodies: Vec<&str> = repository
+            .get_messages(0, 10, HashMap::new(), &BodyQuery::default())
+            .unwrap()
+            .iter()
+            .map(|message| message.body.as_str())
+            .collect();
+        assert_eq!(
+            bodies,
+            vec![
+                r#"{"log": "first", "timestamp": 1}"#,
+                r#"{"log": "second", "timestamp": 1}"#,
+                r#"{"log": "third", "timestamp": 8}"#,
+            ]
+        );
+        assert_eq!(
+            repository
+                .get_messages(2, 8, HashMap::new(), &BodyQuery::default())
+                .unwrap()
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_repo_remove_messages() {
+        let raw_logs = vec![
+            (
+                r#"{"log": "first", "timestamp": 1}"#.to_string(),
+                Metadata::from([("namespace".to_string(), "default".to_string())]),
+            ),
+            (
+                r#"{"log": "second", "timestamp": 1}"#.to_string(),
+                Metadata::from([("namespace".to_string(), "sumologic".to_string())]),
+            ),
+            (
+                r#"{"log": "third", "timestamp": 8}"#.to_string(),
+                Metadata::from([("namespace".to_string(), "default".to_string())]),
+            ),
+        ];
+        let mut repository = LogRepository::from_raw_logs(raw_logs).unwrap();
+
+        // nothing is removed when the query is invalid
+        let query = HashMap::from([("namespace", "(")]);
+        assert!(repository
+            .remove_messages(0, 10, query, &BodyQuery::default())
+            .is_err());
+
+        let query = HashMap::from([("namespace", "default")]);
+        assert_eq!(
+            repository
+                .remove_messages(0, 5, query, &BodyQuery::default())
+                .unwrap(),
+            1
+        );
+        let bodies: Vec<&str> = repository
+            .get_messages(0, 10, HashMap::new(), &BodyQuery::default())
+            .unwrap()
+            .iter()
+            .map(|message| message.body.as_str())
+            .collect();
+        assert_eq!(
+            bodies,
+            vec![
+                r#"{"log": "second", "timestamp": 1}"#,
+                r#"{"log": "third", "timestamp": 8}"#,
+            ]
+        );
+
+        assert_eq!(
+            repository
+                .remove_messages(0, u64::MAX, HashMap::new(), &BodyQuery::default())
+                .unwrap(),
+            2
+        );
+        assert!(repository.messages_by_ts.is_empty());
+    }
+
+    #[test]
+    fn test_repo_evict() {
+        let raw_logs = vec![
+            (
+                r#"{"log": "first", "timestamp": 5}"#.to_string(),
+                Metadata::new(),
+            ),
+            (
+                r#"{"log": "second", "timestamp": 1}"#.to_string(),
+                Metadata::new(),
+            ),
+            (
+                r#"{"log": "third", "timestamp": 5}"#.to_string(),
+                Metadata::new(),
+            ),
+        ];
+        let mut repository = LogRepository::from_raw_logs(raw_logs).unwrap();
+        let byte_count = repository.byte_count();
+        assert_eq!(
+            byte_count,
+            repository
+                .iter()
+                .map(|message| message.byte_count())
+                .sum::<u64>()
+        );
+
+        // logs are evicted in the order of receiving, regardless of their timestamps
+        let limits = RetentionLimits {
+            max_entries: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(repository.evict(&limits, time::get_now_ms()), 2);
+        let bodies: Vec<&str> = repository.iter().map(|message| message.body.as_str()).collect();
+        assert_eq!(bodies, vec![r#"{"log": "third", "timestamp": 5}"#]);
+        assert!(repository.byte_count() < byte_count);
+
+        let limits = RetentionLimits {
+            max_age: Some(std::time::Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert_eq!(repository.evict(&limits, time::get_now_ms()), 0);
+        assert_eq!(repository.evict(&limits, time::get_now_ms() + 61_000), 1);
+        assert_eq!(repository.len(), 0);
+        assert_eq!(repository.byte_count(), 0);
+        assert_eq!(repository.evicted_count, 3);
     }
 
     #[test]
@@ -280,13 +835,23 @@ mod tests {
 
         assert_eq!(
             repository
-                .get_message_count(0, 100, HashMap::from_iter(vec![("key", "value")].into_iter()))
+                .get_message_count(
+                    0,
+                    100,
+                    HashMap::from_iter(vec![("key", "value")].into_iter()),
+                    &BodyQuery::default()
+                )
                 .unwrap(),
             1
         );
         assert_eq!(
             repository
-                .get_message_count(0, 100, HashMap::from_iter(vec![("key", "")].into_iter()))
+                .get_message_count(
+                    0,
+                    100,
+                    HashMap::from_iter(vec![("key", "")].into_iter()),
+                    &BodyQuery::default()
+                )
                 .unwrap(),
             2
         );
@@ -295,7 +860,8 @@ mod tests {
                 .get_message_count(
                     0,
                     100,
-                    HashMap::from_iter(vec![("key", "valueprime"), ("key2", "value2")].into_iter())
+                    HashMap::from_iter(vec![("key", "valueprime"), ("key2", "value2")].into_iter()),
+                    &BodyQuery::default()
                 )
                 .unwrap(),
             1
@@ -322,7 +888,12 @@ mod tests {
         // Check backward compatibility (match only exact matches)
         assert_eq!(
             repository
-                .get_message_count(0, 100, HashMap::from_iter(vec![("key", "value")].into_iter()))
+                .get_message_count(
+                    0,
+                    100,
+                    HashMap::from_iter(vec![("key", "value")].into_iter()),
+                    &BodyQuery::default()
+                )
                 .unwrap(),
             1
         );
@@ -330,14 +901,24 @@ mod tests {
         // Check backward compatibility (empty matches all)
         assert_eq!(
             repository
-                .get_message_count(0, 100, HashMap::from_iter(vec![("key", "")].into_iter()))
+                .get_message_count(
+                    0,
+                    100,
+                    HashMap::from_iter(vec![("key", "")].into_iter()),
+                    &BodyQuery::default()
+                )
                 .unwrap(),
             6
         );
 
         assert_eq!(
             repository
-                .get_message_count(0, 100, HashMap::from_iter(vec![("key", "value.*")].into_iter()))
+                .get_message_count(
+                    0,
+                    100,
+                    HashMap::from_iter(vec![("key", "value.*")].into_iter()),
+                    &BodyQuery::default()
+                )
                 .unwrap(),
             2
         );
@@ -347,7 +928,8 @@ mod tests {
                 .get_message_count(
                     0,
                     100,
-                    HashMap::from_iter(vec![("key", ".*value.*")].into_iter())
+                    HashMap::from_iter(vec![("key", ".*value.*")].into_iter()),
+                    &BodyQuery::default()
                 )
                 .unwrap(),
             4
@@ -358,7 +940,8 @@ mod tests {
                 .get_message_count(
                     0,
                     100,
-                    HashMap::from_iter(vec![("key", "(?!undefined$).*")].into_iter())
+                    HashMap::from_iter(vec![("key", "(?!undefined$).*")].into_iter()),
+                    &BodyQuery::default()
                 )
                 .unwrap(),
             5
@@ -383,7 +966,7 @@ mod tests {
         let repository = LogRepository::from_raw_logs(raw_logs).unwrap();
 
         // No query done yet, confirm that cache is empty.
-        assert_eq!(repository.regex_cache.cache.read().unwrap().len(), 0);
+        assert_eq!(repository.regex_cache.cache.lock().unwrap().len(), 0);
 
         // Do a query
         assert_eq!(
@@ -391,30 +974,31 @@ mod tests {
                 .get_message_count(
                     0,
                     100,
-                    HashMap::from_iter(vec![("key", "value.*"), ("key3", "third.*")].into_iter())
+                    HashMap::from_iter(vec![("key", "value.*"), ("key3", "third.*")].into_iter()),
+                    &BodyQuery::default()
                 )
                 .unwrap(),
             1
         );
 
         // Confirm that cache has increased size
-        assert_eq!(repository.regex_cache.cache.read().unwrap().len(), 2);
+        assert_eq!(repository.regex_cache.cache.lock().unwrap().len(), 2);
 
         // Get the regexes.
         let first = repository
             .regex_cache
             .cache
-            .read()
+            .lock()
             .unwrap()
-            .get("value.*")
+            .peek("value.*")
             .unwrap()
             .clone();
         let third = repository
             .regex_cache
             .cache
-            .read()
+            .lock()
             .unwrap()
-            .get("third.*")
+            .peek("third.*")
             .unwrap()
             .clone();
 
@@ -427,18 +1011,98 @@ mod tests {
                 .get_message_count(
                     0,
                     100,
-                    HashMap::from_iter(vec![("key", "value.*"), ("key2", "value.*"), ("key3", "third.*")].into_iter())
+                    HashMap::from_iter(vec![("key", "value.*"), ("key2", "value.*"), ("key3", "third.*")].into_iter()),
+                    &BodyQuery::default()
                 )
                 .unwrap(),
             1
         );
 
         // Confirm that the cache hasn't changed: the size is the same and the same references are used.
-        assert_eq!(repository.regex_cache.cache.read().unwrap().len(), 2);
+        assert_eq!(repository.regex_cache.cache.lock().unwrap().len(), 2);
         assert_eq!(Arc::strong_count(&first), 2);
         assert_eq!(Arc::strong_count(&third), 2);
     }
 
+    #[test]
+    fn test_lru_cache() {
+        let mut cache = LruCache::new(2);
+        cache.insert(String::from("a"), 1);
+        cache.insert(String::from("b"), 2);
+        // using a makes b the least recently used
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert(String::from("c"), 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+    }
+
+    #[test]
+    fn test_repo_body_query() {
+        let bodies = [
+            "2024-01-01 ERROR failed to connect",
+            "2024-01-01 error: retrying",
+            "2024-01-01 INFO connected",
+        ];
+        let raw_logs = bodies
+            .iter()
+            .map(|body| (body.to_string(), Metadata::new()))
+            .collect();
+        let repository = LogRepository::from_raw_logs(raw_logs).unwrap();
+        let count = |body_query: BodyQuery| {
+            repository
+                .get_message_count(0, u64::MAX, HashMap::new(), &body_query)
+                .unwrap()
+        };
+
+        assert_eq!(
+            count(BodyQuery {
+                body_contains: Some("ERROR".to_string()),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(
+            count(BodyQuery {
+                body_icontains: Some("ERROR".to_string()),
+                ..Default::default()
+            }),
+            2
+        );
+        // regexes aren't anchored, unlike the metadata ones
+        assert_eq!(
+            count(BodyQuery {
+                body_regex: Some("conn(ect|ected)$".to_string()),
+                ..Default::default()
+            }),
+            2
+        );
+        // all the predicates have to match
+        assert_eq!(
+            count(BodyQuery {
+                body_icontains: Some("error".to_string()),
+                body_regex: Some("conn".to_string()),
+                ..Default::default()
+            }),
+            1
+        );
+        assert_eq!(count(BodyQuery::default()), 3);
+
+        assert!(repository
+            .get_message_count(
+                0,
+                u64::MAX,
+                HashMap::new(),
+                &BodyQuery {
+                    body_regex: Some("(".to_string()),
+                    ..Default::default()
+                }
+            )
+            .is_err());
+    }
+
     fn run_bench(count: usize, metadata_count: usize, log_count: usize, cache_enabled: bool) -> u128 {
         let metadata: HashMap<String, String> = (0..metadata_count)
             .map(|x| (format!("key{}", x), format!("value{}", x)))
@@ -454,7 +1118,9 @@ mod tests {
         let now = std::time::SystemTime::now();
         let mut total_count = 0;
         for _ in 0..count {
-            total_count += repository.get_message_count(0, 100, queried.clone()).unwrap();
+            total_count += repository
+                .get_message_count(0, 100, queried.clone(), &BodyQuery::default())
+                .unwrap();
         }
 
         let ret = now.elapsed().unwrap().as_millis();
@@ -478,12 +1144,14 @@ mod tests {
     #[test]
     fn test_get_timestamp_from_body() {
         assert_eq!(
-            get_timestamp_from_body(r#"{"timestamp": 1234567891011}"#).unwrap(),
+            get_timestamp_from_body(r#"{"timestamp": 1234567891011}"#, &[])
+                .unwrap()
+                .0,
             1234567891011
         );
-        assert!(get_timestamp_from_body(r#"{"timestamp": -1}"#).is_none());
-        assert!(get_timestamp_from_body(r#"{"timestamp": 1.5}"#).is_none());
-        assert!(get_timestamp_from_body(r#"{"log": "Some log message"}"#).is_none());
-        assert!(get_timestamp_from_body("Not json at all").is_none())
+        assert!(get_timestamp_from_body(r#"{"timestamp": -1}"#, &[]).is_none());
+        assert!(get_timestamp_from_body(r#"{"timestamp": 1.5}"#, &[]).is_none());
+        assert!(get_timestamp_from_body(r#"{"log": "Some log message"}"#, &[]).is_none());
+        assert!(get_timestamp_from_body("Not json at all", &[]).is_none())
     }
 }
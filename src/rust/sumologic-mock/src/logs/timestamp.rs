@@ -0,0 +1,411 @@
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use fancy_regex::Regex;
+use serde_json::Value;
+
+// Field used when no timestamp fields are configured, kept for backward compatibility
+const DEFAULT_FIELD: &str = "timestamp:epoch_millis";
+
+// Name of the source for timestamps which couldn't be found in the log
+pub const RECEIVE_TIME_SOURCE: &str = "receive_time";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimestampFormat {
+    // RFC3339 strings or epoch numbers with the unit guessed from their magnitude
+    Auto,
+    Rfc3339,
+    EpochSeconds,
+    EpochMillis,
+    EpochNanos,
+    Strftime(String),
+}
+
+impl TimestampFormat {
+    fn parse(format: &str) -> Result<Self> {
+        match format {
+            "auto" => Ok(TimestampFormat::Auto),
+            "rfc3339" => Ok(TimestampFormat::Rfc3339),
+            "epoch_seconds" => Ok(TimestampFormat::EpochSeconds),
+            "epoch_millis" => Ok(TimestampFormat::EpochMillis),
+            "epoch_nanos" => Ok(TimestampFormat::EpochNanos),
+            strftime if strftime.contains('%') => Ok(TimestampFormat::Strftime(strftime.to_string())),
+            _ => Err(anyhow!("Invalid timestamp format: '{}'", format)),
+        }
+    }
+}
+
+// JSON field holding the log timestamp, in the form `<path>[:<format>]`, e.g. `log.time:rfc3339`.
+// Path segments are separated with dots, a key containing dots is matched as a whole first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimestampField {
+    path: String,
+    format: TimestampFormat,
+}
+
+impl TimestampField {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (path, format) = match spec.split_once(':') {
+            Some((path, format)) => (path, TimestampFormat::parse(format)?),
+            None => (spec, TimestampFormat::Auto),
+        };
+        if path.is_empty() {
+            return Err(anyhow!("Invalid timestamp field: '{}'", spec));
+        }
+        Ok(Self {
+            path: path.to_string(),
+            format,
+        })
+    }
+
+    fn get_value<'a>(&self, body: &'a Value) -> Option<&'a Value> {
+        if let Some(value) = body.get(&self.path) {
+            return Some(value);
+        }
+        self.path
+            .split('.')
+            .try_fold(body, |value, segment| value.get(segment))
+    }
+
+    fn get_timestamp(&self, body: &Value) -> Option<u64> {
+        let value = self.get_value(body)?;
+        match (&self.format, value) {
+            (TimestampFormat::Auto, Value::String(string)) => match parse_rfc3339(string) {
+                Some(ts) => Some(ts),
+                None => epoch_to_millis(get_epoch(value)?, None),
+            },
+            (TimestampFormat::Auto, _) => epoch_to_millis(get_epoch(value)?, None),
+            (TimestampFormat::Rfc3339, Value::String(string)) => parse_rfc3339(string),
+            (TimestampFormat::EpochSeconds, _) => epoch_to_millis(get_epoch(value)?, Some(EpochUnit::Seconds)),
+            (TimestampFormat::EpochMillis, _) => epoch_to_millis(get_epoch(value)?, Some(EpochUnit::Millis)),
+            (TimestampFormat::EpochNanos, _) => epoch_to_millis(get_epoch(value)?, Some(EpochUnit::Nanos)),
+            (TimestampFormat::Strftime(format), Value::String(string)) => parse_strftime(string, format),
+            _ => None,
+        }
+    }
+}
+
+// Get the timestamp from the log body, together with the name of the method which found it.
+// JSON bodies are checked for the configured fields (`timestamp` in milliseconds if none are configured)
+// first. Then, like Sumo does, the body is searched for ISO8601, klog and syslog timestamps.
+// Timestamps without a time zone are assumed to be in UTC, and ones without a year in the current year,
+// unless that would put them more than a day in the future, e.g. for logs from Dec 31 received on Jan 1.
+pub fn get_timestamp_from_body(body: &str, fields: &[TimestampField]) -> Option<(u64, String)> {
+    get_timestamp_from_body_at(body, fields, Utc::now())
+}
+
+fn get_timestamp_from_body_at(body: &str, fields: &[TimestampField], now: DateTime<Utc>) -> Option<(u64, String)> {
+    if let Ok(parsed_body) = serde_json::from_str::<Value>(body) {
+        let default_fields;
+        let fields = if fields.is_empty() {
+            default_fields = vec![TimestampField::parse(DEFAULT_FIELD).unwrap()];
+            &default_fields
+        } else {
+            fields
+        };
+        for field in fields {
+            if let Some(ts) = field.get_timestamp(&parsed_body) {
+                return Some((ts, format!("json:{}", field.path)));
+            }
+        }
+    }
+
+    detect_timestamp(body, now)
+}
+
+fn detect_timestamp(body: &str, now: DateTime<Utc>) -> Option<(u64, String)> {
+    static ISO8601: OnceLock<Regex> = OnceLock::new();
+    static KLOG: OnceLock<Regex> = OnceLock::new();
+    static SYSLOG: OnceLock<Regex> = OnceLock::new();
+
+    // e.g. 2024-03-01T12:34:56.789Z, 2024-03-01 12:34:56,789 +0100
+    let iso8601 = ISO8601.get_or_init(|| {
+        Regex::new(r"[0-9]{4}-[0-9]{2}-[0-9]{2}[T ][0-9]{2}:[0-9]{2}:[0-9]{2}(?:[.,][0-9]+)?(?: ?(?:Z|[+-][0-9]{2}:?[0-9]{2}))?")
+            .unwrap()
+    });
+    if let Ok(Some(m)) = iso8601.find(body) {
+        if let Some(ts) = parse_iso8601(m.as_str()) {
+            return Some((ts, String::from("iso8601")));
+        }
+    }
+
+    // e.g. I0301 12:34:56.789012
+    let klog = KLOG.get_or_init(|| Regex::new(r"\b[IWEF][0-9]{4} [0-9]{2}:[0-9]{2}:[0-9]{2}\.[0-9]{6}").unwrap());
+    if let Ok(Some(m)) = klog.find(body) {
+        if let Some(ts) = parse_without_year(&m.as_str()[1..], "%m%d %H:%M:%S%.f", now) {
+            return Some((ts, String::from("klog")));
+        }
+    }
+
+    // e.g. Mar  1 12:34:56
+    let syslog = SYSLOG.get_or_init(|| {
+        Regex::new(r"\b(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec) [ 0-9][0-9] [0-9]{2}:[0-9]{2}:[0-9]{2}\b")
+            .unwrap()
+    });
+    if let Ok(Some(m)) = syslog.find(body) {
+        if let Some(ts) = parse_without_year(m.as_str(), "%b %e %H:%M:%S", now) {
+            return Some((ts, String::from("syslog")));
+        }
+    }
+
+    None
+}
+
+#[derive(Clone, Copy, Debug)]
+enum EpochUnit {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+// Epoch timestamp from a JSON number or string. Integers are kept as they are to avoid losing precision.
+enum Epoch {
+    Integer(u64),
+    Float(f64),
+}
+
+fn get_epoch(value: &Value) -> Option<Epoch> {
+    match value {
+        Value::Number(number) => match number.as_u64() {
+            Some(integer) => Some(Epoch::Integer(integer)),
+            None => number.as_f64().map(Epoch::Float),
+        },
+        Value::String(string) => match string.parse::<u64>() {
+            Ok(integer) => Some(Epoch::Integer(integer)),
+            Err(_) => string.parse::<f64>().ok().map(Epoch::Float),
+        },
+        _ => None,
+    }
+}
+
+// Convert an epoch timestamp to milliseconds. The unit is guessed from the magnitude if not provided.
+fn epoch_to_millis(epoch: Epoch, unit: Option<EpochUnit>) -> Option<u64> {
+    let unit = unit.unwrap_or_else(|| {
+        let value = match epoch {
+            Epoch::Integer(integer) => integer as f64,
+            Epoch::Float(float) => float,
+        };
+        if value < 1e11 {
+            EpochUnit::Seconds
+        } else if value < 1e14 {
+            EpochUnit::Millis
+        } else if value < 1e17 {
+            EpochUnit::Micros
+        } else {
+            EpochUnit::Nanos
+        }
+    });
+    match epoch {
+        Epoch::Integer(integer) => match unit {
+            EpochUnit::Seconds => integer.checked_mul(1000),
+            EpochUnit::Millis => Some(integer),
+            EpochUnit::Micros => Some(integer / 1000),
+            EpochUnit::Nanos => Some(integer / 1_000_000),
+        },
+        // fractions are only supported for seconds
+        Epoch::Float(float) => match unit {
+            EpochUnit::Seconds if float.is_finite() && float >= 0.0 => Some((float * 1e3).round() as u64),
+            _ => None,
+        },
+    }
+}
+
+fn datetime_to_millis(datetime: NaiveDateTime) -> Option<u64> {
+    u64::try_from(datetime.and_utc().timestamp_millis()).ok()
+}
+
+fn parse_rfc3339(value: &str) -> Option<u64> {
+    let datetime = DateTime::parse_from_rfc3339(value).ok()?;
+    u64::try_from(datetime.timestamp_millis()).ok()
+}
+
+fn parse_strftime(value: &str, format: &str) -> Option<u64> {
+    if let Ok(datetime) = DateTime::parse_from_str(value, format) {
+        return u64::try_from(datetime.timestamp_millis()).ok();
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, format) {
+        return datetime_to_millis(datetime);
+    }
+    // formats with only a date
+    let date = NaiveDate::parse_from_str(value, format).ok()?;
+    datetime_to_millis(date.and_hms_opt(0, 0, 0)?)
+}
+
+fn parse_iso8601(value: &str) -> Option<u64> {
+    // The offsets below are byte positions
+    if !value.is_ascii() {
+        return None;
+    }
+    // normalize to RFC3339, which chrono can parse
+    let mut normalized = value
+        .replacen(' ', "T", 1)
+        .replacen(',', ".", 1)
+        .replace(' ', "");
+    let has_offset = normalized.ends_with('Z')
+        || normalized
+            .get(19..)
+            .is_some_and(|rest| rest.contains(['+', '-']));
+    if !has_offset {
+        normalized.push('Z');
+    } else if !normalized.ends_with('Z')
+        && !normalized
+            .get(normalized.len().saturating_sub(5)..)
+            .is_some_and(|offset| offset.contains(':'))
+    {
+        // +0100 -> +01:00
+        normalized.insert(normalized.len() - 2, ':');
+    }
+    parse_rfc3339(&normalized)
+}
+
+fn parse_without_year(value: &str, format: &str, now: DateTime<Utc>) -> Option<u64> {
+    let parse =
+        |year: i32| NaiveDateTime::parse_from_str(&format!("{} {}", year, value), &format!("%Y {}", format)).ok();
+    let datetime = parse(now.year())?;
+    if datetime.and_utc() - now > Duration::days(1) {
+        return datetime_to_millis(parse(now.year() - 1)?);
+    }
+    datetime_to_millis(datetime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_now(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().to_utc()
+    }
+
+    // Timestamps without a year are in 2021
+    fn get_timestamp(body: &str, fields: &[&str]) -> Option<(u64, String)> {
+        let fields: Vec<TimestampField> = fields
+            .iter()
+            .map(|f| TimestampField::parse(f).unwrap())
+            .collect();
+        get_timestamp_from_body_at(body, &fields, get_now("2021-12-08T00:00:00Z"))
+    }
+
+    #[test]
+    fn test_default_field() {
+        assert_eq!(
+            get_timestamp(r#"{"log": "Log message", "timestamp": 1}"#, &[]),
+            Some((1, String::from("json:timestamp")))
+        );
+        assert_eq!(get_timestamp(r#"{"log": "Log message"}"#, &[]), None);
+    }
+
+    #[test]
+    fn test_json_fields() {
+        let fields = ["log.time:rfc3339", "@timestamp:epoch_seconds", "ts:epoch_nanos"];
+        assert_eq!(
+            get_timestamp(r#"{"log": {"time": "2021-12-07T10:36:19.541+01:00"}}"#, &fields),
+            Some((1638869779541, String::from("json:log.time")))
+        );
+        assert_eq!(
+            get_timestamp(r#"{"@timestamp": 1638873379.541}"#, &fields),
+            Some((1638873379541, String::from("json:@timestamp")))
+        );
+        assert_eq!(
+            get_timestamp(r#"{"ts": "1638873379541000000"}"#, &fields),
+            Some((1638873379541, String::from("json:ts")))
+        );
+        // the first field found is used
+        assert_eq!(
+            get_timestamp(r#"{"ts": 1638873379541000000, "@timestamp": 1}"#, &fields),
+            Some((1000, String::from("json:@timestamp")))
+        );
+        // a key with dots is matched as a whole
+        assert_eq!(
+            get_timestamp(r#"{"log.time": "2021-12-07T09:36:19.541Z"}"#, &fields),
+            Some((1638869779541, String::from("json:log.time")))
+        );
+    }
+
+    #[test]
+    fn test_auto_and_strftime_formats() {
+        let fields = ["time", "date:%d/%m/%Y %H:%M:%S"];
+        for (body, expected) in [
+            (r#"{"time": 1638873379}"#, 1638873379000),
+            (r#"{"time": 1638873379541}"#, 1638873379541),
+            (r#"{"time": 1638873379541000}"#, 1638873379541),
+            (r#"{"time": 1638873379541000000}"#, 1638873379541),
+            (r#"{"time": "2021-12-07T10:36:19.541Z"}"#, 1638873379541),
+            (r#"{"date": "07/12/2021 10:36:19"}"#, 1638873379000),
+        ] {
+            assert_eq!(get_timestamp(body, &fields).unwrap().0, expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_plain_text_detection() {
+        assert_eq!(
+            get_timestamp("2021-12-07T10:36:19.541Z INFO started", &[]),
+            Some((1638873379541, String::from("iso8601")))
+        );
+        assert_eq!(
+            get_timestamp("2021-12-07 11:36:19,541 +0100 INFO started", &[]),
+            Some((1638873379541, String::from("iso8601")))
+        );
+        assert_eq!(
+            get_timestamp("[main] 2021-12-07 10:36:19 started", &[]),
+            Some((1638873379000, String::from("iso8601")))
+        );
+        assert_eq!(
+            get_timestamp("I1207 10:36:19.541000       1 main.go:42] started", &[]),
+            Some((1638873379541, String::from("klog")))
+        );
+        assert_eq!(
+            get_timestamp("Dec  7 10:36:19 node-1 kubelet[123]: started", &[]),
+            Some((1638873379000, String::from("syslog")))
+        );
+        // JSON bodies without the configured fields are searched as well
+        assert_eq!(
+            get_timestamp(r#"{"log": "E1207 10:36:19.541000 1 main.go:42] failed"}"#, &[]),
+            Some((1638873379541, String::from("klog")))
+        );
+        assert_eq!(get_timestamp("no timestamp here", &[]), None);
+    }
+
+    #[test]
+    fn test_non_ascii_digits() {
+        // the last digit is ARABIC-INDIC DIGIT NINE
+        assert_eq!(get_timestamp("2021-12-07T10:36:1\u{669} started", &[]), None);
+        assert_eq!(get_timestamp("I1207 10:36:19.54100\u{669} started", &[]), None);
+        assert_eq!(get_timestamp("Dec  7 10:36:1\u{669} started", &[]), None);
+        assert_eq!(parse_iso8601("2021-12-07T10:36:1\u{669}"), None);
+        assert_eq!(parse_iso8601("2021-12-07T10:36:19+01\u{669}0"), None);
+    }
+
+    #[test]
+    fn test_parse_without_year() {
+        let format = "%b %e %H:%M:%S";
+        let new_year = get_now("2022-01-01T00:00:10Z");
+        // logs from the end of the last year
+        assert_eq!(
+            parse_without_year("Dec 31 23:59:59", format, new_year),
+            Some(1640995199000)
+        );
+        assert_eq!(
+            parse_without_year("Jan  1 00:00:05", format, new_year),
+            Some(1640995205000)
+        );
+        // up to a day in the future, e.g. because of a clock skew
+        assert_eq!(
+            parse_without_year("Jan  1 23:00:00", format, new_year),
+            Some(1641078000000)
+        );
+        assert_eq!(
+            parse_without_year("Jan  2 01:00:00", format, new_year),
+            Some(1609549200000)
+        );
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        assert!(TimestampField::parse("").is_err());
+        assert!(TimestampField::parse(":rfc3339").is_err());
+        assert!(TimestampField::parse("time:unknown").is_err());
+    }
+}
@@ -2,12 +2,13 @@
 extern crate json_str;
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Mutex;
 
 use actix_web::web;
 
 use chrono::Duration;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use log::error;
 use log::info;
 use std::thread;
@@ -19,8 +20,14 @@ mod traces;
 
 mod options;
 use options::Options;
+mod capture;
+mod compression;
+mod faults;
 mod metadata;
+mod replay;
+mod request_stats;
 mod router;
+mod state;
 mod time;
 
 #[derive(Parser)]
@@ -31,9 +38,18 @@ mod time;
     about = "Sumo Logic Mock can be used for testing performance or functionality of kubernetes collection without sending data to sumologic"
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(short, long, default_value_t = 3000, help = "Port to listen on")]
     port: u16,
 
+    #[arg(
+        long = "grpc-port",
+        help = "Port to listen on for OTLP/gRPC data (e.g. 4317). The gRPC receiver is disabled when not set"
+    )]
+    grpc_port: Option<u16>,
+
     #[arg(
         short='l',
         long,
@@ -94,6 +110,81 @@ struct Cli {
     )]
     store_traces: bool,
 
+    #[arg(
+        long = "graphite-template",
+        value_parser = metrics::graphite::GraphiteTemplate::parse,
+        help = "Template used to parse Graphite metric paths, e.g. 'host.measurement.field*'. Can be specified multiple times, the first template with a matching filter is used"
+    )]
+    graphite_templates: Vec<metrics::graphite::GraphiteTemplate>,
+
+    #[arg(
+        long = "log-timestamp-field",
+        value_parser = logs::timestamp::TimestampField::parse,
+        help = "JSON field with the log timestamp, as '<path>[:<format>]', e.g. 'log.time:rfc3339'. Format is one of auto, rfc3339, epoch_seconds, epoch_millis, epoch_nanos or a strftime pattern. Can be specified multiple times, the first field found is used. Defaults to 'timestamp:epoch_millis'"
+    )]
+    log_timestamp_fields: Vec<logs::timestamp::TimestampField>,
+
+    #[arg(
+        long = "logs-max-entries",
+        help = "Maximum number of stored logs, the oldest ones are evicted first"
+    )]
+    logs_max_entries: Option<usize>,
+
+    #[arg(
+        long = "logs-max-bytes",
+        help = "Maximum approximate size of the stored logs in bytes, the oldest logs are evicted first"
+    )]
+    logs_max_bytes: Option<u64>,
+
+    #[arg(
+        long = "logs-max-age",
+        help = "Maximum age of the stored logs in seconds, counted from when they were received"
+    )]
+    logs_max_age: Option<u64>,
+
+    #[arg(
+        long = "metrics-max-entries",
+        help = "Maximum number of stored series, the least recently updated ones are evicted first"
+    )]
+    metrics_max_entries: Option<usize>,
+
+    #[arg(
+        long = "metrics-max-bytes",
+        help = "Maximum approximate size of the stored series in bytes, the least recently updated ones are evicted first"
+    )]
+    metrics_max_bytes: Option<u64>,
+
+    #[arg(
+        long = "metrics-max-age",
+        help = "Maximum age of the stored series in seconds, counted from when their last data point was received"
+    )]
+    metrics_max_age: Option<u64>,
+
+    #[arg(
+        long = "traces-max-entries",
+        help = "Maximum number of stored spans, the oldest traces are evicted first, with all their spans"
+    )]
+    traces_max_entries: Option<usize>,
+
+    #[arg(
+        long = "traces-max-bytes",
+        help = "Maximum approximate size of the stored spans in bytes, the oldest traces are evicted first"
+    )]
+    traces_max_bytes: Option<u64>,
+
+    #[arg(
+        long = "traces-max-age",
+        help = "Maximum age of the stored traces in seconds, counted from when their first span was received"
+    )]
+    traces_max_age: Option<u64>,
+
+    #[arg(
+        long = "metrics-max-points-per-series",
+        default_value_t = metrics::series::DEFAULT_MAX_POINTS_PER_SERIES,
+        help = "Maximum number of data points stored for every time series, the oldest ones are dropped first"
+    )]
+    metrics_max_points_per_series: usize,
+
     #[arg(
         short = 'a',
         long = "drop-rate",
@@ -109,6 +200,78 @@ struct Cli {
         help = "Use to specify delay time. It mocks request processing time in milliseconds."
     )]
     delay_time: u64,
+
+    #[arg(
+        long = "state-file",
+        help = "File the state is saved to periodically and on shutdown, and restored from on startup. The state is kept in memory only when not set"
+    )]
+    state_file: Option<PathBuf>,
+
+    #[arg(
+        long = "state-save-interval",
+        default_value_t = 60,
+        help = "Interval between the state snapshots in seconds, used with --state-file"
+    )]
+    state_save_interval: i64,
+
+    #[arg(
+        long = "fault-profile",
+        help = "JSON file with the fault injection rules, see the README. They can be replaced with POST /faults"
+    )]
+    fault_profile: Option<PathBuf>,
+
+    #[arg(
+        long = "capture-dir",
+        help = "Directory every ingest request is written to, so it can be resent with the replay subcommand. Requests aren't captured when not set"
+    )]
+    capture_dir: Option<PathBuf>,
+
+    #[arg(
+        long = "capture-format",
+        value_enum,
+        default_value_t = capture::CaptureFormat::Ndjson,
+        help = "Format of the capture files, used with --capture-dir"
+    )]
+    capture_format: capture::CaptureFormat,
+
+    #[arg(
+        long = "capture-max-file-size",
+        default_value_t = capture::DEFAULT_MAX_FILE_SIZE,
+        help = "Size in bytes after which a new capture file is started, used with --capture-dir"
+    )]
+    capture_max_file_size: u64,
+
+    #[arg(
+        long = "capture-max-files",
+        help = "Maximum number of capture files, the oldest ones are removed first. All of them are kept when not set"
+    )]
+    capture_max_files: Option<usize>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Resend the requests captured with --capture-dir to a target URL and report the response codes")]
+    Replay(ReplayArgs),
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    #[arg(help = "Capture file or directory with capture files")]
+    path: PathBuf,
+
+    #[arg(
+        long,
+        default_value_t = String::from("http://localhost:3000"),
+        help = "URL the requests are sent to, their paths are appended to it"
+    )]
+    target: String,
+
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        help = "Speed multiplier of the original timing, e.g. 2 sends the requests twice as fast. With 0 the requests are sent without waiting"
+    )]
+    speed: f64,
 }
 
 #[actix_web::main]
@@ -117,6 +280,10 @@ async fn main() -> std::io::Result<()> {
 
     let cli = Cli::parse();
 
+    if let Some(Command::Replay(args)) = cli.command {
+        return run_replay(args).await;
+    }
+
     let opts = Options {
         print: options::Print {
             logs: cli.print_logs,
@@ -129,13 +296,111 @@ async fn main() -> std::io::Result<()> {
         store_traces: cli.store_traces,
         store_metrics: cli.store_metrics,
         store_logs: cli.store_logs,
+        graphite_templates: cli.graphite_templates,
+        log_timestamp_fields: cli.log_timestamp_fields,
+        retention: options::Retention {
+            logs: options::RetentionLimits {
+                max_entries: cli.logs_max_entries,
+                max_bytes: cli.logs_max_bytes,
+                max_age: cli.logs_max_age.map(stime::Duration::from_secs),
+            },
+            metrics: options::RetentionLimits {
+                max_entries: cli.metrics_max_entries,
+                max_bytes: cli.metrics_max_bytes,
+                max_age: cli.metrics_max_age.map(stime::Duration::from_secs),
+            },
+            traces: options::RetentionLimits {
+                max_entries: cli.traces_max_entries,
+                max_bytes: cli.traces_max_bytes,
+                max_age: cli.traces_max_age.map(stime::Duration::from_secs),
+            },
+            max_points_per_series: cli.metrics_max_points_per_series,
+        },
+    };
+
+    let fault_profile = match cli.fault_profile {
+        Some(path) => match faults::FaultProfileConfig::load(&path) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                error!("Unable to load the fault profile: {:#}", e);
+                return Err(std::io::Error::other(e.to_string()));
+            }
+        },
+        None => None,
+    };
+    let state_file = cli.state_file.map(|path| (path, cli.state_save_interval));
+    let capture_writer = match cli.capture_dir {
+        Some(dir) => {
+            match capture::CaptureWriter::new(
+                &dir,
+                cli.capture_format,
+                cli.capture_max_file_size,
+                cli.capture_max_files,
+            ) {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    error!("Unable to capture the requests: {:#}", e);
+                    return Err(std::io::Error::other(e.to_string()));
+                }
+            }
+        }
+        None => None,
     };
+    run_app(
+        cli.hostname,
+        cli.port,
+        cli.grpc_port,
+        state_file,
+        capture_writer,
+        fault_profile,
+        opts,
+    )
+    .await
+}
 
-    run_app(cli.hostname, cli.port, opts).await
+async fn run_replay(args: ReplayArgs) -> std::io::Result<()> {
+    let result = match capture::read_capture(&args.path) {
+        Ok(requests) => replay::replay(requests, &args.target, args.speed).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(report) => {
+            // The report is the output of the subcommand, so it goes to stdout rather than to the log
+            println!("{}", report);
+            Ok(())
+        }
+        Err(e) => {
+            error!("Unable to replay {}: {:#}", args.path.display(), e);
+            Err(std::io::Error::other(e.to_string()))
+        }
+    }
 }
 
-async fn run_app(hostname: String, port: u16, opts: Options) -> std::io::Result<()> {
+async fn run_app(
+    hostname: String,
+    port: u16,
+    grpc_port: Option<u16>,
+    state_file: Option<(PathBuf, i64)>,
+    capture_writer: Option<capture::CaptureWriter>,
+    fault_profile: Option<faults::FaultProfileConfig>,
+    opts: Options,
+) -> std::io::Result<()> {
     let app_state = web::Data::new(router::AppState::new());
+    if let Some(config) = fault_profile {
+        // Time windows start now
+        match faults::FaultProfile::new(config, time::get_now_ms()) {
+            Ok(profile) => *app_state.faults.write().unwrap() = profile,
+            Err(e) => {
+                error!("Invalid fault profile: {:#}", e);
+                return Err(std::io::Error::other(e.to_string()));
+            }
+        }
+    }
+    app_state
+        .metrics_series
+        .write()
+        .unwrap()
+        .set_max_points(opts.retention.max_points_per_series);
 
     let t = timer::Timer::new();
     // TODO: configure interval?
@@ -150,27 +415,78 @@ async fn run_app(hostname: String, port: u16, opts: Options) -> std::io::Result<
         fields: Mutex::new(HashMap::new()),
     });
 
+    if let Some((path, interval)) = &state_file {
+        if let Err(e) = state::restore_state(path, &app_state, &terraform_state) {
+            error!("Unable to restore the state: {:#}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+
+        let path = path.clone();
+        let app_state = app_state.clone();
+        let terraform_state = terraform_state.clone();
+        t.schedule_repeating(Duration::try_seconds(*interval).unwrap(), move || {
+            state::save_state(&path, &app_state, &terraform_state);
+        })
+        .ignore();
+    }
+
+    // The state can be over the limits after restoring it, and the maximum age has to be enforced
+    // also when nothing is received
+    app_state.enforce_retention(&opts);
+    {
+        let app_state = app_state.clone();
+        let opts = opts.clone();
+        t.schedule_repeating(Duration::try_seconds(1).unwrap(), move || {
+            app_state.enforce_retention(&opts);
+        })
+        .ignore();
+    }
+
     let create_app = {
         let app_state = app_state.clone();
         let app_metadata = app_metadata.clone();
         let terraform_state = terraform_state.clone();
         let opts = opts.clone();
+        // Shared by all the workers, the requests are written by a thread of its own
+        let capture_sender = capture_writer.map(|writer| writer.spawn().0);
 
         move || {
+            let middleware_app_state = app_state.clone();
+            let middleware_capture_sender = capture_sender.clone();
             actix_web::App::new()
-                // Middleware printing headers for all handlers.
+                // Middleware printing headers, recording the request stats and capturing the requests for all handlers.
                 // For a more robust middleware implementation (in its own type)
                 // one can take a look at https://actix.rs/docs/middleware/
-                .wrap_fn(move |req, srv| {
+                .wrap_fn(move |mut req, srv| {
                     if opts.print.headers {
                         let headers = req.headers();
 
                         router::print_request_headers(req.method(), req.version(), req.uri(), headers);
                     }
 
+                    let capture_sender = middleware_capture_sender.clone();
+                    let capture = capture_sender
+                        .as_ref()
+                        .map(|_| capture::start_capture(&mut req));
+
                     thread::sleep(opts.delay_time);
 
-                    actix_web::dev::Service::call(&srv, req)
+                    // The latency is measured for the handler only, without the delay above
+                    let app_state = middleware_app_state.clone();
+                    let start = stime::Instant::now();
+                    let response = actix_web::dev::Service::call(&srv, req);
+                    async move {
+                        let response = response.await?;
+                        router::record_request(&app_state, &response, start.elapsed());
+
+                        // The route is only known after handling the request
+                        if let (Some(capture_sender), Some(capture)) = (capture_sender, capture) {
+                            if router::is_ingest_request(response.request()) {
+                                capture_sender.send(capture.finish());
+                            }
+                        }
+                        Ok(response)
+                    }
                 })
                 .app_data(app_state.clone()) // Mutable shared state
                 .app_data(web::Data::new(opts.clone()))
@@ -182,6 +498,18 @@ async fn run_app(hostname: String, port: u16, opts: Options) -> std::io::Result<
                     "/traces-list",
                     web::get().to(router::traces_data::handler_get_traces),
                 )
+                .route(
+                    "/traces/{trace_id}",
+                    web::get().to(router::traces_data::handler_get_trace),
+                )
+                .route(
+                    "/traces-summary",
+                    web::get().to(router::traces_data::handler_traces_summary),
+                )
+                .route(
+                    "/traces-reset",
+                    web::post().to(router::traces_data::handler_traces_reset),
+                )
                 .route(
                     "/metrics-reset",
                     web::post().to(router::metrics_data::handler_metrics_reset),
@@ -198,10 +526,44 @@ async fn run_app(hostname: String, port: u16, opts: Options) -> std::io::Result<
                     "/metrics-samples",
                     web::get().to(router::metrics_data::handler_metrics_samples),
                 )
+                .route(
+                    "/metrics-series",
+                    web::get().to(router::metrics_data::handler_metrics_series),
+                )
+                .route(
+                    "/metrics-cardinality",
+                    web::get().to(router::metrics_data::handler_metrics_cardinality),
+                )
                 .route("/metrics", web::get().to(router::handler_metrics))
                 .route("/logs/count", web::get().to(router::handler_logs_count))
+                .route("/logs/list", web::get().to(router::handler_logs_list))
+                .route("/logs-reset", web::post().to(router::handler_logs_reset))
+                .route("/reset", web::post().to(router::handler_reset))
+                .route("/faults", web::get().to(router::handler_faults))
+                .route("/faults", web::post().to(router::handler_faults_update))
                 .service(
                     web::scope("/api/v1")
+                        // Prometheus HTTP API
+                        .route(
+                            "/series",
+                            web::route().to(router::prometheus::handler_prometheus_series),
+                        )
+                        .route(
+                            "/labels",
+                            web::route().to(router::prometheus::handler_prometheus_labels),
+                        )
+                        .route(
+                            "/label/{name}/values",
+                            web::get().to(router::prometheus::handler_prometheus_label_values),
+                        )
+                        .route(
+                            "/query",
+                            web::route().to(router::prometheus::handler_prometheus_query),
+                        )
+                        .route(
+                            "/query_range",
+                            web::route().to(router::prometheus::handler_prometheus_query_range),
+                        )
                         .route(
                             "/collector/register",
                             web::post().to(router::api::v1::handler_collector_register),
@@ -250,6 +612,8 @@ async fn run_app(hostname: String, port: u16, opts: Options) -> std::io::Result<
                         .default_service(web::get().to(router::terraform::handler_terraform)),
                 )
                 .route("/dump", web::post().to(router::handler_dump))
+                // Prometheus remote write
+                .service(router::prometheus::remote_write_service())
                 // OTLP
                 .service(
                     web::scope("/receiver/v1")
@@ -273,6 +637,15 @@ async fn run_app(hostname: String, port: u16, opts: Options) -> std::io::Result<
         }
     };
 
+    if let Some(grpc_port) = grpc_port {
+        let grpc_server = router::otlp::grpc::run_grpc_server(grpc_port, app_state.clone(), opts);
+        actix_rt::spawn(async move {
+            if let Err(e) = grpc_server.await {
+                error!("gRPC server error: {}", e);
+            }
+        });
+    }
+
     // Try to bind to [::] first, fallback to IPv4 if it fails
     let result = match actix_web::HttpServer::new(create_app.clone()).bind(format!("[::]:{}", port)) {
         Ok(server) => {
@@ -294,6 +667,11 @@ async fn run_app(hostname: String, port: u16, opts: Options) -> std::io::Result<
         }
     };
 
+    if let Some((path, _)) = &state_file {
+        info!("Saving the state to {}", path.display());
+        state::save_state(path, &app_state, &terraform_state);
+    }
+
     match result {
         Ok(result) => Ok(result),
         Err(e) => {
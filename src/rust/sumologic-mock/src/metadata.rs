@@ -55,6 +55,45 @@ pub fn parse_sumo_fields_header_value(header_value: &str) -> Result<Metadata, an
     return Ok(field_values);
 }
 
+// Metadata applied to every metric received in a request
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsMetadata {
+    // X-Sumo-Dimensions, these identify the time series
+    pub dimensions: Metadata,
+    // X-Sumo-Metadata and the common X-Sumo-* headers
+    pub metadata: Metadata,
+}
+
+// Get the metadata for metrics from X-Sumo-Dimensions, X-Sumo-Metadata and the common headers.
+// Both X-Sumo-Dimensions and X-Sumo-Metadata are comma separated key=value lists, like X-Sumo-Fields.
+// The dedicated headers (X-Sumo-Name, X-Sumo-Host, X-Sumo-Category) take precedence over
+// the same fields set in X-Sumo-Metadata.
+pub fn get_metrics_metadata_from_headers(headers: &HeaderMap) -> Result<MetricsMetadata, anyhow::Error> {
+    let mut metrics_metadata = MetricsMetadata::default();
+
+    if let Some(header_value) = headers.get("x-sumo-dimensions") {
+        let header_value_str = header_value
+            .to_str()
+            .map_err(|_| anyhow!("Couldn't parse X-Sumo-Dimensions header value"))?;
+        metrics_metadata.dimensions = parse_sumo_fields_header_value(header_value_str)
+            .map_err(|_| anyhow!("Couldn't parse X-Sumo-Dimensions header value"))?;
+    }
+
+    if let Some(header_value) = headers.get("x-sumo-metadata") {
+        let header_value_str = header_value
+            .to_str()
+            .map_err(|_| anyhow!("Couldn't parse X-Sumo-Metadata header value"))?;
+        metrics_metadata.metadata = parse_sumo_fields_header_value(header_value_str)
+            .map_err(|_| anyhow!("Couldn't parse X-Sumo-Metadata header value"))?;
+    }
+
+    metrics_metadata
+        .metadata
+        .extend(get_common_metadata_from_headers(headers)?);
+
+    Ok(metrics_metadata)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -136,4 +175,61 @@ mod tests {
             Err(error) => assert_eq!(error.to_string(), "Couldn't parse X-Sumo-Name header value"),
         }
     }
+
+    #[test]
+    fn test_get_metrics_metadata_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static("x-sumo-dimensions"),
+            HeaderValue::from_static("cluster=kind, namespace=sumologic"),
+        );
+        headers.insert(
+            HeaderName::from_static("x-sumo-metadata"),
+            HeaderValue::from_static("team=ops, _sourceCategory=from-metadata"),
+        );
+        headers.insert(
+            HeaderName::from_static("x-sumo-category"),
+            HeaderValue::from_static("kubernetes/metrics"),
+        );
+        let metrics_metadata = get_metrics_metadata_from_headers(&headers).unwrap();
+
+        assert_eq!(
+            metrics_metadata,
+            MetricsMetadata {
+                dimensions: HashMap::from([
+                    (String::from("cluster"), String::from("kind")),
+                    (String::from("namespace"), String::from("sumologic"))
+                ]),
+                metadata: HashMap::from([
+                    (String::from("team"), String::from("ops")),
+                    (
+                        String::from("_sourceCategory"),
+                        String::from("kubernetes/metrics")
+                    )
+                ]),
+            }
+        );
+
+        assert_eq!(
+            get_metrics_metadata_from_headers(&HeaderMap::new()).unwrap(),
+            MetricsMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_get_metrics_metadata_from_headers_invalid() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static("x-sumo-dimensions"),
+            HeaderValue::from_static("no_equals"),
+        );
+        let result = get_metrics_metadata_from_headers(&headers);
+        match result {
+            Ok(_) => panic!("Expected error, got valid result"),
+            Err(error) => assert_eq!(
+                error.to_string(),
+                "Couldn't parse X-Sumo-Dimensions header value"
+            ),
+        }
+    }
 }
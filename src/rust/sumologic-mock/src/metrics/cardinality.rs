@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::series::SeriesRepository;
+
+pub const SOURCE_CATEGORY_LABEL: &str = "_sourceCategory";
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct MetricCardinality {
+    pub metric: String,
+    pub series_count: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct LabelCardinality {
+    pub name: String,
+    pub values_count: usize,
+    pub series_count: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CardinalityReport {
+    pub series_count: usize,
+    pub metrics_count: usize,
+    pub label_names_count: usize,
+    // Sorted by the number of series, descending
+    pub series_per_metric: Vec<MetricCardinality>,
+    // Top label names by the number of distinct values
+    pub top_label_names: Vec<LabelCardinality>,
+    // A series received from more than one address is counted for each of them
+    pub series_per_source_ip: BTreeMap<String, usize>,
+    pub series_per_source_category: BTreeMap<String, usize>,
+}
+
+// Calculate the cardinality of the stored series.
+// Only labels identifying a series are taken into account for the label names, meta labels are not.
+// The source category is taken from the labels or meta labels.
+pub fn get_cardinality_report(series: &SeriesRepository, label_names_limit: usize) -> CardinalityReport {
+    let mut series_per_metric: HashMap<&str, usize> = HashMap::new();
+    // label name -> (distinct values, series count)
+    let mut labels: HashMap<&str, (HashSet<&str>, usize)> = HashMap::new();
+    let mut series_per_source_ip: BTreeMap<String, usize> = BTreeMap::new();
+    let mut series_per_source_category: BTreeMap<String, usize> = BTreeMap::new();
+
+    for (sample, source_ips) in series.iter_source_ips() {
+        *series_per_metric.entry(&sample.metric).or_default() += 1;
+        for (name, value) in sample.labels.iter() {
+            let label = labels.entry(name).or_default();
+            label.0.insert(value);
+            label.1 += 1;
+        }
+        for source_ip in source_ips {
+            *series_per_source_ip.entry(source_ip.to_string()).or_default() += 1;
+        }
+        if let Some(category) = sample
+            .labels
+            .get(SOURCE_CATEGORY_LABEL)
+            .or_else(|| sample.meta_labels.get(SOURCE_CATEGORY_LABEL))
+        {
+            *series_per_source_category.entry(category.clone()).or_default() += 1;
+        }
+    }
+
+    // Ties are sorted by name, so the report is stable
+    let mut metrics: Vec<MetricCardinality> = series_per_metric
+        .into_iter()
+        .map(|(metric, series_count)| MetricCardinality {
+            metric: metric.to_string(),
+            series_count,
+        })
+        .collect();
+    metrics.sort_by(|a, b| {
+        b.series_count
+            .cmp(&a.series_count)
+            .then_with(|| a.metric.cmp(&b.metric))
+    });
+
+    let label_names_count = labels.len();
+    let mut top_label_names: Vec<LabelCardinality> = labels
+        .into_iter()
+        .map(|(name, (values, series_count))| LabelCardinality {
+            name: name.to_string(),
+            values_count: values.len(),
+            series_count,
+        })
+        .collect();
+    top_label_names.sort_by(|a, b| {
+        b.values_count
+            .cmp(&a.values_count)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    top_label_names.truncate(label_names_limit);
+
+    CardinalityReport {
+        series_count: series.len(),
+        metrics_count: metrics.len(),
+        label_names_count,
+        series_per_metric: metrics,
+        top_label_names,
+        series_per_source_ip,
+        series_per_source_category,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::sample::Sample;
+    use std::net::IpAddr;
+
+    fn get_sample(metric: &str, labels: &[(&str, &str)], meta_labels: &[(&str, &str)]) -> Sample {
+        let to_map = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        Sample {
+            metric: metric.to_string(),
+            value: 1.0,
+            labels: to_map(labels),
+            timestamp: 0,
+            meta_labels: to_map(meta_labels),
+        }
+    }
+
+    #[test]
+    fn test_cardinality_report() {
+        let first_ip = IpAddr::from([10, 0, 0, 1]);
+        let second_ip = IpAddr::from([10, 0, 0, 2]);
+        let mut series = SeriesRepository::new(10);
+        for pod in ["a", "b", "c"] {
+            series.add_sample(
+                get_sample(
+                    "requests",
+                    &[("pod", pod), ("job", "api")],
+                    &[(SOURCE_CATEGORY_LABEL, "kubernetes/api")],
+                ),
+                first_ip,
+            );
+        }
+        series.add_sample(get_sample("up", &[("job", "api")], &[]), first_ip);
+        series.add_sample(get_sample("up", &[("job", "api")], &[]), second_ip);
+        series.add_sample(
+            get_sample("up", &[("job", "web"), (SOURCE_CATEGORY_LABEL, "web")], &[]),
+            second_ip,
+        );
+
+        let report = get_cardinality_report(&series, 2);
+        assert_eq!(report.series_count, 5);
+        assert_eq!(report.metrics_count, 2);
+        assert_eq!(report.label_names_count, 3);
+        assert_eq!(
+            report.series_per_metric,
+            vec![
+                MetricCardinality {
+                    metric: String::from("requests"),
+                    series_count: 3
+                },
+                MetricCardinality {
+                    metric: String::from("up"),
+                    series_count: 2
+                },
+            ]
+        );
+        assert_eq!(
+            report.top_label_names,
+            vec![
+                LabelCardinality {
+                    name: String::from("pod"),
+                    values_count: 3,
+                    series_count: 3
+                },
+                LabelCardinality {
+                    name: String::from("job"),
+                    values_count: 2,
+                    series_count: 5
+                },
+            ]
+        );
+        assert_eq!(
+            report.series_per_source_ip,
+            BTreeMap::from([(String::from("10.0.0.1"), 4), (String::from("10.0.0.2"), 2)])
+        );
+        assert_eq!(
+            report.series_per_source_category,
+            BTreeMap::from([(String::from("kubernetes/api"), 3), (String::from("web"), 1)])
+        );
+    }
+}
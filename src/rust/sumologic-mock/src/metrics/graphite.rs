@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::metrics::sample::Sample;
+use crate::time;
+
+// Template used when none of the configured templates matches the metric path
+const DEFAULT_TEMPLATE: &str = "host.measurement.field*";
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    Skip,
+    Measurement,
+    Field,
+    Tag(String),
+}
+
+// Graphite template along the lines of Telegraf/InfluxDB templates:
+//   [filter] template [default tags]
+// e.g. `cpu.* host.measurement.field* region=us-east,env=prod`.
+// Template parts are separated with dots and can be `measurement`, `field`, a tag name,
+// or empty to skip a path segment. A `*` suffix on the last part makes it consume
+// all the remaining segments.
+// Reference: https://github.com/influxdata/telegraf/blob/master/docs/TEMPLATE_PATTERN.md
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphiteTemplate {
+    filter: Option<Vec<String>>,
+    parts: Vec<TemplatePart>,
+    wildcard: bool,
+    default_tags: HashMap<String, String>,
+}
+
+impl GraphiteTemplate {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let tokens: Vec<&str> = spec.split_whitespace().collect();
+        let (filter, template, tags) = match tokens.as_slice() {
+            [template] => (None, *template, None),
+            [template, tags] if tags.contains('=') => (None, *template, Some(*tags)),
+            [filter, template] => (Some(*filter), *template, None),
+            [filter, template, tags] => (Some(*filter), *template, Some(*tags)),
+            _ => return Err(anyhow!("Invalid Graphite template: '{}'", spec)),
+        };
+
+        let elements: Vec<&str> = template.split('.').collect();
+        let mut parts = Vec::with_capacity(elements.len());
+        let mut wildcard = false;
+        for (i, element) in elements.iter().enumerate() {
+            let name = match element.strip_suffix('*') {
+                Some(name) if i == elements.len() - 1 => {
+                    wildcard = true;
+                    name
+                }
+                Some(_) => {
+                    return Err(anyhow!(
+                        "Only the last part of a Graphite template can end with '*'"
+                    ))
+                }
+                None => element,
+            };
+            parts.push(match name {
+                "" => TemplatePart::Skip,
+                "measurement" => TemplatePart::Measurement,
+                "field" => TemplatePart::Field,
+                tag => TemplatePart::Tag(tag.to_string()),
+            });
+        }
+        if !parts.contains(&TemplatePart::Measurement) {
+            return Err(anyhow!("Graphite template '{}' has no measurement", template));
+        }
+
+        let mut default_tags = HashMap::new();
+        if let Some(tags) = tags {
+            for tag in tags.split(',') {
+                match tag.split_once('=') {
+                    Some((key, value)) if !key.is_empty() => {
+                        default_tags.insert(key.to_string(), value.to_string());
+                    }
+                    _ => return Err(anyhow!("Invalid Graphite template tag: '{}'", tag)),
+                }
+            }
+        }
+
+        Ok(Self {
+            filter: filter.map(|f| f.split('.').map(String::from).collect()),
+            parts,
+            wildcard,
+            default_tags,
+        })
+    }
+
+    // Filters match path prefixes, `*` matches any single segment
+    fn matches(&self, segments: &[&str]) -> bool {
+        match &self.filter {
+            Some(filter) => {
+                filter.len() <= segments.len()
+                    && filter
+                        .iter()
+                        .zip(segments.iter())
+                        .all(|(f, s)| f == "*" || f == s)
+            }
+            None => true,
+        }
+    }
+
+    // Get the metric name and labels for the given path segments.
+    // The name is built from the measurement and field segments joined with underscores.
+    fn apply(&self, segments: &[&str]) -> Result<(String, HashMap<String, String>)> {
+        let mut measurement = vec![];
+        let mut field = vec![];
+        let mut tags: HashMap<String, Vec<&str>> = HashMap::new();
+
+        for (i, segment) in segments.iter().enumerate() {
+            let part = if i < self.parts.len() {
+                &self.parts[i]
+            } else if self.wildcard {
+                &self.parts[self.parts.len() - 1]
+            } else {
+                break;
+            };
+            match part {
+                TemplatePart::Skip => (),
+                TemplatePart::Measurement => measurement.push(*segment),
+                TemplatePart::Field => field.push(*segment),
+                TemplatePart::Tag(name) => tags.entry(name.clone()).or_default().push(segment),
+            }
+        }
+
+        if measurement.is_empty() {
+            return Err(anyhow!(
+                "No measurement in Graphite metric path: {}",
+                segments.join(".")
+            ));
+        }
+        let mut name = measurement.join("_");
+        if !field.is_empty() {
+            name = format!("{}_{}", name, field.join("_"));
+        }
+
+        let mut labels = self.default_tags.clone();
+        labels.extend(tags.into_iter().map(|(name, values)| (name, values.join("."))));
+        Ok((name, labels))
+    }
+}
+
+// Parse a metric line in Graphite format: `<metric path> <value> <timestamp>`.
+// The first template with a matching filter is used, `host.measurement.field*` if none matches.
+// Graphite tags (`<metric path>;tag1=value1;tag2=value2`) are added to the labels.
+pub fn graphite_line_to_sample(line: &str, templates: &[GraphiteTemplate]) -> Result<Sample> {
+    let split_line = line.split_whitespace().collect::<Vec<_>>();
+    let (path, value, timestamp) = match split_line.as_slice() {
+        [path, value] => (*path, *value, None),
+        [path, value, timestamp] => (*path, *value, Some(*timestamp)),
+        _ => return Err(anyhow!("Incorrect graphite metric line: {}", line)),
+    };
+
+    let mut path_tags = path.split(';');
+    let path = path_tags.next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('.').collect();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(anyhow!("Incorrect graphite metric name: {}", path));
+    }
+
+    let (metric, mut labels) = match templates.iter().find(|t| t.matches(&segments)) {
+        Some(template) => template.apply(&segments)?,
+        None => GraphiteTemplate::parse(DEFAULT_TEMPLATE)?.apply(&segments)?,
+    };
+    for tag in path_tags {
+        match tag.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                labels.insert(key.to_string(), value.to_string());
+            }
+            _ => return Err(anyhow!("Incorrect graphite metric tag: {}", tag)),
+        }
+    }
+
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| anyhow!("Incorrect graphite metric value: {}", line))?;
+    // Graphite timestamps are in seconds
+    let timestamp = match timestamp {
+        Some(timestamp) => match timestamp.parse::<u64>() {
+            Ok(timestamp) => timestamp * 1000,
+            Err(_) => return Err(anyhow!("Incorrect graphite metric timestamp: {}", line)),
+        },
+        None => time::get_now_ms(),
+    };
+
+    Ok(Sample {
+        metric,
+        value,
+        labels,
+        timestamp,
+        meta_labels: HashMap::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_default_template() {
+        let sample = graphite_line_to_sample("myhostname.mem.available 33310904320 1601909210", &[]).unwrap();
+        assert_eq!(sample.metric, "mem_available");
+        assert_eq!(sample.value, 33310904320.0);
+        assert_eq!(sample.timestamp, 1601909210000);
+        assert_eq!(sample.labels, labels(&[("host", "myhostname")]));
+
+        // the field consumes all the remaining segments
+        let sample = graphite_line_to_sample("myhostname.disk.used.percent 12.5 1601909210", &[]).unwrap();
+        assert_eq!(sample.metric, "disk_used_percent");
+    }
+
+    #[test]
+    fn test_templates() {
+        let templates = vec![
+            GraphiteTemplate::parse("servers.* .host.measurement* env=prod").unwrap(),
+            GraphiteTemplate::parse("region.host.measurement.field").unwrap(),
+        ];
+
+        let sample = graphite_line_to_sample("servers.localhost.cpu.loadavg.10 1.5 1601909210", &templates).unwrap();
+        assert_eq!(sample.metric, "cpu_loadavg_10");
+        assert_eq!(sample.labels, labels(&[("host", "localhost"), ("env", "prod")]));
+
+        // extra segments are ignored without a wildcard
+        let sample = graphite_line_to_sample("us-west.node1.mem.free.extra 10 1601909210", &templates).unwrap();
+        assert_eq!(sample.metric, "mem_free");
+        assert_eq!(
+            sample.labels,
+            labels(&[("region", "us-west"), ("host", "node1")])
+        );
+    }
+
+    #[test]
+    fn test_graphite_tags() {
+        let sample = graphite_line_to_sample("myhostname.mem.free;team=ops;dc=eu 10 1601909210", &[]).unwrap();
+        assert_eq!(sample.metric, "mem_free");
+        assert_eq!(
+            sample.labels,
+            labels(&[("host", "myhostname"), ("team", "ops"), ("dc", "eu")])
+        );
+    }
+
+    #[test]
+    fn test_malformed_lines() {
+        assert!(graphite_line_to_sample("myhostname.mem.free", &[]).is_err());
+        assert!(graphite_line_to_sample("myhostname.mem.free 1 2 3", &[]).is_err());
+        assert!(graphite_line_to_sample("myhostname.mem.free abc 1601909210", &[]).is_err());
+        assert!(graphite_line_to_sample("myhostname.mem.free 1 abc", &[]).is_err());
+        assert!(graphite_line_to_sample("myhostname..free 1 1601909210", &[]).is_err());
+        // the default template needs at least two segments to have a measurement
+        assert!(graphite_line_to_sample("myhostname 1 1601909210", &[]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(GraphiteTemplate::parse("host.field").is_err());
+        assert!(GraphiteTemplate::parse("host*.measurement").is_err());
+        assert!(GraphiteTemplate::parse("a.* host.measurement tag").is_err());
+        assert!(GraphiteTemplate::parse("a b c d").is_err());
+    }
+}
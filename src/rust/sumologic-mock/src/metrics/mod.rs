@@ -1,28 +1,37 @@
 use log::debug;
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::net::IpAddr;
 
 use sample::*;
 
+use crate::metadata::MetricsMetadata;
 use crate::options;
 
+pub mod cardinality;
+pub mod graphite;
+pub mod promql;
+pub mod remote_write;
 pub mod sample;
+pub mod series;
 
 pub struct MetricsHandleResult {
     pub metrics_count: u64,
+    pub malformed_count: u64,
     pub metrics_list: HashMap<String, u64>,
     pub metrics_ip_list: HashMap<IpAddr, u64>,
-    pub metrics_samples: HashSet<Sample>,
+    // All the received samples in the order of receiving,
+    // including multiple data points of the same series
+    pub metrics_samples: Vec<Sample>,
 }
 
 impl MetricsHandleResult {
     pub fn new() -> Self {
         return Self {
             metrics_count: 0,
+            malformed_count: 0,
             metrics_list: HashMap::new(),
             metrics_ip_list: HashMap::new(),
-            metrics_samples: HashSet::new(),
+            metrics_samples: Vec::new(),
         };
     }
 
@@ -36,31 +45,39 @@ impl MetricsHandleResult {
         let metrics_ip_list = self.metrics_ip_list.entry(ip_address).or_insert(0);
         *metrics_ip_list += 1;
     }
+
+    // Add the request metadata to all the samples.
+    pub fn add_metadata(&mut self, metrics_metadata: &MetricsMetadata) {
+        for sample in self.metrics_samples.iter_mut() {
+            sample.add_metadata(metrics_metadata);
+        }
+    }
 }
 
 // Handle metrics in Carbon2.0 format
 // Reference: https://help.sumologic.com/Metrics/Introduction-to-Metrics/Metric-Formats#carbon-2-0
-pub fn handle_carbon2(lines: std::str::Lines, address: IpAddr, print_opts: options::Print) -> MetricsHandleResult {
+pub fn handle_carbon2(lines: std::str::Lines, address: IpAddr, opts: &options::Options) -> MetricsHandleResult {
     let mut result = MetricsHandleResult::new();
 
     for line in lines {
-        if print_opts.metrics {
+        if opts.print.metrics {
             debug!("metric => {}", line);
         }
-        let mut split = line.split("  ");
-        let intrinsic_metrics = split.nth(0).unwrap();
-        for metric in intrinsic_metrics.split(" ") {
-            let mut split = metric.split("=");
-            let field_name = split.nth(0).unwrap().to_string();
-            if field_name == "metric" {
-                // nth() consumes elements hence nth(0) again
-                let metric_name = split.nth(0).unwrap().to_string();
-                result.handle_metric(metric_name);
-                break;
+
+        let sample = match carbon2_line_to_sample(line) {
+            Ok(sample) => sample,
+            Err(e) => {
+                debug!("{}", e);
+                result.malformed_count += 1;
+                continue;
             }
-        }
+        };
 
+        result.handle_metric(sample.metric.clone());
         result.handle_ip(address);
+        if opts.store_metrics {
+            result.metrics_samples.push(sample);
+        }
     }
 
     result
@@ -68,29 +85,28 @@ pub fn handle_carbon2(lines: std::str::Lines, address: IpAddr, print_opts: optio
 
 // Handle metrics in Graphite format
 // Reference: https://help.sumologic.com/Metrics/Introduction-to-Metrics/Metric-Formats#graphite
-pub fn handle_graphite(lines: std::str::Lines, address: IpAddr, print_opts: options::Print) -> MetricsHandleResult {
+pub fn handle_graphite(lines: std::str::Lines, address: IpAddr, opts: &options::Options) -> MetricsHandleResult {
     let mut result = MetricsHandleResult::new();
 
     for line in lines {
-        if print_opts.metrics {
+        if opts.print.metrics {
             debug!("metric => {}", line);
         }
-        let split_line = line.split(' ').collect::<Vec<_>>();
-        if split_line.len() != 3 {
-            debug!("Incorrect graphite metric line: {}", line);
-            continue;
-        }
 
-        let split_metric = split_line[0].split('.').collect::<Vec<_>>();
-        if split_metric.len() != 3 {
-            debug!("Incorrect graphite metric name: {}", split_line[0]);
-            continue;
-        }
+        let sample = match graphite::graphite_line_to_sample(line, &opts.graphite_templates) {
+            Ok(sample) => sample,
+            Err(e) => {
+                debug!("{}", e);
+                result.malformed_count += 1;
+                continue;
+            }
+        };
 
-        let metric_name = split_metric[1];
-        let metric_field = split_metric[2];
-        result.handle_metric(format!("{}_{}", metric_name, metric_field));
+        result.handle_metric(sample.metric.clone());
         result.handle_ip(address);
+        if opts.store_metrics {
+            result.metrics_samples.push(sample);
+        }
     }
 
     result
@@ -98,12 +114,20 @@ pub fn handle_graphite(lines: std::str::Lines, address: IpAddr, print_opts: opti
 
 // Handle metrics in Prometheus format
 // Reference: https://help.sumologic.com/Metrics/Introduction-to-Metrics/Metric-Formats#prometheus
-pub fn handle_prometheus(lines: std::str::Lines, address: IpAddr, opts: &options::Options) -> MetricsHandleResult {
+pub fn handle_prometheus(
+    lines: std::str::Lines,
+    address: IpAddr,
+    opts: &options::Options,
+) -> anyhow::Result<MetricsHandleResult> {
     let mut result = MetricsHandleResult::new();
 
     let mut lines_vec = vec![];
     for l in lines {
         if l.starts_with("#") {
+            // Comments aren't metrics, but TYPE lines tell the parser which series are histograms and summaries
+            if opts.store_metrics {
+                lines_vec.push(l.to_owned());
+            }
             continue;
         }
 
@@ -121,10 +145,10 @@ pub fn handle_prometheus(lines: std::str::Lines, address: IpAddr, opts: &options
     }
 
     if opts.store_metrics {
-        result.metrics_samples = lines_to_samples(lines_vec);
+        result.metrics_samples = lines_to_samples(lines_vec)?.into_iter().collect();
     }
 
-    result
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -147,13 +171,11 @@ metric=mem_available host=myhostname  34655776768 1601906858"
             .lines();
 
         let ip_address = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
-        let print_opts = options::Print {
-            logs: false,
-            headers: false,
-            metrics: false,
-            spans: false,
+        let opts = options::Options {
+            store_metrics: true,
+            ..Default::default()
         };
-        let result = handle_carbon2(lines, ip_address, print_opts);
+        let result = handle_carbon2(lines, ip_address, &opts);
 
         assert_eq!(result.metrics_count, 9);
 
@@ -172,6 +194,33 @@ metric=mem_available host=myhostname  34655776768 1601906858"
 
         assert_eq!(result.metrics_ip_list.contains_key(&ip_address), true);
         assert_eq!(*result.metrics_ip_list.get(&ip_address).unwrap(), 9);
+
+        assert_eq!(result.metrics_samples.len(), 9);
+        let sample = result
+            .metrics_samples
+            .iter()
+            .find(|s| s.metric == "mem_free")
+            .unwrap();
+        assert_eq!(sample.value, 12677414912.0);
+        assert_eq!(sample.timestamp, 1601906858000);
+        assert_eq!(sample.labels.get("host"), Some(&String::from("myhostname")));
+    }
+
+    #[test]
+    fn test_carbon_malformed() {
+        let lines = "metric
+host=myhostname  50 1601906858
+metric=mem_free host=myhostname  12677414912 1601906858"
+            .lines();
+
+        let ip_address = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+        let opts = options::Options::default();
+        let result = handle_carbon2(lines, ip_address, &opts);
+
+        assert_eq!(result.metrics_count, 1);
+        assert_eq!(result.malformed_count, 2);
+        assert_eq!(result.metrics_list.len(), 1);
+        assert_eq!(result.metrics_list.get("mem_free"), Some(&1));
     }
 
     #[test]
@@ -189,19 +238,10 @@ mem_free{host="myhostname"} 1.190197248e+10"##
 
         let ip_address = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
         let opts = options::Options {
-            print: options::Print {
-                logs: false,
-                headers: false,
-                metrics: false,
-                spans: false,
-            },
-            delay_time: std::time::Duration::from_secs(0),
-            drop_rate: 0,
-            store_traces: false,
-            store_metrics: false,
             store_logs: true,
+            ..Default::default()
         };
-        let result = handle_prometheus(lines, ip_address, &opts);
+        let result = handle_prometheus(lines, ip_address, &opts).unwrap();
 
         assert_eq!(result.metrics_count, 9);
 
@@ -236,13 +276,11 @@ myhostname.mem.wired 5680394240 1601909210"
             .lines();
 
         let ip_address = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
-        let print_opts = options::Print {
-            logs: false,
-            headers: false,
-            metrics: false,
-            spans: false,
+        let opts = options::Options {
+            store_metrics: true,
+            ..Default::default()
         };
-        let result = handle_graphite(lines, ip_address, print_opts);
+        let result = handle_graphite(lines, ip_address, &opts);
 
         assert_eq!(result.metrics_count, 9);
 
@@ -261,5 +299,50 @@ myhostname.mem.wired 5680394240 1601909210"
 
         assert_eq!(result.metrics_ip_list.contains_key(&ip_address), true);
         assert_eq!(*result.metrics_ip_list.get(&ip_address).unwrap(), 9);
+
+        assert_eq!(result.malformed_count, 0);
+        assert_eq!(result.metrics_samples.len(), 9);
+        let sample = result
+            .metrics_samples
+            .iter()
+            .find(|s| s.metric == "mem_available")
+            .unwrap();
+        assert_eq!(sample.value, 33310904320.0);
+        assert_eq!(sample.timestamp, 1601909210000);
+        assert_eq!(sample.labels.get("host"), Some(&String::from("myhostname")));
+    }
+
+    #[test]
+    fn test_graphite_templates() {
+        let lines = "servers.node-1.cpu.load.1m 0.5 1601909210
+servers.node-1.cpu.idle 97.5 1601909210
+servers.node-1.cpu.idle
+servers.node-1.cpu.idle abc 1601909210
+servers 1 1601909210"
+            .lines();
+
+        let ip_address = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+        let opts = options::Options {
+            store_metrics: true,
+            graphite_templates: vec![graphite::GraphiteTemplate::parse("servers.* .host.measurement.field*").unwrap()],
+            ..Default::default()
+        };
+        let result = handle_graphite(lines, ip_address, &opts);
+
+        assert_eq!(result.metrics_count, 2);
+        assert_eq!(result.malformed_count, 3);
+
+        let mut metrics_list: HashMap<String, u64> = HashMap::new();
+        metrics_list.insert(String::from("cpu_load_1m"), 1);
+        metrics_list.insert(String::from("cpu_idle"), 1);
+        assert_eq!(result.metrics_list, metrics_list);
+
+        let sample = result
+            .metrics_samples
+            .iter()
+            .find(|s| s.metric == "cpu_load_1m")
+            .unwrap();
+        assert_eq!(sample.value, 0.5);
+        assert_eq!(sample.labels.get("host"), Some(&String::from("node-1")));
     }
 }
@@ -0,0 +1,798 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use fancy_regex::Regex;
+
+use crate::metrics::sample::Sample;
+use crate::metrics::series::{DataPoint, SeriesRepository};
+
+// A small subset of PromQL, evaluated over the stored metrics series:
+// - instant and range vector selectors with `=`, `!=`, `=~` and `!~` matchers,
+//   e.g. `up{job="apiserver", code=~"5.."}` and `up[5m]`
+// - `rate` and `increase` of range vectors, without the extrapolation done by Prometheus
+// - `sum`, `count`, `avg`, `min` and `max` aggregations with `by` and `without`
+// Reference: https://prometheus.io/docs/prometheus/latest/querying/basics/
+
+// The same as the Prometheus default
+pub const LOOKBACK_DELTA_MS: u64 = 5 * 60 * 1000;
+
+// Series labels including the metric name as `__name__`
+pub type Labels = BTreeMap<String, String>;
+
+const METRIC_NAME_LABEL: &str = "__name__";
+
+// Labels of the series a sample belongs to. Meta labels are included, labels take precedence.
+pub fn get_sample_labels(sample: &Sample) -> Labels {
+    let mut labels: Labels = sample
+        .meta_labels
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    labels.extend(
+        sample
+            .labels
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone())),
+    );
+    labels.insert(METRIC_NAME_LABEL.to_string(), sample.metric.clone());
+    labels
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatchOp {
+    Equal,
+    NotEqual,
+    RegexMatch,
+    RegexNoMatch,
+}
+
+#[derive(Debug)]
+pub struct LabelMatcher {
+    pub name: String,
+    pub op: MatchOp,
+    pub value: String,
+    regex: Option<Regex>,
+}
+
+impl LabelMatcher {
+    pub fn new(name: &str, op: MatchOp, value: &str) -> Result<Self> {
+        // Regular expressions are fully anchored, as in Prometheus
+        let regex = match op {
+            MatchOp::RegexMatch | MatchOp::RegexNoMatch => Some(Regex::new(&format!("^(?:{})$", value))?),
+            MatchOp::Equal | MatchOp::NotEqual => None,
+        };
+        Ok(Self {
+            name: name.to_string(),
+            op,
+            value: value.to_string(),
+            regex,
+        })
+    }
+
+    // Missing labels are treated as labels with an empty value
+    fn matches(&self, value: &str) -> bool {
+        let regex_matches = || {
+            self.regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(value).unwrap_or(false))
+        };
+        match self.op {
+            MatchOp::Equal => value == self.value,
+            MatchOp::NotEqual => value != self.value,
+            MatchOp::RegexMatch => regex_matches(),
+            MatchOp::RegexNoMatch => !regex_matches(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Selector {
+    pub matchers: Vec<LabelMatcher>,
+}
+
+impl Selector {
+    // Parse a series selector, e.g. a `match[]` parameter of the Prometheus HTTP API
+    pub fn parse(input: &str) -> Result<Self> {
+        match parse(input)? {
+            Expr::VectorSelector(selector) => Ok(selector),
+            _ => Err(anyhow!("Expected a series selector: {}", input)),
+        }
+    }
+
+    pub fn matches(&self, labels: &Labels) -> bool {
+        self.matchers.iter().all(|matcher| {
+            matcher.matches(
+                labels
+                    .get(&matcher.name)
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+            )
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Function {
+    Rate,
+    Increase,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AggregateOp {
+    Sum,
+    Count,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Grouping {
+    By(Vec<String>),
+    Without(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    VectorSelector(Selector),
+    // Range in milliseconds
+    MatrixSelector(Selector, u64),
+    Call(Function, Box<Expr>),
+    Aggregate(AggregateOp, Grouping, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InstantSample {
+    pub labels: Labels,
+    pub value: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RangeSeries {
+    pub labels: Labels,
+    pub points: Vec<DataPoint>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Vector(Vec<InstantSample>),
+    Matrix(Vec<RangeSeries>),
+}
+
+// Evaluate the expression at the given time, in milliseconds.
+// Results are sorted by labels.
+pub fn evaluate(expr: &Expr, series: &SeriesRepository, time: u64) -> Result<Value> {
+    match expr {
+        Expr::VectorSelector(selector) => {
+            let from = time.saturating_sub(LOOKBACK_DELTA_MS);
+            let mut result: Vec<InstantSample> = series
+                .iter()
+                .filter_map(|(sample, points)| {
+                    let labels = get_sample_labels(sample);
+                    if !selector.matches(&labels) {
+                        return None;
+                    }
+                    // The latest point in the lookback window, points are kept in the order of receiving
+                    let point = points
+                        .iter()
+                        .filter(|point| from < point.timestamp && point.timestamp <= time)
+                        .fold(None, |latest: Option<&DataPoint>, point| match latest {
+                            Some(latest) if latest.timestamp > point.timestamp => Some(latest),
+                            _ => Some(point),
+                        })?;
+                    Some(InstantSample {
+                        labels,
+                        value: point.value,
+                    })
+                })
+                .collect();
+            result.sort_by(|a, b| a.labels.cmp(&b.labels));
+            Ok(Value::Vector(result))
+        }
+        Expr::MatrixSelector(selector, range) => {
+            let from = time.saturating_sub(*range);
+            let mut result: Vec<RangeSeries> = series
+                .iter()
+                .filter_map(|(sample, points)| {
+                    let labels = get_sample_labels(sample);
+                    if !selector.matches(&labels) {
+                        return None;
+                    }
+                    let mut points: Vec<DataPoint> = points
+                        .iter()
+                        .filter(|point| from < point.timestamp && point.timestamp <= time)
+                        .cloned()
+                        .collect();
+                    if points.is_empty() {
+                        return None;
+                    }
+                    points.sort_by_key(|point| point.timestamp);
+                    Some(RangeSeries { labels, points })
+                })
+                .collect();
+            result.sort_by(|a, b| a.labels.cmp(&b.labels));
+            Ok(Value::Matrix(result))
+        }
+        Expr::Call(function, arg) => {
+            let matrix = match evaluate(arg, series, time)? {
+                Value::Matrix(matrix) => matrix,
+                Value::Vector(_) => return Err(anyhow!("Expected a range vector in {:?}", function)),
+            };
+            let result = matrix
+                .into_iter()
+                .filter_map(|mut series| {
+                    let value = match function {
+                        Function::Rate => get_rate(&series.points)?,
+                        Function::Increase => get_increase(&series.points)?,
+                    };
+                    series.labels.remove(METRIC_NAME_LABEL);
+                    Some(InstantSample {
+                        labels: series.labels,
+                        value,
+                    })
+                })
+                .collect();
+            Ok(Value::Vector(result))
+        }
+        Expr::Aggregate(op, grouping, arg) => {
+            let vector = match evaluate(arg, series, time)? {
+                Value::Vector(vector) => vector,
+                Value::Matrix(_) => return Err(anyhow!("Expected an instant vector in {:?}", op)),
+            };
+            Ok(Value::Vector(aggregate(*op, grouping, vector)))
+        }
+    }
+}
+
+// Increase of a counter, taking counter resets into account
+fn get_increase(points: &[DataPoint]) -> Option<f64> {
+    if points.len() < 2 {
+        return None;
+    }
+    let mut increase = 0.0;
+    for pair in points.windows(2) {
+        let (previous, current) = (pair[0].value, pair[1].value);
+        increase += if current < previous {
+            current
+        } else {
+            current - previous
+        };
+    }
+    Some(increase)
+}
+
+// Per-second rate of increase between the first and the last point in the range
+fn get_rate(points: &[DataPoint]) -> Option<f64> {
+    let increase = get_increase(points)?;
+    let duration_ms = points[points.len() - 1].timestamp - points[0].timestamp;
+    if duration_ms == 0 {
+        return None;
+    }
+    Some(increase * 1000.0 / duration_ms as f64)
+}
+
+fn aggregate(op: AggregateOp, grouping: &Grouping, vector: Vec<InstantSample>) -> Vec<InstantSample> {
+    // (sum, count, min, max) of every group
+    let mut groups: BTreeMap<Labels, (f64, usize, f64, f64)> = BTreeMap::new();
+    for sample in vector {
+        let labels: Labels = sample
+            .labels
+            .into_iter()
+            .filter(|(name, _)| match grouping {
+                Grouping::By(names) => names.contains(name),
+                Grouping::Without(names) => name != METRIC_NAME_LABEL && !names.contains(name),
+            })
+            .collect();
+        let group = groups
+            .entry(labels)
+            .or_insert((0.0, 0, f64::INFINITY, f64::NEG_INFINITY));
+        group.0 += sample.value;
+        group.1 += 1;
+        group.2 = group.2.min(sample.value);
+        group.3 = group.3.max(sample.value);
+    }
+
+    groups
+        .into_iter()
+        .map(|(labels, (sum, count, min, max))| InstantSample {
+            labels,
+            value: match op {
+                AggregateOp::Sum => sum,
+                AggregateOp::Count => count as f64,
+                AggregateOp::Avg => sum / count as f64,
+                AggregateOp::Min => min,
+                AggregateOp::Max => max,
+            },
+        })
+        .collect()
+}
+
+// Parse a duration like `5m`, `1h30m` or `500ms` into milliseconds
+pub fn parse_duration(input: &str) -> Result<u64> {
+    let invalid = || anyhow!("Invalid duration: '{}'", input);
+    let mut rest = input;
+    let mut duration = 0;
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let number: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            "d" => 24 * 60 * 60 * 1000,
+            "w" => 7 * 24 * 60 * 60 * 1000,
+            "y" => 365 * 24 * 60 * 60 * 1000,
+            _ => return Err(invalid()),
+        };
+        duration = number
+            .checked_mul(unit)
+            .and_then(|value| value.checked_add(duration))
+            .ok_or_else(invalid)?;
+        rest = &rest[unit_len..];
+    }
+    Ok(duration)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Duration(u64),
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    MatchOp(MatchOp),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '{' => Token::LeftBrace,
+            '}' => Token::RightBrace,
+            ',' => Token::Comma,
+            '[' => {
+                let mut duration = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => duration.push(c),
+                        None => return Err(anyhow!("Unclosed range in: {}", input)),
+                    }
+                }
+                Token::Duration(parse_duration(duration.trim())?)
+            }
+            '=' if chars.next_if_eq(&'~').is_some() => Token::MatchOp(MatchOp::RegexMatch),
+            '=' => Token::MatchOp(MatchOp::Equal),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::MatchOp(MatchOp::NotEqual),
+            '!' if chars.next_if_eq(&'~').is_some() => Token::MatchOp(MatchOp::RegexNoMatch),
+            '"' | '\'' | '`' => {
+                let quote = c;
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(c) if c == quote => break,
+                        // Raw strings in backticks don't have escape sequences
+                        Some('\\') if quote != '`' => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(c) => value.push(c),
+                            None => return Err(anyhow!("Unterminated string in: {}", input)),
+                        },
+                        Some(c) => value.push(c),
+                        None => return Err(anyhow!("Unterminated string in: {}", input)),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == ':' => {
+                let mut ident = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == ':') {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            c => return Err(anyhow!("Unexpected character '{}' in: {}", c, input)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+// Maximum number of nested expressions in a query. This keeps the recursive parser,
+// and evaluate on the parsed expression, from overflowing the stack.
+const MAX_NESTING_DEPTH: usize = 128;
+
+// Parse a PromQL expression
+pub fn parse(input: &str) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+        depth: 0,
+    };
+    let expr = parser.parse_expr()?;
+    if let Some(token) = parser.next() {
+        return Err(anyhow!("Unexpected {:?} in: {}", token, input));
+    }
+    Ok(expr)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    // number of expressions being parsed, the current one included
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_ident(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => Some(ident),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(anyhow!("Expected {:?}, got {:?}", expected, token)),
+            None => Err(anyhow!("Expected {:?}, got the end of the query", expected)),
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(anyhow!(
+                "Query is nested deeper than {} expressions",
+                MAX_NESTING_DEPTH
+            ));
+        }
+        self.depth += 1;
+        let expr = self.parse_nested_expr();
+        self.depth -= 1;
+        expr
+    }
+
+    fn parse_nested_expr(&mut self) -> Result<Expr> {
+        let ident = self.peek_ident().map(String::from);
+        let next_is_paren = self.tokens.get(self.position + 1) == Some(&Token::LeftParen);
+        let aggregate_op = match ident.as_deref() {
+            Some("sum") => Some(AggregateOp::Sum),
+            Some("count") => Some(AggregateOp::Count),
+            Some("avg") => Some(AggregateOp::Avg),
+            Some("min") => Some(AggregateOp::Min),
+            Some("max") => Some(AggregateOp::Max),
+            _ => None,
+        };
+        let grouping_follows = matches!(
+            self.tokens.get(self.position + 1),
+            Some(Token::Ident(ident)) if ident == "by" || ident == "without"
+        );
+        if let Some(op) = aggregate_op {
+            if next_is_paren || grouping_follows {
+                self.next();
+                return self.parse_aggregation(op);
+            }
+        }
+
+        let function = match ident.as_deref() {
+            Some("rate") => Some(Function::Rate),
+            Some("increase") => Some(Function::Increase),
+            _ => None,
+        };
+        if let Some(function) = function {
+            if next_is_paren {
+                self.next();
+                self.expect(Token::LeftParen)?;
+                let arg = self.parse_expr()?;
+                self.expect(Token::RightParen)?;
+                if !matches!(arg, Expr::MatrixSelector(..)) {
+                    return Err(anyhow!("Expected a range vector selector in {:?}", function));
+                }
+                return Ok(Expr::Call(function, Box::new(arg)));
+            }
+        }
+
+        self.parse_selector()
+    }
+
+    // `<op> [by|without (<labels>)] (<expr>) [by|without (<labels>)]`
+    fn parse_aggregation(&mut self, op: AggregateOp) -> Result<Expr> {
+        let mut grouping = self.parse_grouping()?;
+        self.expect(Token::LeftParen)?;
+        let expr = self.parse_expr()?;
+        self.expect(Token::RightParen)?;
+        if grouping.is_none() {
+            grouping = self.parse_grouping()?;
+        }
+        Ok(Expr::Aggregate(
+            op,
+            grouping.unwrap_or(Grouping::By(vec![])),
+            Box::new(expr),
+        ))
+    }
+
+    fn parse_grouping(&mut self) -> Result<Option<Grouping>> {
+        let by = match self.peek_ident() {
+            Some("by") => true,
+            Some("without") => false,
+            _ => return Ok(None),
+        };
+        self.next();
+        self.expect(Token::LeftParen)?;
+        let mut labels = vec![];
+        loop {
+            match self.next() {
+                Some(Token::RightParen) => break,
+                Some(Token::Ident(label)) => {
+                    labels.push(label);
+                    match self.next() {
+                        Some(Token::Comma) => continue,
+                        Some(Token::RightParen) => break,
+                        token => return Err(anyhow!("Unexpected {:?} in grouping labels", token)),
+                    }
+                }
+                token => return Err(anyhow!("Unexpected {:?} in grouping labels", token)),
+            }
+        }
+        Ok(Some(if by {
+            Grouping::By(labels)
+        } else {
+            Grouping::Without(labels)
+        }))
+    }
+
+    // `[<metric name>][{<matchers>}][[<range>]]`
+    fn parse_selector(&mut self) -> Result<Expr> {
+        let mut matchers = vec![];
+        if let Some(Token::Ident(name)) = self.peek().cloned() {
+            self.next();
+            matchers.push(LabelMatcher::new(METRIC_NAME_LABEL, MatchOp::Equal, &name)?);
+        }
+        if self.peek() == Some(&Token::LeftBrace) {
+            self.next();
+            loop {
+                let name = match self.next() {
+                    Some(Token::RightBrace) => break,
+                    Some(Token::Ident(name)) => name,
+                    token => return Err(anyhow!("Expected a label name, got {:?}", token)),
+                };
+                let op = match self.next() {
+                    Some(Token::MatchOp(op)) => op,
+                    token => return Err(anyhow!("Expected a label matcher operator, got {:?}", token)),
+                };
+                let value = match self.next() {
+                    Some(Token::Str(value)) => value,
+                    token => return Err(anyhow!("Expected a label value string, got {:?}", token)),
+                };
+                matchers.push(LabelMatcher::new(&name, op, &value)?);
+                match self.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RightBrace) => break,
+                    token => return Err(anyhow!("Unexpected {:?} in label matchers", token)),
+                }
+            }
+        }
+        // Same as in Prometheus, a selector matching every series is not allowed
+        if matchers.iter().all(|matcher| matcher.matches("")) {
+            return Err(anyhow!(
+                "Vector selector must contain at least one matcher which doesn't match empty labels"
+            ));
+        }
+
+        let selector = Selector { matchers };
+        if let Some(Token::Duration(range)) = self.peek().cloned() {
+            self.next();
+            return Ok(Expr::MatrixSelector(selector, range));
+        }
+        Ok(Expr::VectorSelector(selector))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::net::IpAddr;
+
+    fn get_repository() -> SeriesRepository {
+        let mut repository = SeriesRepository::new(100);
+        let series = [
+            ("requests_total", "api", "200", [0.0, 60.0, 120.0]),
+            ("requests_total", "api", "500", [10.0, 16.0, 4.0]),
+            ("requests_total", "web", "200", [0.0, 30.0, 60.0]),
+        ];
+        for (metric, job, code, values) in series {
+            for (i, value) in values.iter().enumerate() {
+                let sample = Sample {
+                    metric: metric.to_string(),
+                    value: *value,
+                    labels: HashMap::from([
+                        (String::from("job"), job.to_string()),
+                        (String::from("code"), code.to_string()),
+                    ]),
+                    timestamp: 1_000_000 + i as u64 * 60_000,
+                    meta_labels: HashMap::new(),
+                };
+                repository.add_sample(sample, IpAddr::from([127, 0, 0, 1]));
+            }
+        }
+        repository
+    }
+
+    fn get_vector(query: &str, time: u64) -> Vec<(Labels, f64)> {
+        let expr = parse(query).unwrap();
+        match evaluate(&expr, &get_repository(), time).unwrap() {
+            Value::Vector(vector) => vector
+                .into_iter()
+                .map(|sample| (sample.labels, sample.value))
+                .collect(),
+            Value::Matrix(_) => panic!("Expected a vector for {}", query),
+        }
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> Labels {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5m").unwrap(), 300_000);
+        assert_eq!(parse_duration("1h30m").unwrap(), 5_400_000);
+        assert_eq!(parse_duration("500ms").unwrap(), 500);
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("5x").is_err());
+        // overflowing multiplication and addition
+        assert_eq!(
+            parse_duration("9999999999999y").unwrap_err().to_string(),
+            "Invalid duration: '9999999999999y'"
+        );
+        assert!(parse_duration("584542046y584542046y").is_err());
+        assert!(parse("up[9999999999999y]").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("{}").is_err());
+        assert!(parse(r#"{job=~".*"}"#).is_err());
+        assert!(parse("up{job=\"api\"").is_err());
+        assert!(parse("up{job=api}").is_err());
+        assert!(parse("rate(up)").is_err());
+        assert!(parse("sum(up) up").is_err());
+        assert!(parse("up[5x]").is_err());
+        assert!(parse(r#"up{job=~"("}"#).is_err());
+
+        let nested = |depth: usize| format!("{}up{}", "sum(".repeat(depth), ")".repeat(depth));
+        assert!(parse(&nested(MAX_NESTING_DEPTH - 1)).is_ok());
+        assert!(parse(&nested(MAX_NESTING_DEPTH)).is_err());
+        assert!(parse(&nested(100_000)).is_err());
+    }
+
+    #[test]
+    fn test_selectors() {
+        let time = 1_120_000;
+        let result = get_vector(r#"requests_total{job="api"}"#, time);
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            result[0],
+            (
+                labels(&[("__name__", "requests_total"), ("code", "200"), ("job", "api")]),
+                120.0
+            )
+        );
+
+        assert_eq!(get_vector(r#"requests_total{code!="200"}"#, time).len(), 1);
+        assert_eq!(
+            get_vector(r#"{__name__=~"requests_.*", job=~"a.*"}"#, time).len(),
+            2
+        );
+        // regular expressions are anchored
+        assert_eq!(get_vector(r#"requests_total{job=~"ap"}"#, time).len(), 0);
+        assert_eq!(get_vector(r#"requests_total{job!~"api|web"}"#, time).len(), 0);
+        // missing labels match empty values
+        assert_eq!(get_vector(r#"requests_total{missing=""}"#, time).len(), 3);
+
+        // the value at the given time is the latest one in the lookback window
+        assert_eq!(get_vector(r#"requests_total{job="web"}"#, 1_070_000)[0].1, 30.0);
+        assert_eq!(get_vector("requests_total", 999_999).len(), 0);
+        assert_eq!(
+            get_vector("requests_total", 1_120_000 + LOOKBACK_DELTA_MS).len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_range_selector() {
+        let expr = parse(r#"requests_total{job="web"}[1m]"#).unwrap();
+        let value = evaluate(&expr, &get_repository(), 1_120_000).unwrap();
+        assert_eq!(
+            value,
+            Value::Matrix(vec![RangeSeries {
+                labels: labels(&[("__name__", "requests_total"), ("code", "200"), ("job", "web")]),
+                points: vec![DataPoint {
+                    value: 60.0,
+                    timestamp: 1_120_000
+                }],
+            }])
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let result = get_vector(r#"rate(requests_total{job="api"}[5m])"#, 1_120_000);
+        assert_eq!(
+            result,
+            vec![
+                (labels(&[("code", "200"), ("job", "api")]), 1.0),
+                // the counter reset between 16 and 4 is taken into account
+                (labels(&[("code", "500"), ("job", "api")]), 10.0 / 120.0),
+            ]
+        );
+
+        let result = get_vector(r#"increase(requests_total{code="500"}[5m])"#, 1_120_000);
+        assert_eq!(result[0].1, 10.0);
+        // at least two points are needed
+        assert!(get_vector("rate(requests_total[1m])", 1_120_000).is_empty());
+    }
+
+    #[test]
+    fn test_aggregations() {
+        let time = 1_120_000;
+        assert_eq!(
+            get_vector("sum(requests_total)", time),
+            vec![(labels(&[]), 184.0)]
+        );
+        assert_eq!(
+            get_vector("sum by (job) (requests_total)", time),
+            vec![(labels(&[("job", "api")]), 124.0), (labels(&[("job", "web")]), 60.0)]
+        );
+        assert_eq!(
+            get_vector("count(requests_total) by (code)", time),
+            vec![(labels(&[("code", "200")]), 2.0), (labels(&[("code", "500")]), 1.0)]
+        );
+        assert_eq!(
+            get_vector("max without (code) (requests_total)", time),
+            vec![(labels(&[("job", "api")]), 120.0), (labels(&[("job", "web")]), 60.0)]
+        );
+        assert_eq!(
+            get_vector("avg(requests_total{code=\"200\"})", time),
+            vec![(labels(&[]), 90.0)]
+        );
+        assert_eq!(
+            get_vector("min by (job) (rate(requests_total[5m]))", time),
+            vec![
+                (labels(&[("job", "api")]), 10.0 / 120.0),
+                (labels(&[("job", "web")]), 0.5)
+            ]
+        );
+        // `sum` is a metric name when it isn't followed by a parenthesis
+        assert!(get_vector("sum", time).is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use log::debug;
+
+use crate::metrics::sample::Sample;
+use crate::metrics::MetricsHandleResult;
+use crate::options;
+
+// Prometheus remote write 1.0 protocol messages.
+// Only the fields the mock needs are defined, the rest (exemplars, native histograms
+// and metadata) are skipped when decoding.
+// Reference: https://prometheus.io/docs/concepts/remote_write_spec/
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    pub labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    pub samples: Vec<RemoteSample>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Label {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RemoteSample {
+    #[prost(double, tag = "1")]
+    pub value: f64,
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+}
+
+// Handle metrics sent with Prometheus remote write.
+// Every data point is counted as a metric. The `__name__` label becomes the metric name.
+pub fn handle_remote_write(request: WriteRequest, address: IpAddr, opts: &options::Options) -> MetricsHandleResult {
+    let mut result = MetricsHandleResult::new();
+
+    for timeseries in request.timeseries {
+        let mut metric = String::new();
+        let mut labels = HashMap::with_capacity(timeseries.labels.len());
+        for label in timeseries.labels {
+            if label.name == "__name__" {
+                metric = label.value;
+            } else {
+                labels.insert(label.name, label.value);
+            }
+        }
+
+        if metric.is_empty() {
+            debug!("Time series without the __name__ label: {:?}", labels);
+            result.malformed_count += timeseries.samples.len() as u64;
+            continue;
+        }
+
+        for remote_sample in timeseries.samples {
+            let Ok(timestamp) = u64::try_from(remote_sample.timestamp) else {
+                debug!("Negative timestamp {} of {}", remote_sample.timestamp, metric);
+                result.malformed_count += 1;
+                continue;
+            };
+            let sample = Sample {
+                metric: metric.clone(),
+                value: remote_sample.value,
+                labels: labels.clone(),
+                timestamp,
+                meta_labels: HashMap::new(),
+            };
+            if opts.print.metrics {
+                debug!("metric => {:?}", sample);
+            }
+
+            result.handle_metric(metric.clone());
+            result.handle_ip(address);
+
+            if opts.store_metrics {
+                result.metrics_samples.push(sample);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn get_label(name: &str, value: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_handle_remote_write() {
+        let request = WriteRequest {
+            timeseries: vec![
+                TimeSeries {
+                    labels: vec![get_label("__name__", "up"), get_label("job", "apiserver")],
+                    samples: vec![
+                        RemoteSample {
+                            value: 0.0,
+                            timestamp: 1638873379000,
+                        },
+                        RemoteSample {
+                            value: 1.0,
+                            timestamp: 1638873379541,
+                        },
+                        RemoteSample {
+                            value: 2.0,
+                            timestamp: -1,
+                        },
+                    ],
+                },
+                TimeSeries {
+                    labels: vec![get_label("job", "no-name")],
+                    samples: vec![RemoteSample {
+                        value: 1.0,
+                        timestamp: 1638873379541,
+                    }],
+                },
+            ],
+        };
+        let opts = options::Options {
+            store_metrics: true,
+            ..Default::default()
+        };
+        let ip_address = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+        let result = handle_remote_write(request, ip_address, &opts);
+
+        assert_eq!(result.metrics_count, 2);
+        // the series without a name and the negative timestamp
+        assert_eq!(result.malformed_count, 2);
+        assert_eq!(result.metrics_list.get("up"), Some(&2));
+        assert_eq!(result.metrics_ip_list.get(&ip_address), Some(&2));
+
+        // every data point is kept, in the order of sending
+        assert_eq!(result.metrics_samples.len(), 2);
+        assert_eq!(result.metrics_samples[0].timestamp, 1638873379000);
+        let sample = &result.metrics_samples[1];
+        assert_eq!(sample.metric, "up");
+        assert_eq!(sample.value, 1.0);
+        assert_eq!(sample.timestamp, 1638873379541);
+        assert_eq!(
+            sample.labels,
+            HashMap::from([(String::from("job"), String::from("apiserver"))])
+        );
+    }
+}
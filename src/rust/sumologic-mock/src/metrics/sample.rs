@@ -1,6 +1,9 @@
 use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
+use crate::metadata::MetricsMetadata;
+use crate::time;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 
 // Would love to use predefined structs from prometheus_parse create but since those
@@ -13,6 +16,27 @@ pub struct Sample {
     pub value: f64,
     pub labels: HashMap<String, String>,
     pub timestamp: u64, // milliseconds epoch timestamp
+    // Labels which don't identify the time series, e.g. Carbon 2.0 meta tags
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub meta_labels: HashMap<String, String>,
+}
+
+impl Sample {
+    // Add the request metadata to the sample.
+    // Labels and meta labels from the data point take precedence over the ones from the headers,
+    // and dimensions take precedence over metadata with the same key.
+    pub fn add_metadata(&mut self, metrics_metadata: &MetricsMetadata) {
+        for (key, value) in metrics_metadata.dimensions.iter() {
+            if !self.labels.contains_key(key) {
+                self.labels.insert(key.clone(), value.clone());
+            }
+        }
+        for (key, value) in metrics_metadata.metadata.iter() {
+            if !self.labels.contains_key(key) && !self.meta_labels.contains_key(key) {
+                self.meta_labels.insert(key.clone(), value.clone());
+            }
+        }
+    }
 }
 
 impl PartialEq for Sample {
@@ -40,37 +64,132 @@ impl Hash for Sample {
     }
 }
 
-pub fn lines_to_samples(lines: Vec<String>) -> HashSet<Sample> {
-    let scrape = prometheus_parse::Scrape::parse(lines.into_iter().map(|s| Ok(s))).unwrap();
-    let samples = scrape.samples;
+// Parse lines in Prometheus text format into samples.
+// Histograms and summaries are flattened the same way Prometheus stores them: every bucket
+// becomes a `<name>_bucket` sample with the `le` label and every quantile a `<name>` sample
+// with the `quantile` label. `_sum` and `_count` series are separate lines in the input.
+pub fn lines_to_samples(lines: Vec<String>) -> Result<HashSet<Sample>> {
+    let scrape = prometheus_parse::Scrape::parse(lines.into_iter().map(Ok))
+        .map_err(|e| anyhow!("Unable to parse Prometheus metrics: {}", e))?;
 
-    samples
-        .iter()
-        .map(|sample| {
-            let n = sample.labels.len();
-            let mut labels: HashMap<String, String> = HashMap::with_capacity(n);
-            for s in sample.labels.iter() {
-                labels.insert(s.0.to_owned(), s.1.to_owned());
-            }
+    let mut samples = HashSet::new();
+    for sample in scrape.samples.iter() {
+        let labels: HashMap<String, String> = sample
+            .labels
+            .iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+        let timestamp = sample.timestamp.timestamp_millis() as u64;
 
-            let value = match sample.value {
-                prometheus_parse::Value::Counter(v)
-                | prometheus_parse::Value::Gauge(v)
-                | prometheus_parse::Value::Untyped(v) => v,
-                // Don't support summaries and histograms
-                _ => 0.0,
-            };
-
-            Sample {
-                metric: sample.metric.clone(),
-                value: value,
-                labels: labels,
-                timestamp: sample.timestamp.timestamp_millis() as u64,
+        match &sample.value {
+            prometheus_parse::Value::Counter(v)
+            | prometheus_parse::Value::Gauge(v)
+            | prometheus_parse::Value::Untyped(v) => {
+                samples.replace(Sample {
+                    metric: sample.metric.clone(),
+                    value: *v,
+                    labels,
+                    timestamp,
+                    meta_labels: HashMap::new(),
+                });
+            }
+            prometheus_parse::Value::Histogram(buckets) => {
+                for bucket in buckets {
+                    let mut bucket_labels = labels.clone();
+                    bucket_labels.insert(String::from("le"), format_float_label(bucket.less_than));
+                    samples.replace(Sample {
+                        metric: format!("{}_bucket", sample.metric),
+                        value: bucket.count,
+                        labels: bucket_labels,
+                        timestamp,
+                        meta_labels: HashMap::new(),
+                    });
+                }
+            }
+            prometheus_parse::Value::Summary(quantiles) => {
+                for quantile in quantiles {
+                    let mut quantile_labels = labels.clone();
+                    quantile_labels.insert(String::from("quantile"), format_float_label(quantile.quantile));
+                    samples.replace(Sample {
+                        metric: sample.metric.clone(),
+                        value: quantile.count,
+                        labels: quantile_labels,
+                        timestamp,
+                        meta_labels: HashMap::new(),
+                    });
+                }
             }
+        }
+    }
+
+    Ok(samples)
+}
+
+// Parse a metric line in Carbon 2.0 format: `<intrinsic tags>  <meta tags>  <value> <timestamp>`.
+// Meta tags are optional. The `metric` intrinsic tag becomes the metric name, the rest of
+// the intrinsic tags identify the series and meta tags are kept separately as `meta_labels`.
+// Reference: https://help.sumologic.com/docs/metrics/introduction/metric-formats/#carbon-20
+pub fn carbon2_line_to_sample(line: &str) -> Result<Sample> {
+    let parts: Vec<&str> = line
+        .split("  ")
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect();
+    let (intrinsic_tags, meta_tags, value_and_timestamp) = match parts.as_slice() {
+        [intrinsic_tags, value_and_timestamp] => (*intrinsic_tags, "", *value_and_timestamp),
+        [intrinsic_tags, meta_tags, value_and_timestamp] => (*intrinsic_tags, *meta_tags, *value_and_timestamp),
+        _ => return Err(anyhow!("Invalid Carbon 2.0 metric line: {}", line)),
+    };
+
+    let mut labels = parse_carbon2_tags(intrinsic_tags)?;
+    let metric = labels
+        .remove("metric")
+        .ok_or_else(|| anyhow!("Missing metric intrinsic tag: {}", line))?;
+    let meta_labels = parse_carbon2_tags(meta_tags)?;
+
+    let mut split = value_and_timestamp.split_whitespace();
+    let value = match split.next().map(|v| v.parse::<f64>()) {
+        Some(Ok(value)) => value,
+        _ => return Err(anyhow!("Invalid Carbon 2.0 metric value: {}", line)),
+    };
+    // Carbon 2.0 timestamps are in seconds
+    let timestamp = match split.next().map(|ts| ts.parse::<u64>()) {
+        Some(Ok(timestamp)) => timestamp
+            .checked_mul(1000)
+            .ok_or_else(|| anyhow!("Invalid Carbon 2.0 metric timestamp: {}", line))?,
+        Some(Err(_)) => return Err(anyhow!("Invalid Carbon 2.0 metric timestamp: {}", line)),
+        None => time::get_now_ms(),
+    };
+
+    Ok(Sample {
+        metric,
+        value,
+        labels,
+        timestamp,
+        meta_labels,
+    })
+}
+
+fn parse_carbon2_tags(tags: &str) -> Result<HashMap<String, String>> {
+    tags.split_whitespace()
+        .map(|tag| match tag.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(anyhow!("Invalid Carbon 2.0 tag: {}", tag)),
         })
         .collect()
 }
 
+// Format a float as a label value the way Prometheus does it, e.g. `0.5`, `1` or `+Inf`
+pub fn format_float_label(value: f64) -> String {
+    if value == f64::INFINITY {
+        String::from("+Inf")
+    } else if value == f64::NEG_INFINITY {
+        String::from("-Inf")
+    } else {
+        value.to_string()
+    }
+}
+
 // filter_samples filters the provided samples based on the provided labels.
 // In order for the sample to be returned it has to contain all the provided labels
 // with provided values. If a label value is not provided then it's checked for
@@ -79,47 +198,277 @@ pub fn lines_to_samples(lines: Vec<String>) -> HashSet<Sample> {
 pub fn filter_samples(samples: &HashSet<Sample>, labels: HashMap<String, String>) -> HashSet<Sample> {
     samples
         .iter()
-        .filter(|sample| {
-            // For every provided param 'key-value' pair...
-            for (param_key, param_val) in &labels {
-                // In order to keep the params simply a key value list let's treat
-                // '__name__' specially so that it matches the metric name.
-                if param_key == "__name__" {
-                    if param_val != "" && &sample.metric != param_val {
-                        // If the metric name doesn't match the provided '__name__'
-                        // value then drop the sample.
-                        return false;
-                    }
-                    // Otherwise continue (get next key value pair from params)
+        .filter(|sample| sample_matches(sample, &labels))
+        .cloned()
+        .collect()
+}
+
+pub fn sample_matches(sample: &Sample, labels: &HashMap<String, String>) -> bool {
+    // For every provided param 'key-value' pair...
+    for (param_key, param_val) in labels {
+        // In order to keep the params simply a key value list let's treat
+        // '__name__' specially so that it matches the metric name.
+        if param_key == "__name__" {
+            if param_val != "" && &sample.metric != param_val {
+                // If the metric name doesn't match the provided '__name__'
+                // value then drop the sample.
+                return false;
+            }
+            // Otherwise continue (get next key value pair from params)
+            continue;
+        }
+
+        // ...try to find it in sample's labels or meta labels...
+        match sample
+            .labels
+            .get(&param_key[..])
+            .or_else(|| sample.meta_labels.get(&param_key[..]))
+        {
+            Some(sample_value) => {
+                // ...if sample contains it and query param was provided
+                // without a value then keep iterating...
+                if param_val == "" {
                     continue;
                 }
 
-                // ...try to find it in sample's labels...
-                match sample.labels.get(&param_key[..]) {
-                    Some(sample_value) => {
-                        // ...if sample contains it and query param was provided
-                        // without a value then keep iterating...
-                        if param_val == "" {
-                            continue;
-                        }
-
-                        // ...if the value was provided and it matches sample's
-                        // label value then also keep iterating...
-                        if sample_value == param_val {
-                            continue;
-                        }
-
-                        // ...otherwise drop this sample: the requested label has
-                        // a different value.
-                        return false;
-                    }
-
-                    // If the requested label wasn't found in sample's labels then bail.
-                    None => return false,
+                // ...if the value was provided and it matches sample's
+                // label value then also keep iterating...
+                if sample_value == param_val {
+                    continue;
                 }
+
+                // ...otherwise drop this sample: the requested label has
+                // a different value.
+                return false;
             }
-            true
-        })
-        .cloned()
-        .collect()
+
+            // If the requested label wasn't found in sample's labels then bail.
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_value(samples: &HashSet<Sample>, metric: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        let labels: HashMap<String, String> = labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        samples
+            .iter()
+            .find(|s| s.metric == metric && s.labels == labels)
+            .map(|s| s.value)
+    }
+
+    #[test]
+    fn test_carbon2_line_to_sample() {
+        let sample = carbon2_line_to_sample(
+            "metric=cpu_idle cluster=prod node=node-1  _sourceHost=host-1 team=ops  97.85 1528072150",
+        )
+        .unwrap();
+        assert_eq!(sample.metric, "cpu_idle");
+        assert_eq!(sample.value, 97.85);
+        assert_eq!(sample.timestamp, 1528072150000);
+        assert_eq!(
+            sample.labels,
+            HashMap::from([
+                (String::from("cluster"), String::from("prod")),
+                (String::from("node"), String::from("node-1")),
+            ])
+        );
+        assert_eq!(
+            sample.meta_labels,
+            HashMap::from([
+                (String::from("_sourceHost"), String::from("host-1")),
+                (String::from("team"), String::from("ops")),
+            ])
+        );
+
+        // meta tags are optional
+        let sample = carbon2_line_to_sample("metric=mem_free host=myhostname  12677414912 1601906858").unwrap();
+        assert_eq!(sample.metric, "mem_free");
+        assert_eq!(sample.value, 12677414912.0);
+        assert_eq!(
+            sample.labels,
+            HashMap::from([(String::from("host"), String::from("myhostname"))])
+        );
+        assert!(sample.meta_labels.is_empty());
+
+        assert!(carbon2_line_to_sample("metric=mem_free host=myhostname").is_err());
+        assert!(carbon2_line_to_sample("host=myhostname  1 1601906858").is_err());
+        assert!(carbon2_line_to_sample("metric=mem_free host  1 1601906858").is_err());
+        assert!(carbon2_line_to_sample("metric=mem_free  abc 1601906858").is_err());
+    }
+
+    #[test]
+    fn test_filter_samples_meta_labels() {
+        let samples: HashSet<Sample> = vec![
+            carbon2_line_to_sample("metric=cpu_idle node=node-1  team=ops  97.85 1528072150").unwrap(),
+            carbon2_line_to_sample("metric=cpu_idle node=node-2  team=dev  12.5 1528072150").unwrap(),
+        ]
+        .into_iter()
+        .collect();
+
+        let filtered = filter_samples(
+            &samples,
+            HashMap::from([(String::from("team"), String::from("ops"))]),
+        );
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered.iter().next().unwrap().labels["node"], "node-1");
+
+        let filtered = filter_samples(&samples, HashMap::from([(String::from("node"), String::new())]));
+        assert_eq!(filtered.len(), 2);
+    }
+
+    #[test]
+    fn test_lines_to_samples_histogram() {
+        let lines = r#"# TYPE http_request_duration_seconds histogram
+http_request_duration_seconds_bucket{code="200",le="0.05"} 24054 1395066363000
+http_request_duration_seconds_bucket{code="200",le="0.1"} 33444 1395066363000
+http_request_duration_seconds_bucket{code="200",le="+Inf"} 144320 1395066363000
+http_request_duration_seconds_sum{code="200"} 53423 1395066363000
+http_request_duration_seconds_count{code="200"} 144320 1395066363000"#
+            .lines()
+            .map(String::from)
+            .collect();
+
+        let samples = lines_to_samples(lines).unwrap();
+        assert_eq!(samples.len(), 5);
+        assert_eq!(
+            get_value(
+                &samples,
+                "http_request_duration_seconds_bucket",
+                &[("code", "200"), ("le", "0.05")]
+            ),
+            Some(24054.0)
+        );
+        assert_eq!(
+            get_value(
+                &samples,
+                "http_request_duration_seconds_bucket",
+                &[("code", "200"), ("le", "0.1")]
+            ),
+            Some(33444.0)
+        );
+        assert_eq!(
+            get_value(
+                &samples,
+                "http_request_duration_seconds_bucket",
+                &[("code", "200"), ("le", "+Inf")]
+            ),
+            Some(144320.0)
+        );
+        assert_eq!(
+            get_value(
+                &samples,
+                "http_request_duration_seconds_sum",
+                &[("code", "200")]
+            ),
+            Some(53423.0)
+        );
+        assert_eq!(
+            get_value(
+                &samples,
+                "http_request_duration_seconds_count",
+                &[("code", "200")]
+            ),
+            Some(144320.0)
+        );
+        assert!(samples.iter().all(|s| s.timestamp == 1395066363000));
+    }
+
+    #[test]
+    fn test_lines_to_samples_summary() {
+        let lines = r#"# TYPE rpc_duration_seconds summary
+rpc_duration_seconds{service="backup",quantile="0.5"} 4773 1395066363000
+rpc_duration_seconds{service="backup",quantile="0.99"} 76656 1395066363000
+rpc_duration_seconds_sum{service="backup"} 1.7560473e+07 1395066363000
+rpc_duration_seconds_count{service="backup"} 2693 1395066363000"#
+            .lines()
+            .map(String::from)
+            .collect();
+
+        let samples = lines_to_samples(lines).unwrap();
+        assert_eq!(samples.len(), 4);
+        assert_eq!(
+            get_value(
+                &samples,
+                "rpc_duration_seconds",
+                &[("service", "backup"), ("quantile", "0.5")]
+            ),
+            Some(4773.0)
+        );
+        assert_eq!(
+            get_value(
+                &samples,
+                "rpc_duration_seconds",
+                &[("service", "backup"), ("quantile", "0.99")]
+            ),
+            Some(76656.0)
+        );
+        assert_eq!(
+            get_value(&samples, "rpc_duration_seconds_sum", &[("service", "backup")]),
+            Some(1.7560473e+07)
+        );
+        assert_eq!(
+            get_value(&samples, "rpc_duration_seconds_count", &[("service", "backup")]),
+            Some(2693.0)
+        );
+    }
+
+    #[test]
+    fn test_lines_to_samples_untyped_buckets() {
+        // Without type information bucket lines are regular samples which already have the `le` label
+        let lines = vec![
+            String::from(r#"apiserver_request_duration_seconds_bucket{verb="GET",le="0.1"} 10 1395066363000"#),
+            String::from(r#"apiserver_request_duration_seconds_bucket{verb="GET",le="+Inf"} 12 1395066363000"#),
+        ];
+
+        let samples = lines_to_samples(lines).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(
+            get_value(
+                &samples,
+                "apiserver_request_duration_seconds_bucket",
+                &[("verb", "GET"), ("le", "+Inf")]
+            ),
+            Some(12.0)
+        );
+    }
+
+    #[test]
+    fn test_add_metadata() {
+        let mut sample =
+            carbon2_line_to_sample("metric=cpu_usage cluster=prod  _sourceCategory=line  1 1601909210").unwrap();
+        sample.add_metadata(&MetricsMetadata {
+            dimensions: HashMap::from([
+                (String::from("cluster"), String::from("dev")),
+                (String::from("node"), String::from("node-1")),
+            ]),
+            metadata: HashMap::from([
+                (String::from("_sourceCategory"), String::from("header")),
+                (String::from("_sourceHost"), String::from("host")),
+                (String::from("node"), String::from("node-2")),
+            ]),
+        });
+
+        assert_eq!(
+            sample.labels,
+            HashMap::from([
+                (String::from("cluster"), String::from("prod")),
+                (String::from("node"), String::from("node-1")),
+            ])
+        );
+        assert_eq!(
+            sample.meta_labels,
+            HashMap::from([
+                (String::from("_sourceCategory"), String::from("line")),
+                (String::from("_sourceHost"), String::from("host")),
+            ])
+        );
+    }
 }
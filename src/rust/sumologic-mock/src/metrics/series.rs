@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::sample::{sample_matches, Sample};
+use crate::options::RetentionLimits;
+use crate::time::get_now_ms;
+
+// Number of data points kept for every series, the oldest ones are dropped first
+pub const DEFAULT_MAX_POINTS_PER_SERIES: usize = 1000;
+
+fn get_byte_count(sample: &Sample, points_count: usize) -> u64 {
+    let labels_byte_count: usize = sample
+        .labels
+        .iter()
+        .chain(sample.meta_labels.iter())
+        .map(|(key, value)| key.len() + value.len())
+        .sum();
+    (sample.metric.len() + labels_byte_count + points_count * std::mem::size_of::<DataPoint>()) as u64
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DataPoint {
+    pub value: f64,
+    pub timestamp: u64, // milliseconds epoch timestamp
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Series {
+    pub metric: String,
+    pub labels: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub meta_labels: HashMap<String, String>,
+    pub points: Vec<DataPoint>,
+}
+
+// A series with all its data, as stored in the state snapshots
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SeriesSnapshot {
+    pub sample: Sample,
+    pub points: Vec<DataPoint>,
+    pub source_ips: Vec<IpAddr>,
+    #[serde(default)]
+    pub received_timestamp: u64,
+}
+
+#[derive(Default)]
+struct SeriesEntry {
+    points: VecDeque<DataPoint>,
+    // Addresses the series was received from
+    source_ips: HashSet<IpAddr>,
+    // milliseconds epoch timestamp of when the last data point was received
+    received_timestamp: u64,
+    // key in the usage order
+    usage: u64,
+    byte_count: u64,
+}
+
+// History of the received data points, per series.
+// Series are identified the same way as in the samples set, by the metric name and labels.
+// The key is the most recently received sample, so its meta labels are the latest ones.
+pub struct SeriesRepository {
+    max_points: usize,
+    series: HashMap<Sample, SeriesEntry>,
+    // Series by the time of their last update, the least recently updated ones are evicted first
+    usage: BTreeMap<u64, Sample>,
+    tick: u64,
+    byte_count: u64,
+    pub evicted_count: u64,
+}
+
+impl SeriesRepository {
+    pub fn new(max_points: usize) -> Self {
+        Self {
+            max_points,
+            series: HashMap::new(),
+            usage: BTreeMap::new(),
+            tick: 0,
+            byte_count: 0,
+            evicted_count: 0,
+        }
+    }
+
+    pub fn set_max_points(&mut self, max_points: usize) {
+        self.max_points = max_points;
+    }
+
+    pub fn add_sample(&mut self, sample: Sample, source_ip: IpAddr) {
+        let mut entry = match self.series.remove(&sample) {
+            Some(entry) => {
+                self.usage.remove(&entry.usage);
+                self.byte_count -= entry.byte_count;
+                entry
+            }
+            None => SeriesEntry::default(),
+        };
+        entry.points.push_back(DataPoint {
+            value: sample.value,
+            timestamp: sample.timestamp,
+        });
+        while entry.points.len() > self.max_points {
+            entry.points.pop_front();
+        }
+        entry.source_ips.insert(source_ip);
+        entry.received_timestamp = get_now_ms();
+        self.insert(sample, entry);
+    }
+
+    fn insert(&mut self, sample: Sample, mut entry: SeriesEntry) {
+        self.tick += 1;
+        entry.usage = self.tick;
+        entry.byte_count = get_byte_count(&sample, entry.points.len());
+        self.byte_count += entry.byte_count;
+        self.usage.insert(self.tick, sample.clone());
+        self.series.insert(sample, entry);
+    }
+
+    // Approximate size of the stored series, their labels and data points
+    pub fn byte_count(&self) -> u64 {
+        self.byte_count
+    }
+
+    // Evict the least recently updated series until the limits are met. Returns the evicted series.
+    pub fn evict(&mut self, limits: &RetentionLimits, now_ms: u64) -> Vec<Sample> {
+        let mut evicted = Vec::new();
+        while let Some((_, sample)) = self.usage.first_key_value() {
+            let expired = self
+                .series
+                .get(sample)
+                .is_some_and(|entry| limits.is_expired(entry.received_timestamp, now_ms));
+            if !expired && !limits.is_exceeded(self.series.len(), self.byte_count) {
+                break;
+            }
+
+            let (_, sample) = self.usage.pop_first().unwrap();
+            if let Some(entry) = self.series.remove(&sample) {
+                self.byte_count -= entry.byte_count;
+            }
+            evicted.push(sample);
+        }
+        self.evicted_count += evicted.len() as u64;
+        evicted
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Sample, &VecDeque<DataPoint>)> {
+        self.series
+            .iter()
+            .map(|(sample, entry)| (sample, &entry.points))
+    }
+
+    pub fn iter_source_ips(&self) -> impl Iterator<Item = (&Sample, &HashSet<IpAddr>)> {
+        self.series
+            .iter()
+            .map(|(sample, entry)| (sample, &entry.source_ips))
+    }
+
+    pub fn len(&self) -> usize {
+        self.series.len()
+    }
+
+    pub fn snapshot(&self) -> Vec<SeriesSnapshot> {
+        self.series
+            .iter()
+            .map(|(sample, entry)| SeriesSnapshot {
+                sample: sample.clone(),
+                points: entry.points.iter().cloned().collect(),
+                source_ips: entry.source_ips.iter().cloned().collect(),
+                received_timestamp: entry.received_timestamp,
+            })
+            .collect()
+    }
+
+    // Replace the stored series with the ones from a snapshot, keeping at most max_points of the latest points
+    pub fn restore(&mut self, mut snapshot: Vec<SeriesSnapshot>) {
+        self.series.clear();
+        self.usage.clear();
+        self.byte_count = 0;
+        snapshot.sort_by_key(|series| series.received_timestamp);
+        for series in snapshot {
+            let skip = series.points.len().saturating_sub(self.max_points);
+            let entry = SeriesEntry {
+                points: series.points.into_iter().skip(skip).collect(),
+                source_ips: series.source_ips.into_iter().collect(),
+                received_timestamp: series.received_timestamp,
+                ..Default::default()
+            };
+            self.insert(series.sample, entry);
+        }
+    }
+
+    // Remove the series matching the labels. Returns the number of removed series.
+    pub fn remove_series(&mut self, labels: &HashMap<String, String>) -> usize {
+        let samples: Vec<Sample> = self
+            .series
+            .keys()
+            .filter(|sample| sample_matches(sample, labels))
+            .cloned()
+            .collect();
+        for sample in samples.iter() {
+            if let Some(entry) = self.series.remove(sample) {
+                self.usage.remove(&entry.usage);
+                self.byte_count -= entry.byte_count;
+            }
+        }
+        samples.len()
+    }
+
+    // Get the series matching the labels with their data points in the [from_ts, to_ts) range.
+    // Points are sorted by timestamp, points with equal timestamps are kept in the order of receiving.
+    // Series without points in the range are skipped.
+    pub fn get_series(&self, labels: &HashMap<String, String>, from_ts: u64, to_ts: u64) -> Vec<Series> {
+        let mut result: Vec<Series> = self
+            .iter()
+            .filter(|(sample, _)| sample_matches(sample, labels))
+            .filter_map(|(sample, points)| {
+                let mut points: Vec<DataPoint> = points
+                    .iter()
+                    .filter(|point| from_ts <= point.timestamp && point.timestamp < to_ts)
+                    .cloned()
+                    .collect();
+                if points.is_empty() {
+                    return None;
+                }
+                points.sort_by_key(|point| point.timestamp);
+
+                Some(Series {
+                    metric: sample.metric.clone(),
+                    labels: sample.labels.clone(),
+                    meta_labels: sample.meta_labels.clone(),
+                    points,
+                })
+            })
+            .collect();
+
+        result.sort_by_cached_key(|series| {
+            let mut labels: Vec<_> = series.labels.iter().collect();
+            labels.sort();
+            format!("{}{:?}", series.metric, labels)
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_sample(metric: &str, instance: &str, value: f64, timestamp: u64) -> Sample {
+        Sample {
+            metric: metric.to_string(),
+            value,
+            labels: HashMap::from([(String::from("instance"), instance.to_string())]),
+            timestamp,
+            meta_labels: HashMap::new(),
+        }
+    }
+
+    fn get_ip() -> IpAddr {
+        IpAddr::from([127, 0, 0, 1])
+    }
+
+    fn get_timestamps(series: &Series) -> Vec<u64> {
+        series.points.iter().map(|point| point.timestamp).collect()
+    }
+
+    #[test]
+    fn test_series_history() {
+        let mut repository = SeriesRepository::new(3);
+        for (i, timestamp) in [10, 30, 20, 40].iter().enumerate() {
+            repository.add_sample(get_sample("up", "a", i as f64, *timestamp), get_ip());
+        }
+        repository.add_sample(get_sample("up", "b", 1.0, 10), get_ip());
+        repository.add_sample(get_sample("down", "a", 1.0, 10), get_ip());
+
+        let series = repository.get_series(&HashMap::new(), 0, u64::MAX);
+        assert_eq!(series.len(), 3);
+        assert_eq!(series[0].metric, "down");
+        assert_eq!(series[1].labels["instance"], "a");
+        // the oldest point was dropped and the rest is sorted by timestamp
+        assert_eq!(get_timestamps(&series[1]), vec![20, 30, 40]);
+        assert_eq!(series[1].points[0].value, 2.0);
+        assert_eq!(series[2].labels["instance"], "b");
+
+        let labels = HashMap::from([
+            (String::from("__name__"), String::from("up")),
+            (String::from("instance"), String::from("a")),
+        ]);
+        let series = repository.get_series(&labels, 20, 40);
+        assert_eq!(series.len(), 1);
+        assert_eq!(get_timestamps(&series[0]), vec![20, 30]);
+
+        // series without points in the range are skipped
+        assert!(repository.get_series(&HashMap::new(), 50, 60).is_empty());
+
+        assert_eq!(repository.remove_series(&labels), 1);
+        assert_eq!(repository.len(), 2);
+    }
+
+    #[test]
+    fn test_series_eviction() {
+        let mut repository = SeriesRepository::new(10);
+        repository.add_sample(get_sample("up", "a", 1.0, 10), get_ip());
+        repository.add_sample(get_sample("up", "b", 1.0, 10), get_ip());
+        repository.add_sample(get_sample("up", "c", 1.0, 10), get_ip());
+        // updating a series makes it the most recently used one
+        repository.add_sample(get_sample("up", "a", 2.0, 20), get_ip());
+
+        let limits = RetentionLimits {
+            max_entries: Some(2),
+            ..Default::default()
+        };
+        let evicted = repository.evict(&limits, get_now_ms());
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].labels["instance"], "b");
+        assert_eq!(repository.len(), 2);
+        assert_eq!(repository.evicted_count, 1);
+
+        let byte_count = repository.byte_count();
+        assert!(byte_count > 0);
+        let limits = RetentionLimits {
+            max_bytes: Some(byte_count - 1),
+            ..Default::default()
+        };
+        let evicted = repository.evict(&limits, get_now_ms());
+        assert_eq!(evicted[0].labels["instance"], "c");
+        assert_eq!(repository.len(), 1);
+        assert!(repository.byte_count() < byte_count);
+
+        let limits = RetentionLimits {
+            max_age: Some(std::time::Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert!(repository.evict(&limits, get_now_ms()).is_empty());
+        assert_eq!(repository.evict(&limits, get_now_ms() + 61_000).len(), 1);
+        assert_eq!(repository.len(), 0);
+        assert_eq!(repository.byte_count(), 0);
+        assert_eq!(repository.evicted_count, 3);
+    }
+}
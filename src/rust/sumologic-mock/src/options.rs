@@ -1,6 +1,10 @@
 use std::time;
 
-#[derive(Clone, Copy)]
+use crate::logs::timestamp::TimestampField;
+use crate::metrics::graphite::GraphiteTemplate;
+use crate::metrics::series::DEFAULT_MAX_POINTS_PER_SERIES;
+
+#[derive(Clone, Default)]
 pub struct Options {
     pub print: Print,
     pub drop_rate: i64,
@@ -8,12 +12,54 @@ pub struct Options {
     pub store_traces: bool,
     pub store_metrics: bool,
     pub store_logs: bool,
+    pub graphite_templates: Vec<GraphiteTemplate>,
+    pub log_timestamp_fields: Vec<TimestampField>,
+    pub retention: Retention,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub struct Print {
     pub logs: bool,
     pub headers: bool,
     pub metrics: bool,
     pub spans: bool,
 }
+
+// Limits of a store, data is evicted oldest first when any of them is exceeded
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetentionLimits {
+    pub max_entries: Option<usize>,
+    pub max_bytes: Option<u64>,
+    pub max_age: Option<time::Duration>,
+}
+
+impl RetentionLimits {
+    pub fn is_exceeded(&self, entries: usize, bytes: u64) -> bool {
+        self.max_entries.is_some_and(|max| entries > max) || self.max_bytes.is_some_and(|max| bytes > max)
+    }
+
+    // Whether data received at the timestamp (in milliseconds) is too old
+    pub fn is_expired(&self, received_ms: u64, now_ms: u64) -> bool {
+        self.max_age
+            .is_some_and(|max_age| now_ms.saturating_sub(received_ms) > max_age.as_millis() as u64)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Retention {
+    pub logs: RetentionLimits,
+    pub metrics: RetentionLimits,
+    pub traces: RetentionLimits,
+    pub max_points_per_series: usize,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            logs: RetentionLimits::default(),
+            metrics: RetentionLimits::default(),
+            traces: RetentionLimits::default(),
+            max_points_per_series: DEFAULT_MAX_POINTS_PER_SERIES,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use actix_rt::time::{sleep_until, Instant};
+use anyhow::{anyhow, Result};
+use log::{error, warn};
+
+use crate::capture::CapturedRequest;
+
+// Headers describing the original connection rather than the request, they're set by the client
+const SKIPPED_HEADERS: &[&str] = &["host", "content-length", "connection", "transfer-encoding"];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ReplayReport {
+    pub statuses: BTreeMap<u16, u64>,
+    // Requests which didn't get a response, e.g. because the target was unreachable
+    pub errors: u64,
+}
+
+impl std::fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total: u64 = self.statuses.values().sum::<u64>() + self.errors;
+        writeln!(f, "Replayed requests: {}", total)?;
+        for (status, count) in self.statuses.iter() {
+            writeln!(f, "{}: {}", status, count)?;
+        }
+        write!(f, "errors: {}", self.errors)
+    }
+}
+
+// Time from the start of the replay to sending a request received at the timestamp.
+// With speed 0 the requests are sent one after another, without waiting.
+fn get_send_offset(first_timestamp: u64, timestamp: u64, speed: f64) -> Result<Duration> {
+    if speed <= 0.0 {
+        return Ok(Duration::ZERO);
+    }
+    let offset = Duration::from_millis(timestamp.saturating_sub(first_timestamp)).as_secs_f64() / speed;
+    Duration::try_from_secs_f64(offset).map_err(|e| anyhow!("Invalid send offset for speed {}: {}", speed, e))
+}
+
+// Send the captured requests to the target, e.g. http://localhost:3000, keeping their relative timing
+// sped up by the speed multiplier
+pub async fn replay(requests: Vec<CapturedRequest>, target: &str, speed: f64) -> Result<ReplayReport> {
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(anyhow!(
+            "Speed must be a finite non-negative number, got {}",
+            speed
+        ));
+    }
+    let client = reqwest::Client::new();
+    let target = target.trim_end_matches('/');
+    let mut report = ReplayReport::default();
+
+    let start = Instant::now();
+    let first_timestamp = requests.first().map_or(0, |request| request.timestamp);
+    for request in requests {
+        let offset = get_send_offset(first_timestamp, request.timestamp, speed)?;
+        let deadline = start
+            .checked_add(offset)
+            .ok_or_else(|| anyhow!("Send offset {:?} is out of range", offset))?;
+        sleep_until(deadline).await;
+
+        let method = reqwest::Method::from_bytes(request.method.as_bytes())?;
+        let mut builder = client.request(method, format!("{}{}", target, request.path));
+        for (name, value) in request.headers.iter() {
+            if !SKIPPED_HEADERS.contains(&name.to_lowercase().as_str()) {
+                builder = builder.header(name, value);
+            }
+        }
+        match builder.body(request.body).send().await {
+            Ok(response) => {
+                let status = response.status();
+                if !status.is_success() {
+                    warn!("{} {} returned {}", request.method, request.path, status);
+                }
+                *report.statuses.entry(status.as_u16()).or_insert(0) += 1;
+            }
+            Err(e) => {
+                error!("{} {} failed: {}", request.method, request.path, e);
+                report.errors += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use bytes::Bytes;
+
+    fn get_request(timestamp: u64, path: &str, body: &'static str) -> CapturedRequest {
+        CapturedRequest {
+            timestamp,
+            method: String::from("POST"),
+            path: path.to_string(),
+            headers: vec![
+                (String::from("host"), String::from("example.com")),
+                (String::from("x-sumo-category"), String::from("test")),
+            ],
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_get_send_offset() {
+        assert_eq!(
+            get_send_offset(1000, 3000, 1.0).unwrap(),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            get_send_offset(1000, 3000, 4.0).unwrap(),
+            Duration::from_millis(500)
+        );
+        assert_eq!(get_send_offset(1000, 3000, 0.0).unwrap(), Duration::ZERO);
+        assert!(get_send_offset(0, u64::MAX, f64::MIN_POSITIVE).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_replay() {
+        // Accepts only the expected body with the category header, so the test can check what's sent
+        async fn handler(req: HttpRequest, body: web::Bytes) -> HttpResponse {
+            match (req.headers().get("x-sumo-category"), body.as_ref()) {
+                (Some(_), b"log line") => HttpResponse::Ok().finish(),
+                _ => HttpResponse::BadRequest().finish(),
+            }
+        }
+        let server = HttpServer::new(|| App::new().route("/receiver", web::post().to(handler)))
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_rt::spawn(server);
+
+        let requests = vec![
+            get_request(1000, "/receiver", "log line"),
+            get_request(1010, "/receiver", "other line"),
+            get_request(1020, "/unknown", "log line"),
+        ];
+        let report = replay(requests, &format!("http://{}/", address), 0.0)
+            .await
+            .unwrap();
+        handle.stop(false).await;
+        assert_eq!(
+            report,
+            ReplayReport {
+                statuses: BTreeMap::from([(200, 1), (400, 1), (404, 1)]),
+                errors: 0,
+            }
+        );
+
+        // nothing listens on the port anymore
+        let report = replay(
+            vec![get_request(0, "/receiver", "")],
+            &format!("http://{}", address),
+            1.0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.errors, 1);
+
+        for speed in [-1.0, f64::NAN, f64::INFINITY] {
+            let requests = vec![get_request(0, "/receiver", "")];
+            assert!(replay(requests, &format!("http://{}", address), speed)
+                .await
+                .is_err());
+        }
+    }
+}
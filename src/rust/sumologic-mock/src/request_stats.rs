@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// Upper bounds of the histogram buckets, the +Inf bucket is implicit
+pub const PAYLOAD_SIZE_BUCKETS: &[f64] = &[
+    256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0,
+];
+pub const ITEMS_BUCKETS: &[f64] = &[1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0];
+pub const LATENCY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Route label of requests handled by the default service, i.e. the receiver for all the other paths.
+// Using it instead of the path means unknown paths don't add new series.
+pub const DEFAULT_ROUTE: &str = "default";
+
+// Content type without parameters like charset, in lower case, so clients don't add new series with them
+pub fn normalize_content_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    buckets: &'static [f64],
+    // Not cumulative, counts[i] is the number of observations in (buckets[i-1], buckets[i]],
+    // the last one is for observations above the highest bound
+    counts: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    pub fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: vec![0; buckets.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let index = self
+            .buckets
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.buckets.len());
+        self.counts[index] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+
+    // Cumulative counts with their upper bounds, ending with the +Inf bucket
+    pub fn cumulative_buckets(&self) -> Vec<(String, u64)> {
+        let mut total = 0;
+        let mut result = Vec::with_capacity(self.counts.len());
+        for (i, count) in self.counts.iter().enumerate() {
+            total += count;
+            let bound = match self.buckets.get(i) {
+                Some(bound) => bound.to_string(),
+                None => String::from("+Inf"),
+            };
+            result.push((bound, total));
+        }
+        result
+    }
+}
+
+// Key of the request counter
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RequestKey {
+    pub route: String,
+    pub content_type: String,
+    pub status: u16,
+}
+
+// Stats of the requests received by the mock, exposed in /metrics.
+// Sorted maps keep the output stable.
+pub struct RequestStatsRepository {
+    pub requests: BTreeMap<RequestKey, u64>,
+    // per route
+    pub payload_sizes: BTreeMap<String, Histogram>,
+    // per route
+    pub latencies: BTreeMap<String, Histogram>,
+    // Received log lines, metric data points or spans per request, per signal
+    pub items: BTreeMap<String, Histogram>,
+    // Requests dropped because of the drop rate, per content type
+    pub dropped: BTreeMap<String, u64>,
+}
+
+impl RequestStatsRepository {
+    pub fn new() -> Self {
+        Self {
+            requests: BTreeMap::new(),
+            payload_sizes: BTreeMap::new(),
+            latencies: BTreeMap::new(),
+            items: BTreeMap::new(),
+            dropped: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_request(&mut self, mut key: RequestKey, latency: Duration) {
+        key.content_type = normalize_content_type(&key.content_type);
+        self.latencies
+            .entry(key.route.clone())
+            .or_insert_with(|| Histogram::new(LATENCY_BUCKETS))
+            .observe(latency.as_secs_f64());
+        *self.requests.entry(key).or_insert(0) += 1;
+    }
+
+    pub fn add_payload_size(&mut self, route: &str, size: u64) {
+        self.payload_sizes
+            .entry(route.to_string())
+            .or_insert_with(|| Histogram::new(PAYLOAD_SIZE_BUCKETS))
+            .observe(size as f64);
+    }
+
+    pub fn add_items(&mut self, signal: &str, count: u64) {
+        self.items
+            .entry(signal.to_string())
+            .or_insert_with(|| Histogram::new(ITEMS_BUCKETS))
+            .observe(count as f64);
+    }
+
+    pub fn add_dropped(&mut self, content_type: &str) {
+        *self
+            .dropped
+            .entry(normalize_content_type(content_type))
+            .or_insert(0) += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(&[1.0, 10.0]);
+        for value in [0.5, 1.0, 5.0, 20.0] {
+            histogram.observe(value);
+        }
+
+        assert_eq!(histogram.count, 4);
+        assert_eq!(histogram.sum, 26.5);
+        assert_eq!(
+            histogram.cumulative_buckets(),
+            vec![
+                (String::from("1"), 2),
+                (String::from("10"), 3),
+                (String::from("+Inf"), 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_stats() {
+        let mut stats = RequestStatsRepository::new();
+        let key = RequestKey {
+            route: String::from("/receiver/v1/logs"),
+            content_type: String::from("application/x-protobuf"),
+            status: 200,
+        };
+        stats.add_request(key.clone(), Duration::from_millis(3));
+        stats.add_request(key.clone(), Duration::from_millis(30));
+        stats.add_items("logs", 7);
+        stats.add_dropped("application/x-protobuf");
+        // counted without the parameters
+        stats.add_request(
+            RequestKey {
+                content_type: String::from("Application/X-Protobuf; charset=utf-8"),
+                ..key.clone()
+            },
+            Duration::from_millis(3),
+        );
+        stats.add_dropped("application/x-protobuf;charset=utf-8");
+
+        assert_eq!(stats.requests[&key], 3);
+        assert_eq!(stats.latencies["/receiver/v1/logs"].count, 3);
+        assert_eq!(stats.items["logs"].sum, 7.0);
+        assert_eq!(stats.dropped["application/x-protobuf"], 2);
+    }
+}
@@ -73,19 +73,7 @@ mod tests_api {
 
     #[actix_rt::test]
     async fn test_api_v1_collector_register() {
-        let opts = options::Options {
-            print: options::Print {
-                logs: false,
-                headers: false,
-                metrics: false,
-                spans: false,
-            },
-            delay_time: std::time::Duration::from_secs(0),
-            drop_rate: 0,
-            store_traces: false,
-            store_metrics: false,
-            store_logs: false,
-        };
+        let opts = options::Options::default();
 
         let mut app = test::init_service(App::new().app_data(web::Data::new(opts.clone())).service(
             web::scope("/api/v1").route(
@@ -149,19 +137,7 @@ mod tests_api {
     #[actix_rt::test]
     async fn test_api_v1_collector_heartbeat() {
         let app_data = web::Data::new(router::AppState::new());
-        let opts = options::Options {
-            print: options::Print {
-                logs: false,
-                headers: false,
-                metrics: false,
-                spans: false,
-            },
-            delay_time: std::time::Duration::from_secs(0),
-            drop_rate: 0,
-            store_traces: false,
-            store_metrics: false,
-            store_logs: false,
-        };
+        let opts = options::Options::default();
 
         let mut app = test::init_service(
             App::new()
@@ -190,19 +166,7 @@ mod tests_api {
     #[actix_rt::test]
     async fn test_api_v1_collector_metadata() {
         let app_data = web::Data::new(router::AppState::new());
-        let opts = options::Options {
-            print: options::Print {
-                logs: false,
-                headers: false,
-                metrics: false,
-                spans: false,
-            },
-            delay_time: std::time::Duration::from_secs(0),
-            drop_rate: 0,
-            store_traces: false,
-            store_metrics: false,
-            store_logs: false,
-        };
+        let opts = options::Options::default();
 
         let mut app = test::init_service(
             App::new()
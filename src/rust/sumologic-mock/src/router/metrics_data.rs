@@ -3,15 +3,41 @@ use std::collections::HashMap;
 use crate::metrics;
 use crate::options;
 use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
 
-use super::AppState;
+use super::{default_from_ts, default_to_ts, AppState};
+
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MetricsResetResponse {
+    pub samples: usize,
+    pub series: usize,
+}
+
+// Remove the stored samples and series matching the labels, as in `/metrics-samples`.
+// Without labels the counters are reset as well.
+pub(super) fn reset_metrics(app_state: &AppState, labels: &HashMap<String, String>) -> MetricsResetResponse {
+    let mut samples = app_state.metrics_samples.write().unwrap();
+    let mut series = app_state.metrics_series.write().unwrap();
+    let samples_count = samples.len();
+    samples.retain(|sample| !metrics::sample::sample_matches(sample, labels));
+    let response = MetricsResetResponse {
+        samples: samples_count - samples.len(),
+        series: series.remove_series(labels),
+    };
+
+    if labels.is_empty() {
+        series.evicted_count = 0;
+        *app_state.metrics.write().unwrap() = 0;
+        *app_state.metrics_malformed.write().unwrap() = 0;
+        app_state.metrics_list.write().unwrap().clear();
+        app_state.metrics_ip_list.write().unwrap().clear();
+    }
+    response
+}
 
 // Reset metrics
 pub async fn handler_metrics_reset(app_state: web::Data<AppState>) -> impl Responder {
-    *app_state.metrics.write().unwrap() = 0;
-    app_state.metrics_list.write().unwrap().clear();
-    app_state.metrics_ip_list.write().unwrap().clear();
-    app_state.metrics_samples.write().unwrap().clear();
+    reset_metrics(&app_state, &HashMap::new());
 
     HttpResponse::Ok().body("All metrics were reset successfully")
 }
@@ -86,3 +112,84 @@ pub async fn handler_metrics_samples(
 
     HttpResponse::Ok().json(response)
 }
+
+#[derive(Deserialize)]
+pub struct MetricsSeriesParams {
+    #[serde(default = "default_from_ts")]
+    from_ts: u64,
+    #[serde(default = "default_to_ts")]
+    to_ts: u64,
+}
+
+// Return the history of the data points of every series matching the query.
+// Labels are filtered the same way as in `/metrics-samples`, `from_ts` (inclusive) and `to_ts` (exclusive)
+// limit the data point timestamps, in milliseconds.
+//
+// Exemplar usage of this endpoint:
+//
+// $ curl -s localhost:3000/metrics-series\?__name__=up\&job=apiserver\&from_ts=1638873379000 | jq .
+// [
+//     {
+//       "metric": "up",
+//       "labels": {
+//         "job": "apiserver",
+//         "instance": "172.18.0.2:6443"
+//       },
+//       "points": [
+//         {
+//           "value": 1,
+//           "timestamp": 1638873379541
+//         },
+//         {
+//           "value": 1,
+//           "timestamp": 1638873409541
+//         }
+//       ]
+//     }
+//   ]
+//
+pub async fn handler_metrics_series(
+    app_state: web::Data<AppState>,
+    web::Query(params): web::Query<MetricsSeriesParams>,
+    web::Query(mut labels): web::Query<HashMap<String, String>>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_metrics {
+        return HttpResponse::NotImplemented().body("");
+    }
+
+    labels.remove("from_ts");
+    labels.remove("to_ts");
+    let series = app_state.metrics_series.read().unwrap();
+    let response = series.get_series(&labels, params.from_ts, params.to_ts);
+
+    HttpResponse::Ok().json(response)
+}
+
+// Number of label names in the cardinality report by default
+const DEFAULT_CARDINALITY_LABEL_NAMES_LIMIT: usize = 10;
+
+#[derive(Deserialize)]
+pub struct MetricsCardinalityParams {
+    limit: Option<usize>,
+}
+
+// Return the cardinality report of the stored series: series per metric name,
+// the top `limit` label names by the number of distinct values, series per source IP and `_sourceCategory`.
+pub async fn handler_metrics_cardinality(
+    app_state: web::Data<AppState>,
+    web::Query(params): web::Query<MetricsCardinalityParams>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_metrics {
+        return HttpResponse::NotImplemented().body("");
+    }
+
+    let series = app_state.metrics_series.read().unwrap();
+    let report = metrics::cardinality::get_cardinality_report(
+        &series,
+        params.limit.unwrap_or(DEFAULT_CARDINALITY_LABEL_NAMES_LIMIT),
+    );
+
+    HttpResponse::Ok().json(report)
+}
@@ -1,17 +1,23 @@
-use std::collections::{HashMap, HashSet};
-use std::iter::FromIterator;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::{IpAddr, Ipv4Addr};
 use std::sync::atomic::AtomicU64;
 use std::sync::RwLock;
+use std::time::Duration;
 
+use crate::compression::{decompress, ContentEncoding, EncodingStatsRepository, LimitExceeded, MAX_DECOMPRESSED_SIZE};
+use crate::faults;
 use crate::logs;
-use crate::metadata::{get_common_metadata_from_headers, parse_sumo_fields_header_value, Metadata};
+use crate::metadata::{
+    get_common_metadata_from_headers, get_metrics_metadata_from_headers, parse_sumo_fields_header_value, Metadata,
+};
 use crate::metrics;
 use crate::options;
-use crate::time::get_now;
+use crate::request_stats::{Histogram, RequestKey, RequestStatsRepository, DEFAULT_ROUTE};
+use crate::time::{get_now, get_now_ms};
 use crate::traces;
 use actix_http::header::HeaderValue;
-use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
+use actix_web::dev::ServiceResponse;
+use actix_web::{error, http::StatusCode, web, HttpRequest, HttpResponse, Responder};
 use anyhow::anyhow;
 use log::debug;
 use rand::Rng;
@@ -19,6 +25,7 @@ use serde::{Deserialize, Serialize};
 
 pub mod api;
 pub mod otlp;
+pub mod prometheus;
 pub mod terraform;
 
 pub mod metrics_data;
@@ -33,13 +40,20 @@ pub struct AppState {
     pub log_messages: RwLock<logs::LogRepository>,
 
     pub metrics: RwLock<u64>,
+    pub metrics_malformed: RwLock<u64>,
     pub metrics_samples: RwLock<HashSet<metrics::sample::Sample>>,
+    pub metrics_series: RwLock<metrics::series::SeriesRepository>,
     pub metrics_list: RwLock<HashMap<String, u64>>,
     pub metrics_ip_list: RwLock<HashMap<IpAddr, u64>>,
 
     pub spans: AtomicU64,
     pub spans_list: RwLock<HashMap<traces::SpanId, traces::Span>>,
     pub traces_list: RwLock<HashMap<traces::TraceId, traces::Trace>>,
+    pub trace_index: RwLock<traces::TraceIndex>,
+
+    pub encoding_stats: RwLock<EncodingStatsRepository>,
+    pub request_stats: RwLock<RequestStatsRepository>,
+    pub faults: RwLock<faults::FaultProfile>,
 }
 
 impl AppState {
@@ -49,13 +63,22 @@ impl AppState {
             log_messages: RwLock::new(logs::LogRepository::new()),
 
             metrics: RwLock::new(0),
+            metrics_malformed: RwLock::new(0),
             metrics_list: RwLock::new(HashMap::new()),
             metrics_ip_list: RwLock::new(HashMap::new()),
             metrics_samples: RwLock::new(HashSet::new()),
+            metrics_series: RwLock::new(metrics::series::SeriesRepository::new(
+                metrics::series::DEFAULT_MAX_POINTS_PER_SERIES,
+            )),
 
             spans: AtomicU64::new(0),
             spans_list: RwLock::new(HashMap::new()),
             traces_list: RwLock::new(HashMap::new()),
+            trace_index: RwLock::new(traces::TraceIndex::new()),
+
+            encoding_stats: RwLock::new(EncodingStatsRepository::new()),
+            request_stats: RwLock::new(RequestStatsRepository::new()),
+            faults: RwLock::new(faults::FaultProfile::default()),
         };
     }
 }
@@ -64,28 +87,38 @@ impl AppState {
     pub fn add_traces_result(&self, result: traces::TracesHandleResult, opts: &options::Options) {
         self.spans
             .fetch_add(result.spans_count, std::sync::atomic::Ordering::Relaxed);
+        self.request_stats
+            .write()
+            .unwrap()
+            .add_items("spans", result.spans_count);
 
         if opts.store_traces {
             {
                 let mut spans = self.spans_list.write().unwrap();
                 let mut traces = self.traces_list.write().unwrap();
+                let mut trace_index = self.trace_index.write().unwrap();
                 for span in result.spans {
-                    traces
-                        .entry(span.trace_id.clone())
-                        .or_insert(traces::Trace::new())
-                        .span_ids
-                        .push(span.id.clone());
-                    spans.insert(span.id.clone(), span);
+                    trace_index.add_span(&mut traces, &mut spans, span);
                 }
             }
+            self.evict_traces(opts);
         }
     }
 
-    pub fn add_metrics_result(&self, result: metrics::MetricsHandleResult, opts: &options::Options) {
+    pub fn add_metrics_result(&self, result: metrics::MetricsHandleResult, ipaddr: IpAddr, opts: &options::Options) {
         {
             let mut metrics = self.metrics.write().unwrap();
             *metrics += result.metrics_count;
         }
+        self.request_stats
+            .write()
+            .unwrap()
+            .add_items("metrics", result.metrics_count);
+
+        {
+            let mut metrics_malformed = self.metrics_malformed.write().unwrap();
+            *metrics_malformed += result.malformed_count;
+        }
 
         {
             let mut metrics_list = self.metrics_list.write().unwrap();
@@ -104,10 +137,16 @@ impl AppState {
         if opts.store_metrics {
             // Replace old data points that represent the same data series
             // (the same metric name and labels) with new ones.
+            // The history keeps all of them.
             let mut samples = self.metrics_samples.write().unwrap();
+            let mut series = self.metrics_series.write().unwrap();
             for s in result.metrics_samples {
+                series.add_sample(s.clone(), ipaddr);
                 samples.replace(s);
             }
+            drop(series);
+            drop(samples);
+            self.evict_metrics(opts);
         }
     }
 
@@ -125,14 +164,76 @@ impl AppState {
             message_count += 1;
             byte_count += line.len() as u64;
             if opts.store_logs {
-                log_messages.add_log_message(line.to_string(), metadata.clone())
+                log_messages.add_log_message(
+                    line.to_string(),
+                    metadata.clone(),
+                    ipaddr,
+                    &opts.log_timestamp_fields,
+                )
+            }
+        }
+        if opts.store_logs {
+            log_messages.evict(&opts.retention.logs, get_now_ms());
+        }
+        drop(log_messages);
+        self.request_stats
+            .write()
+            .unwrap()
+            .add_items("logs", message_count);
+        let mut log_stats = self.log_stats.write().unwrap();
+        log_stats.update(message_count, byte_count, ipaddr);
+    }
+
+    // Add logs with their own metadata and possibly timestamps, e.g. OTLP log records
+    pub fn add_received_logs(&self, logs: Vec<logs::ReceivedLog>, ipaddr: IpAddr, opts: &options::Options) {
+        let message_count = logs.len() as u64;
+        let byte_count = logs.iter().map(|log| log.body.len() as u64).sum();
+        if opts.store_logs {
+            let mut log_messages = self.log_messages.write().unwrap();
+            for log in logs {
+                log_messages.add_received_log(log, ipaddr, &opts.log_timestamp_fields);
             }
+            log_messages.evict(&opts.retention.logs, get_now_ms());
         }
+        self.request_stats
+            .write()
+            .unwrap()
+            .add_items("logs", message_count);
         let mut log_stats = self.log_stats.write().unwrap();
         log_stats.update(message_count, byte_count, ipaddr);
     }
 }
 
+impl AppState {
+    fn evict_metrics(&self, opts: &options::Options) {
+        let mut samples = self.metrics_samples.write().unwrap();
+        let mut series = self.metrics_series.write().unwrap();
+        // The latest sample of an evicted series goes away with it
+        for sample in series.evict(&opts.retention.metrics, get_now_ms()) {
+            samples.remove(&sample);
+        }
+    }
+
+    fn evict_traces(&self, opts: &options::Options) {
+        let mut spans = self.spans_list.write().unwrap();
+        let mut traces = self.traces_list.write().unwrap();
+        self.trace_index
+            .write()
+            .unwrap()
+            .evict(&mut traces, &mut spans, &opts.retention.traces, get_now_ms());
+    }
+
+    // Evict the data over the limits, so the maximum age is enforced also when nothing is being received
+    pub fn enforce_retention(&self, opts: &options::Options) {
+        self.log_messages
+            .write()
+            .unwrap()
+            .evict(&opts.retention.logs, get_now_ms());
+        self.evict_metrics(opts);
+        self.evict_traces(opts);
+    }
+}
+
 #[derive(Serialize)]
 struct ReceiverErrorErrorsField {
     code: String,
@@ -153,13 +254,32 @@ pub async fn handler_metrics(app_state: web::Data<AppState>) -> impl Responder {
     let mut body = format!(
         "# TYPE sumologic_mock_metrics_count counter
 sumologic_mock_metrics_count {}
+# TYPE sumologic_mock_metrics_malformed_count counter
+sumologic_mock_metrics_malformed_count {}
 # TYPE sumologic_mock_logs_count counter
 sumologic_mock_logs_count {}
 # TYPE sumologic_mock_logs_bytes_count counter
-sumologic_mock_logs_bytes_count {}\n",
+sumologic_mock_logs_bytes_count {}
+# TYPE sumologic_mock_requests_compressed_bytes_count counter
+sumologic_mock_requests_compressed_bytes_count {}
+# TYPE sumologic_mock_requests_uncompressed_bytes_count counter
+sumologic_mock_requests_uncompressed_bytes_count {}\n",
         app_state.metrics.read().unwrap(),
+        app_state.metrics_malformed.read().unwrap(),
         app_state.log_stats.read().unwrap().total.message_count,
         app_state.log_stats.read().unwrap().total.byte_count,
+        app_state
+            .encoding_stats
+            .read()
+            .unwrap()
+            .total
+            .compressed_byte_count,
+        app_state
+            .encoding_stats
+            .read()
+            .unwrap()
+            .total
+            .uncompressed_byte_count,
     );
 
     {
@@ -176,6 +296,90 @@ sumologic_mock_logs_bytes_count {}\n",
         }
     }
 
+    {
+        let series = app_state.metrics_series.read().unwrap();
+        let report = metrics::cardinality::get_cardinality_report(&series, 0);
+        body.push_str(&format!(
+            "# TYPE sumologic_mock_metrics_series_count gauge
+sumologic_mock_metrics_series_count {}
+# TYPE sumologic_mock_metrics_names_count gauge
+sumologic_mock_metrics_names_count {}
+# TYPE sumologic_mock_metrics_label_names_count gauge
+sumologic_mock_metrics_label_names_count {}\n",
+            report.series_count, report.metrics_count, report.label_names_count,
+        ));
+
+        if !report.series_per_source_ip.is_empty() {
+            body.push_str("# TYPE sumologic_mock_metrics_series_ip_count gauge\n");
+            for (ip, count) in report.series_per_source_ip.iter() {
+                body.push_str(&format!(
+                    "sumologic_mock_metrics_series_ip_count{{ip_address=\"{}\"}} {}\n",
+                    ip, count
+                ));
+            }
+        }
+        if !report.series_per_source_category.is_empty() {
+            body.push_str("# TYPE sumologic_mock_metrics_series_source_category_count gauge\n");
+            for (category, count) in report.series_per_source_category.iter() {
+                body.push_str(&format!(
+                    "sumologic_mock_metrics_series_source_category_count{{source_category=\"{}\"}} {}\n",
+                    escape_label_value(category),
+                    count
+                ));
+            }
+        }
+    }
+
+    {
+        // Sizes of the stored data and the number of items evicted because of the retention limits
+        let stores = {
+            let log_messages = app_state.log_messages.read().unwrap();
+            let series = app_state.metrics_series.read().unwrap();
+            let spans_count = app_state.spans_list.read().unwrap().len();
+            let trace_index = app_state.trace_index.read().unwrap();
+            [
+                (
+                    "logs",
+                    log_messages.len(),
+                    log_messages.byte_count(),
+                    log_messages.evicted_count,
+                ),
+                (
+                    "metrics",
+                    series.len(),
+                    series.byte_count(),
+                    series.evicted_count,
+                ),
+                (
+                    "traces",
+                    spans_count,
+                    trace_index.byte_count(),
+                    trace_index.evicted_count,
+                ),
+            ]
+        };
+        let mut entries_string = String::from("# TYPE sumologic_mock_store_entries gauge\n");
+        let mut bytes_string = String::from("# TYPE sumologic_mock_store_bytes gauge\n");
+        let mut evicted_string = String::from("# TYPE sumologic_mock_store_evicted_count counter\n");
+        for (signal, entries, bytes, evicted) in stores {
+            entries_string.push_str(&format!(
+                "sumologic_mock_store_entries{{signal=\"{}\"}} {}\n",
+                signal, entries
+            ));
+            bytes_string.push_str(&format!(
+                "sumologic_mock_store_bytes{{signal=\"{}\"}} {}\n",
+                signal, bytes
+            ));
+            evicted_string.push_str(&format!(
+                "sumologic_mock_store_evicted_count{{signal=\"{}\"}} {}\n",
+                signal, evicted
+            ));
+        }
+        body.push_str(&entries_string);
+        body.push_str(&bytes_string);
+        body.push_str(&evicted_string);
+    }
+
     {
         let log_ipaddr_stats = &app_state.log_stats.read().unwrap().ipaddr;
         if log_ipaddr_stats.len() > 0 {
@@ -197,9 +401,115 @@ sumologic_mock_logs_bytes_count {}\n",
         }
     }
 
+    {
+        let encoding_stats = &app_state.encoding_stats.read().unwrap().encodings;
+        if !encoding_stats.is_empty() {
+            let mut requests_string = String::from("# TYPE sumologic_mock_requests_encoding_count counter\n");
+            let mut compressed_bytes_string =
+                String::from("# TYPE sumologic_mock_requests_compressed_bytes_encoding_count counter\n");
+            let mut uncompressed_bytes_string =
+                String::from("# TYPE sumologic_mock_requests_uncompressed_bytes_encoding_count counter\n");
+
+            for (encoding, val) in encoding_stats.iter() {
+                requests_string.push_str(&format!(
+                    "sumologic_mock_requests_encoding_count{{encoding=\"{}\"}} {}\n",
+                    encoding, val.request_count
+                ));
+                compressed_bytes_string.push_str(&format!(
+                    "sumologic_mock_requests_compressed_bytes_encoding_count{{encoding=\"{}\"}} {}\n",
+                    encoding, val.compressed_byte_count
+                ));
+                uncompressed_bytes_string.push_str(&format!(
+                    "sumologic_mock_requests_uncompressed_bytes_encoding_count{{encoding=\"{}\"}} {}\n",
+                    encoding, val.uncompressed_byte_count
+                ));
+            }
+            body.push_str(&requests_string);
+            body.push_str(&compressed_bytes_string);
+            body.push_str(&uncompressed_bytes_string);
+        }
+    }
+
+    {
+        let request_stats = app_state.request_stats.read().unwrap();
+        if !request_stats.requests.is_empty() {
+            body.push_str("# TYPE sumologic_mock_http_requests_count counter\n");
+            for (key, count) in request_stats.requests.iter() {
+                body.push_str(&format!(
+                    "sumologic_mock_http_requests_count{{route=\"{}\",content_type=\"{}\",status=\"{}\"}} {}\n",
+                    escape_label_value(&key.route),
+                    escape_label_value(&key.content_type),
+                    key.status,
+                    count
+                ));
+            }
+        }
+        push_histograms(
+            &mut body,
+            "sumologic_mock_http_request_size_bytes",
+            "route",
+            &request_stats.payload_sizes,
+        );
+        push_histograms(
+            &mut body,
+            "sumologic_mock_http_request_duration_seconds",
+            "route",
+            &request_stats.latencies,
+        );
+        push_histograms(
+            &mut body,
+            "sumologic_mock_request_items",
+            "signal",
+            &request_stats.items,
+        );
+        if !request_stats.dropped.is_empty() {
+            body.push_str("# TYPE sumologic_mock_dropped_requests_count counter\n");
+            for (content_type, count) in request_stats.dropped.iter() {
+                body.push_str(&format!(
+                    "sumologic_mock_dropped_requests_count{{content_type=\"{}\"}} {}\n",
+                    escape_label_value(content_type),
+                    count
+                ));
+            }
+        }
+    }
+
     HttpResponse::Ok().body(body)
 }
 
+// Append histograms in the Prometheus text format, one per label value
+fn push_histograms(body: &mut String, name: &str, label_name: &str, histograms: &BTreeMap<String, Histogram>) {
+    if histograms.is_empty() {
+        return;
+    }
+    body.push_str(&format!("# TYPE {} histogram\n", name));
+    for (label_value, histogram) in histograms.iter() {
+        let label_value = escape_label_value(label_value);
+        for (le, count) in histogram.cumulative_buckets() {
+            body.push_str(&format!(
+                "{}_bucket{{{}=\"{}\",le=\"{}\"}} {}\n",
+                name, label_name, label_value, le, count
+            ));
+        }
+        body.push_str(&format!(
+            "{}_sum{{{}=\"{}\"}} {}\n",
+            name, label_name, label_value, histogram.sum
+        ));
+        body.push_str(&format!(
+            "{}_count{{{}=\"{}\"}} {}\n",
+            name, label_name, label_value, histogram.count
+        ));
+    }
+}
+
+// Escape a label value in the Prometheus text format
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
 pub async fn handler_receiver(
     req: HttpRequest,
     body: web::Bytes,
@@ -207,11 +517,6 @@ pub async fn handler_receiver(
     opts: web::Data<options::Options>,
 ) -> impl Responder {
     let remote_address = get_address(&req);
-    let string_body = match String::from_utf8(body.to_vec()) {
-        Ok(x) => x,
-        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
-    };
-    let lines = string_body.trim().lines();
 
     let content_type = match get_content_type(&req) {
         Ok(x) => x,
@@ -219,33 +524,38 @@ pub async fn handler_receiver(
     };
 
     // parse the value of the X-Sumo-* headers, excluding X-Sumo-Fields, which is handled separately
-    // TODO: use the metadata for metrics
     let metadata = match get_common_metadata_from_headers(req.headers()) {
         Ok(metadata) => metadata,
         Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
     };
 
-    if let Some(response) = try_dropping_data(&opts, &content_type) {
+    if let Some(response) = try_dropping_data(&opts, &app_state, &req, &content_type) {
         return response;
     }
 
-    match content_type.as_str() {
+    let body = match get_decompressed_body(&req, body, &app_state) {
+        Ok(body) => body,
+        Err(e) => return e.error_response(),
+    };
+    let string_body = match String::from_utf8(body.to_vec()) {
+        Ok(x) => x,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    let lines = string_body.trim().lines();
+
+    let metrics_result = match content_type.as_str() {
         // Metrics in carbon2 format
-        "application/vnd.sumologic.carbon2" => {
-            let result = metrics::handle_carbon2(lines, remote_address, opts.print);
-            app_state.add_metrics_result(result, opts.get_ref());
-        }
+        "application/vnd.sumologic.carbon2" => Some(metrics::handle_carbon2(lines, remote_address, opts.get_ref())),
 
         // Metrics in graphite format
-        "application/vnd.sumologic.graphite" => {
-            let result = metrics::handle_graphite(lines, remote_address, opts.print);
-            app_state.add_metrics_result(result, opts.get_ref());
-        }
+        "application/vnd.sumologic.graphite" => Some(metrics::handle_graphite(lines, remote_address, opts.get_ref())),
 
         // Metrics in prometheus format
         "application/vnd.sumologic.prometheus" => {
-            let result = metrics::handle_prometheus(lines, remote_address, opts.get_ref());
-            app_state.add_metrics_result(result, opts.get_ref());
+            match metrics::handle_prometheus(lines, remote_address, opts.get_ref()) {
+                Ok(result) => Some(result),
+                Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+            }
         }
 
         // Logs & events
@@ -269,20 +579,54 @@ pub async fn handler_receiver(
                     debug!("log => {}", line);
                 }
             }
+            None
         }
 
         &_ => {
             return get_invalid_header_response(&content_type);
         }
+    };
+
+    if let Some(mut result) = metrics_result {
+        match get_metrics_metadata_from_headers(req.headers()) {
+            Ok(metrics_metadata) => result.add_metadata(&metrics_metadata),
+            Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+        }
+        app_state.add_metrics_result(result, remote_address, opts.get_ref());
     }
 
     HttpResponse::Ok().body("")
 }
 
-fn try_dropping_data(opts: &web::Data<options::Options>, content_type: &str) -> Option<HttpResponse> {
-    let mut rng = rand::rng();
-    let number: i64 = rng.random_range(0..100);
-    if number < opts.drop_rate {
+// Respond with an error instead of handling the data, according to the fault profile or the drop rate
+fn try_dropping_data(
+    opts: &web::Data<options::Options>,
+    app_state: &AppState,
+    req: &HttpRequest,
+    content_type: &str,
+) -> Option<HttpResponse> {
+    let route = get_route(req);
+    if let Some(fault) = get_fault(app_state, &route, content_type) {
+        let msg = format!(
+            "Injecting fault {} for {} {}",
+            fault.status.as_u16(),
+            route,
+            content_type
+        );
+        debug!("{}", msg);
+        let mut response = HttpResponse::build(fault.status);
+        if let Some(retry_after) = fault.retry_after {
+            response.insert_header(("Retry-After", retry_after.to_string()));
+        }
+        return Some(response.body(msg));
+    }
+
+    if should_drop_data(opts) {
+        app_state
+            .request_stats
+            .write()
+            .unwrap()
+            .add_dropped(content_type);
         let msg = format!("Dropping data for {}", content_type);
         debug!("{}", msg);
         return Some(HttpResponse::InternalServerError().body(msg));
@@ -291,6 +635,73 @@ fn try_dropping_data(opts: &web::Data<options::Options>, content_type: &str) ->
     None
 }
 
+pub fn get_fault(app_state: &AppState, route: &str, content_type: &str) -> Option<faults::Fault> {
+    app_state
+        .faults
+        .write()
+        .unwrap()
+        .get_fault(route, content_type, get_now_ms(), &mut rand::rng())
+}
+
+// Randomly decide whether the received data should be dropped, according to the drop rate
+fn should_drop_data(opts: &options::Options) -> bool {
+    let mut rng = rand::rng();
+    let number: i64 = rng.random_range(0..100);
+    number < opts.drop_rate
+}
+
+// Decompress the request body according to the Content-Encoding header
+// and record the compressed and uncompressed sizes for the encoding used
+fn get_decompressed_body(
+    req: &HttpRequest,
+    body: web::Bytes,
+    app_state: &AppState,
+) -> Result<web::Bytes, actix_web::Error> {
+    let header_value = match req.headers().get("content-encoding") {
+        Some(header_value) => match header_value.to_str() {
+            Ok(x) => x,
+            Err(_) => {
+                return Err(error::ErrorBadRequest(
+                    "Unable to parse Content-Encoding header value",
+                ))
+            }
+        },
+        None => "",
+    };
+    let encoding = match ContentEncoding::from_header_value(header_value) {
+        Ok(x) => x,
+        Err(e) => return Err(error::ErrorUnsupportedMediaType(e.to_string())),
+    };
+
+    let decompressed = match encoding {
+        ContentEncoding::Identity => body.clone(),
+        _ => match decompress(encoding, &body, MAX_DECOMPRESSED_SIZE) {
+            Ok(x) => web::Bytes::from(x),
+            Err(e) => {
+                let msg = format!("Unable to decompress {} body: {}", encoding.as_str(), e);
+                debug!("{}", msg);
+                if e.is::<LimitExceeded>() {
+                    return Err(error::ErrorPayloadTooLarge(msg));
+                }
+                return Err(error::ErrorBadRequest(msg));
+            }
+        },
+    };
+
+    app_state
+        .encoding_stats
+        .write()
+        .unwrap()
+        .update(encoding, body.len() as u64, decompressed.len() as u64);
+    app_state
+        .request_stats
+        .write()
+        .unwrap()
+        .add_payload_size(&get_route(req), body.len() as u64);
+
+    Ok(decompressed)
+}
+
 fn get_address(req: &HttpRequest) -> IpAddr {
     // Don't fail when we can't read remote address.
     // Default to localhost and just ingest what was sent.
@@ -298,6 +709,44 @@ fn get_address(req: &HttpRequest) -> IpAddr {
     req.peer_addr().unwrap_or(localhost).ip()
 }
 
+// Route pattern of the request, e.g. /traces/{trace_id}, rather than its path
+fn get_route(req: &HttpRequest) -> String {
+    req.match_pattern()
+        .unwrap_or_else(|| String::from(DEFAULT_ROUTE))
+}
+
+// Route patterns of the endpoints receiving data, apart from the receiver for all the other paths
+const INGEST_ROUTE_PREFIXES: &[&str] = &[
+    "/receiver/v1/",
+    "/prometheus.metrics",
+    "/api/v1/collector/logs",
+    "/api/v1/collector/metrics",
+    "/api/v1/collector/traces",
+];
+
+// Whether the request was handled by one of the endpoints receiving data. Used by the middleware in run_app.
+pub fn is_ingest_request(req: &HttpRequest) -> bool {
+    match req.match_pattern() {
+        Some(pattern) => INGEST_ROUTE_PREFIXES
+            .iter()
+            .any(|prefix| pattern.starts_with(prefix)),
+        None => true,
+    }
+}
+
+// Record a handled HTTP request in the request stats. Used by the middleware in run_app.
+pub fn record_request(app_state: &AppState, res: &ServiceResponse, latency: Duration) {
+    let content_type = get_content_type(res.request()).unwrap_or_default();
+    app_state.request_stats.write().unwrap().add_request(
+        RequestKey {
+            route: get_route(res.request()),
+            content_type,
+            status: res.status().as_u16(),
+        },
+        latency,
+    );
+}
+
 fn get_content_type(req: &HttpRequest) -> anyhow::Result<String> {
     let empty_header = HeaderValue::from_str("").unwrap();
     match req
@@ -348,25 +797,20 @@ pub struct LogsCountResponse {
 pub async fn handler_logs_count(
     app_state: web::Data<AppState>,
     web::Query(params): web::Query<LogsParams>,
+    web::Query(body_query): web::Query<logs::BodyQuery>,
     web::Query(all_params): web::Query<HashMap<String, String>>,
     opts: web::Data<options::Options>,
 ) -> impl Responder {
     if !opts.store_logs {
         return HttpResponse::NotImplemented().body("Use the --store-logs flag to enable this endpoint");
     }
-    // all_params has all the parameters, so we need to remove the fixed ones
-    let fixed_params: HashSet<&str> = HashSet::from_iter(vec!["from_ts", "to_ts"].into_iter());
-    let metadata_params: HashMap<&str, &str> = all_params
-        .iter()
-        .filter(|(key, _)| !fixed_params.contains(key.as_str()))
-        .map(|(key, value)| (key.as_str(), value.as_str()))
-        .collect();
-    let count_res =
-        app_state
-            .log_messages
-            .read()
-            .unwrap()
-            .get_message_count(params.from_ts, params.to_ts, metadata_params);
+    let metadata_params = get_logs_metadata_params(&all_params);
+    let count_res = app_state.log_messages.read().unwrap().get_message_count(
+        params.from_ts,
+        params.to_ts,
+        metadata_params,
+        &body_query,
+    );
 
     match count_res {
         Ok(count) => HttpResponse::Ok().json(LogsCountResponse { count }),
@@ -374,6 +818,177 @@ pub async fn handler_logs_count(
     }
 }
 
+#[derive(Deserialize)]
+pub struct LogsListParams {
+    #[serde(default = "default_from_ts")]
+    from_ts: u64,
+    #[serde(default = "default_to_ts")]
+    to_ts: u64,
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LogsListResponse {
+    total: usize, // number of logs matching the query, before applying limit and offset
+    logs: Vec<logs::LogMessage>,
+}
+
+// Returns the logs received in a given timestamp range, ordered by timestamp
+pub async fn handler_logs_list(
+    app_state: web::Data<AppState>,
+    web::Query(params): web::Query<LogsListParams>,
+    web::Query(body_query): web::Query<logs::BodyQuery>,
+    web::Query(all_params): web::Query<HashMap<String, String>>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_logs {
+        return HttpResponse::NotImplemented().body("Use the --store-logs flag to enable this endpoint");
+    }
+    let metadata_params = get_logs_metadata_params(&all_params);
+    let log_messages = app_state.log_messages.read().unwrap();
+    let messages = match log_messages.get_messages(params.from_ts, params.to_ts, metadata_params, &body_query) {
+        Ok(messages) => messages,
+        Err(e) => return HttpResponse::BadRequest().json(e.to_string()),
+    };
+
+    let total = messages.len();
+    let logs = messages
+        .into_iter()
+        .skip(params.offset)
+        .take(params.limit.unwrap_or(usize::MAX))
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(LogsListResponse { total, logs })
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogsResetResponse {
+    pub logs: usize,
+}
+
+// Remove the logs matching the query, as in `/logs/list`. The log counters are reset if requested.
+fn reset_logs(
+    app_state: &AppState,
+    from_ts: u64,
+    to_ts: u64,
+    metadata_query: HashMap<&str, &str>,
+    body_query: &logs::BodyQuery,
+    reset_counters: bool,
+) -> anyhow::Result<LogsResetResponse> {
+    let mut log_messages = app_state.log_messages.write().unwrap();
+    let logs = log_messages.remove_messages(from_ts, to_ts, metadata_query, body_query)?;
+
+    if reset_counters {
+        log_messages.evicted_count = 0;
+        *app_state.log_stats.write().unwrap() = logs::LogStatsRepository::new();
+    }
+    Ok(LogsResetResponse { logs })
+}
+
+// Remove the logs matching the query, without a query the log counters are reset as well
+pub async fn handler_logs_reset(
+    app_state: web::Data<AppState>,
+    web::Query(params): web::Query<LogsParams>,
+    web::Query(body_query): web::Query<logs::BodyQuery>,
+    web::Query(all_params): web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let metadata_params = get_logs_metadata_params(&all_params);
+    match reset_logs(
+        &app_state,
+        params.from_ts,
+        params.to_ts,
+        metadata_params,
+        &body_query,
+        all_params.is_empty(),
+    ) {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => HttpResponse::BadRequest().json(e.to_string()),
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResetResponse {
+    pub logs: LogsResetResponse,
+    pub metrics: metrics_data::MetricsResetResponse,
+    pub traces: traces_data::TracesResetResponse,
+}
+
+// Remove the data of all signals matching the selector. Each key value pair is matched against log metadata,
+// metric labels and span attributes, the same way as in the respective list endpoints.
+// Without a selector all the data and counters are reset, as after a restart.
+pub async fn handler_reset(
+    app_state: web::Data<AppState>,
+    web::Query(selector): web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let metadata_query = selector
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    // Logs go first, as they're the only ones which can fail to match, e.g. because of an invalid regex
+    let logs = match reset_logs(
+        &app_state,
+        default_from_ts(),
+        default_to_ts(),
+        metadata_query,
+        &logs::BodyQuery::default(),
+        selector.is_empty(),
+    ) {
+        Ok(logs) => logs,
+        Err(e) => return HttpResponse::BadRequest().json(e.to_string()),
+    };
+    let metrics = metrics_data::reset_metrics(&app_state, &selector);
+    let traces = traces_data::reset_traces(&app_state, selector.clone(), &traces::SpanQuery::default());
+
+    if selector.is_empty() {
+        *app_state.encoding_stats.write().unwrap() = EncodingStatsRepository::new();
+        *app_state.request_stats.write().unwrap() = RequestStatsRepository::new();
+    }
+
+    HttpResponse::Ok().json(ResetResponse { logs, metrics, traces })
+}
+
+// Current fault profile with the number of matched requests and injected faults per rule
+pub async fn handler_faults(app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(app_state.faults.read().unwrap().status(get_now_ms()))
+}
+
+// Replace the fault profile, its time windows and sequences start over
+pub async fn handler_faults_update(
+    app_state: web::Data<AppState>,
+    config: web::Json<faults::FaultProfileConfig>,
+) -> impl Responder {
+    let now_ms = get_now_ms();
+    match faults::FaultProfile::new(config.into_inner(), now_ms) {
+        Ok(profile) => {
+            let mut faults = app_state.faults.write().unwrap();
+            *faults = profile;
+            HttpResponse::Ok().json(faults.status(now_ms))
+        }
+        Err(e) => HttpResponse::BadRequest().json(format!("{:#}", e)),
+    }
+}
+
+// all_params has all the query parameters, so we need to remove the fixed ones
+// to get the metadata filters
+fn get_logs_metadata_params(all_params: &HashMap<String, String>) -> HashMap<&str, &str> {
+    let fixed_params: HashSet<&str> = HashSet::from([
+        "from_ts",
+        "to_ts",
+        "limit",
+        "offset",
+        "body_contains",
+        "body_icontains",
+        "body_regex",
+    ]);
+    all_params
+        .iter()
+        .filter(|(key, _)| !fixed_params.contains(key.as_str()))
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect()
+}
+
 pub async fn handler_dump(body: web::Bytes) -> impl Responder {
     let string_body = String::from_utf8(body.to_vec()).unwrap_or("not an utf-8 string".to_string());
     debug!("dump: {}", string_body);
@@ -418,13 +1033,14 @@ pub fn start_print_stats_timer(
         // TODO: make this print metrics per minute (as DPM) and logs
         // per second, regardless of used interval
         // ref: https://github.com/SumoLogic/sumologic-kubernetes-tools/issues/57
+        // The counters can go down after a reset
         debug!(
             "{} Metrics: {:10.} Logs: {:10.}; {:6.6} MB/s Spans: {:10.};",
             now,
-            *metrics - p_metrics,
-            log_stats.total.message_count - p_logs,
-            ((log_stats.total.byte_count - p_logs_bytes) as f64) / ((now - ts) as f64) / (1e6 as f64),
-            spans - p_spans,
+            metrics.saturating_sub(p_metrics),
+            log_stats.total.message_count.saturating_sub(p_logs),
+            (log_stats.total.byte_count.saturating_sub(p_logs_bytes) as f64) / ((now - ts) as f64) / 1e6,
+            spans.saturating_sub(p_spans),
         );
 
         ts = now;
@@ -490,10 +1106,34 @@ mod tests_metrics {
                 web::Bytes::from_static(
                     b"# TYPE sumologic_mock_metrics_count counter\n\
                  sumologic_mock_metrics_count 3000\n\
+                 # TYPE sumologic_mock_metrics_malformed_count counter\n\
+                 sumologic_mock_metrics_malformed_count 0\n\
                  # TYPE sumologic_mock_logs_count counter\n\
                  sumologic_mock_logs_count 0\n\
                  # TYPE sumologic_mock_logs_bytes_count counter\n\
-                 sumologic_mock_logs_bytes_count 0\n",
+                 sumologic_mock_logs_bytes_count 0\n\
+                 # TYPE sumologic_mock_requests_compressed_bytes_count counter\n\
+                 sumologic_mock_requests_compressed_bytes_count 0\n\
+                 # TYPE sumologic_mock_requests_uncompressed_bytes_count counter\n\
+                 sumologic_mock_requests_uncompressed_bytes_count 0\n\
+                 # TYPE sumologic_mock_metrics_series_count gauge\n\
+                 sumologic_mock_metrics_series_count 0\n\
+                 # TYPE sumologic_mock_metrics_names_count gauge\n\
+                 sumologic_mock_metrics_names_count 0\n\
+                 # TYPE sumologic_mock_metrics_label_names_count gauge\n\
+                 sumologic_mock_metrics_label_names_count 0\n\
+                 # TYPE sumologic_mock_store_entries gauge\n\
+                 sumologic_mock_store_entries{signal=\"logs\"} 0\n\
+                 sumologic_mock_store_entries{signal=\"metrics\"} 0\n\
+                 sumologic_mock_store_entries{signal=\"traces\"} 0\n\
+                 # TYPE sumologic_mock_store_bytes gauge\n\
+                 sumologic_mock_store_bytes{signal=\"logs\"} 0\n\
+                 sumologic_mock_store_bytes{signal=\"metrics\"} 0\n\
+                 sumologic_mock_store_bytes{signal=\"traces\"} 0\n\
+                 # TYPE sumologic_mock_store_evicted_count counter\n\
+                 sumologic_mock_store_evicted_count{signal=\"logs\"} 0\n\
+                 sumologic_mock_store_evicted_count{signal=\"metrics\"} 0\n\
+                 sumologic_mock_store_evicted_count{signal=\"traces\"} 0\n",
                 ),
                 body,
             );
@@ -516,10 +1156,34 @@ mod tests_metrics {
                 web::Bytes::from_static(
                     b"# TYPE sumologic_mock_metrics_count counter\n\
                   sumologic_mock_metrics_count 0\n\
+                  # TYPE sumologic_mock_metrics_malformed_count counter\n\
+                  sumologic_mock_metrics_malformed_count 0\n\
                   # TYPE sumologic_mock_logs_count counter\n\
                   sumologic_mock_logs_count 0\n\
                   # TYPE sumologic_mock_logs_bytes_count counter\n\
-                  sumologic_mock_logs_bytes_count 0\n",
+                  sumologic_mock_logs_bytes_count 0\n\
+                  # TYPE sumologic_mock_requests_compressed_bytes_count counter\n\
+                  sumologic_mock_requests_compressed_bytes_count 0\n\
+                  # TYPE sumologic_mock_requests_uncompressed_bytes_count counter\n\
+                  sumologic_mock_requests_uncompressed_bytes_count 0\n\
+                  # TYPE sumologic_mock_metrics_series_count gauge\n\
+                  sumologic_mock_metrics_series_count 0\n\
+                  # TYPE sumologic_mock_metrics_names_count gauge\n\
+                  sumologic_mock_metrics_names_count 0\n\
+                  # TYPE sumologic_mock_metrics_label_names_count gauge\n\
+                  sumologic_mock_metrics_label_names_count 0\n\
+                  # TYPE sumologic_mock_store_entries gauge\n\
+                  sumologic_mock_store_entries{signal=\"logs\"} 0\n\
+                  sumologic_mock_store_entries{signal=\"metrics\"} 0\n\
+                  sumologic_mock_store_entries{signal=\"traces\"} 0\n\
+                  # TYPE sumologic_mock_store_bytes gauge\n\
+                  sumologic_mock_store_bytes{signal=\"logs\"} 0\n\
+                  sumologic_mock_store_bytes{signal=\"metrics\"} 0\n\
+                  sumologic_mock_store_bytes{signal=\"traces\"} 0\n\
+                  # TYPE sumologic_mock_store_evicted_count counter\n\
+                  sumologic_mock_store_evicted_count{signal=\"logs\"} 0\n\
+                  sumologic_mock_store_evicted_count{signal=\"metrics\"} 0\n\
+                  sumologic_mock_store_evicted_count{signal=\"traces\"} 0\n",
                 ),
                 body,
             );
@@ -565,17 +1229,9 @@ mod tests_metrics {
     async fn test_handler_metrics_storage() {
         let web_data_app_state = web::Data::new(AppState::new());
         let opts = options::Options {
-            print: options::Print {
-                logs: false,
-                headers: false,
-                metrics: false,
-                spans: false,
-            },
-            delay_time: std::time::Duration::from_secs(0),
-            drop_rate: 0,
-            store_traces: false,
             store_metrics: true,
             store_logs: true,
+            ..Default::default()
         };
 
         let mut app = test::init_service(
@@ -748,6 +1404,109 @@ mod tests_metrics {
             assert_eq!(result.len(), 2);
         }
     }
+
+    #[actix_rt::test]
+    async fn test_handler_metrics_header_metadata() {
+        let web_data_app_state = web::Data::new(AppState::new());
+        let opts = options::Options {
+            store_metrics: true,
+            ..Default::default()
+        };
+
+        let app = test::init_service(
+            actix_web::App::new()
+                .app_data(web::Data::new(opts))
+                .app_data(web_data_app_state.clone())
+                .route(
+                    "/metrics-samples",
+                    web::get().to(metrics_data::handler_metrics_samples),
+                )
+                .route(
+                    "/metrics-cardinality",
+                    web::get().to(metrics_data::handler_metrics_cardinality),
+                )
+                .route("/metrics", web::get().to(handler_metrics))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            let req = test::TestRequest::post()
+                .uri("/")
+                .set_payload(r#"node_load1{cluster="microk8s",node="node-1"} 0.25 1638873379541"#)
+                .insert_header(("Content-Type", "application/vnd.sumologic.prometheus"))
+                .insert_header(("X-Sumo-Category", "kubernetes/metrics"))
+                .insert_header(("X-Sumo-Dimensions", "cluster=kind, namespace=sumologic"))
+                .insert_header(("X-Sumo-Metadata", "team=ops"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+        }
+        {
+            let req = test::TestRequest::post()
+                .uri("/")
+                .set_payload("node_load1 0.25 1638873379541")
+                .insert_header(("Content-Type", "application/vnd.sumologic.prometheus"))
+                .insert_header(("X-Sumo-Dimensions", "no_equals"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400);
+        }
+        {
+            let req = test::TestRequest::get()
+                .uri("/metrics-samples?_sourceCategory=kubernetes/metrics&namespace=sumologic")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let result: Vec<Sample> = test::read_body_json(resp).await;
+            assert_eq!(result.len(), 1);
+            // labels from the data point take precedence over X-Sumo-Dimensions
+            assert_eq!(
+                result[0].labels,
+                HashMap::from([
+                    ("cluster".to_owned(), "microk8s".to_owned()),
+                    ("node".to_owned(), "node-1".to_owned()),
+                    ("namespace".to_owned(), "sumologic".to_owned()),
+                ])
+            );
+            assert_eq!(
+                result[0].meta_labels,
+                HashMap::from([
+                    ("_sourceCategory".to_owned(), "kubernetes/metrics".to_owned()),
+                    ("team".to_owned(), "ops".to_owned()),
+                ])
+            );
+        }
+        {
+            let req = test::TestRequest::get()
+                .uri("/metrics-cardinality?limit=1")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let result: metrics::cardinality::CardinalityReport = test::read_body_json(resp).await;
+            assert_eq!(result.series_count, 1);
+            assert_eq!(result.label_names_count, 3);
+            assert_eq!(result.top_label_names.len(), 1);
+            assert_eq!(result.series_per_source_ip.get("127.0.0.1"), Some(&1));
+            assert_eq!(
+                result.series_per_source_category.get("kubernetes/metrics"),
+                Some(&1)
+            );
+        }
+        {
+            let req = test::TestRequest::get().uri("/metrics").to_request();
+            let resp = test::call_service(&app, req).await;
+            let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+            assert!(body.contains("sumologic_mock_metrics_series_count 1\n"));
+            assert!(body.contains("sumologic_mock_metrics_series_ip_count{ip_address=\"127.0.0.1\"} 1\n"));
+            assert!(body.contains(
+                "sumologic_mock_metrics_series_source_category_count{source_category=\"kubernetes/metrics\"} 1\n"
+            ));
+        }
+        assert_eq!(*web_data_app_state.metrics.read().unwrap(), 1);
+    }
 }
 
 #[cfg(test)]
@@ -786,17 +1545,10 @@ mod tests_logs {
         let app_state = AppState::new();
         let app_data = web::Data::new(app_state);
         let opts = options::Options {
-            print: options::Print {
-                logs: false,
-                headers: false,
-                metrics: false,
-                spans: false,
-            },
-            delay_time: std::time::Duration::from_secs(0),
-            drop_rate: 0,
             store_traces: true,
             store_metrics: true,
             store_logs: true,
+            ..Default::default()
         };
 
         let mut app = test::init_service(
@@ -930,22 +1682,116 @@ mod tests_logs {
         }
     }
 
+    #[actix_rt::test]
+    async fn test_handler_logs_list() {
+        let app_data = web::Data::new(AppState::new());
+        let opts = options::Options {
+            store_logs: true,
+            ..Default::default()
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts))
+                .app_data(app_data.clone())
+                .route("/logs/list", web::get().to(handler_logs_list))
+                .route("/logs/count", web::get().to(handler_logs_count))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        for (ts, category) in [(5, "first"), (1, "second"), (8, "first")] {
+            let req = test::TestRequest::post()
+                .uri("/")
+                .set_payload(format!(
+                    "{{\"log\": \"Log message {}\", \"timestamp\": {}}}",
+                    ts, ts
+                ))
+                .insert_header(("Content-Type", "application/x-www-form-urlencoded"))
+                .insert_header(("X-Sumo-Category", category))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+        }
+
+        // all the logs, ordered by timestamp
+        {
+            let req = test::TestRequest::get().uri("/logs/list").to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let response_body: LogsListResponse = test::read_body_json(resp).await;
+            assert_eq!(response_body.total, 3);
+            let timestamps: Vec<u64> = response_body.logs.iter().map(|log| log.timestamp).collect();
+            assert_eq!(timestamps, vec![1, 5, 8]);
+            assert_eq!(
+                response_body.logs[0].body,
+                r#"{"log": "Log message 1", "timestamp": 1}"#
+            );
+            assert_eq!(
+                response_body.logs[0].metadata,
+                Metadata::from([("_sourceCategory".to_string(), "second".to_string())])
+            );
+            assert_eq!(
+                response_body.logs[0].source_ip,
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))
+            );
+        }
+
+        // filtering with metadata, limit and offset
+        {
+            let req = test::TestRequest::get()
+                .uri("/logs/list?_sourceCategory=first&from_ts=0&to_ts=10&limit=1&offset=1")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let response_body: LogsListResponse = test::read_body_json(resp).await;
+            assert_eq!(response_body.total, 2);
+            assert_eq!(response_body.logs.len(), 1);
+            assert_eq!(response_body.logs[0].timestamp, 8);
+        }
+
+        // filtering with body predicates
+        {
+            let req = test::TestRequest::get()
+                .uri("/logs/list?body_icontains=LOG%20MESSAGE%205")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let response_body: LogsListResponse = test::read_body_json(resp).await;
+            assert_eq!(response_body.total, 1);
+            assert_eq!(response_body.logs[0].timestamp, 5);
+        }
+        {
+            let req = test::TestRequest::get()
+                .uri("/logs/count?_sourceCategory=first&body_regex=message%20%5B0-5%5D")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let response_body: LogsCountResponse = test::read_body_json(resp).await;
+            assert_eq!(response_body.count, 1);
+        }
+        {
+            let req = test::TestRequest::get()
+                .uri("/logs/count?body_regex=%28")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400);
+        }
+    }
+
     #[actix_rt::test]
     async fn test_empty_content_type() {
         simple_logger::SimpleLogger::new().env().init().unwrap();
 
         let opts = options::Options {
-            print: options::Print {
-                logs: false,
-                headers: false,
-                metrics: false,
-                spans: false,
-            },
-            delay_time: std::time::Duration::from_secs(0),
-            drop_rate: 0,
             store_traces: true,
             store_metrics: true,
             store_logs: true,
+            ..Default::default()
         };
         let mut app = test::init_service(
             App::new()
@@ -985,4 +1831,364 @@ mod tests_logs {
             assert_eq!(response_body.count, 1);
         }
     }
+
+    #[actix_rt::test]
+    async fn test_compressed_logs() {
+        use std::io::Write;
+
+        let opts = options::Options {
+            store_traces: true,
+            store_metrics: true,
+            store_logs: true,
+            ..Default::default()
+        };
+        let app_data = web::Data::new(AppState::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .app_data(web::Data::new(opts))
+                .route("/logs/count", web::get().to(handler_logs_count))
+                .route("/metrics", web::get().to(handler_metrics))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        let raw_logs = "first log line\nsecond log line";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(raw_logs.as_bytes()).unwrap();
+        let gzip_logs = encoder.finish().unwrap();
+
+        {
+            let req = test::TestRequest::post()
+                .uri("/")
+                .set_payload(gzip_logs.clone())
+                .insert_header(("Content-Type", "application/x-www-form-urlencoded"))
+                .insert_header(("Content-Encoding", "gzip"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+        }
+
+        // unsupported encodings are rejected
+        {
+            let req = test::TestRequest::post()
+                .uri("/")
+                .set_payload(raw_logs)
+                .insert_header(("Content-Type", "application/x-www-form-urlencoded"))
+                .insert_header(("Content-Encoding", "br"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 415);
+        }
+
+        // so are bodies which can't be decompressed
+        {
+            let req = test::TestRequest::post()
+                .uri("/")
+                .set_payload(raw_logs)
+                .insert_header(("Content-Type", "application/x-www-form-urlencoded"))
+                .insert_header(("Content-Encoding", "zstd"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400);
+        }
+
+        {
+            let req = test::TestRequest::get().uri("/logs/count").to_request();
+            let resp = test::call_service(&app, req).await;
+
+            let response_body: LogsCountResponse = test::read_body_json(resp).await;
+
+            assert_eq!(response_body.count, 2);
+        }
+
+        {
+            let req = test::TestRequest::get().uri("/metrics").to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+            let expected_lines = [
+                format!(
+                    "sumologic_mock_requests_compressed_bytes_count {}",
+                    gzip_logs.len()
+                ),
+                format!(
+                    "sumologic_mock_requests_uncompressed_bytes_count {}",
+                    raw_logs.len()
+                ),
+                String::from("sumologic_mock_requests_encoding_count{encoding=\"gzip\"} 1"),
+                format!(
+                    "sumologic_mock_requests_compressed_bytes_encoding_count{{encoding=\"gzip\"}} {}",
+                    gzip_logs.len()
+                ),
+                format!(
+                    "sumologic_mock_requests_uncompressed_bytes_encoding_count{{encoding=\"gzip\"}} {}",
+                    raw_logs.len()
+                ),
+            ];
+            for line in expected_lines.iter() {
+                assert!(body.lines().any(|l| l == line), "missing {} in {}", line, body);
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_request_stats() {
+        let mut opts = options::Options {
+            store_traces: true,
+            store_metrics: true,
+            store_logs: true,
+            ..Default::default()
+        };
+        let app_data = web::Data::new(AppState::new());
+        let middleware_app_data = app_data.clone();
+        let app = test::init_service(
+            App::new()
+                .wrap_fn(move |req, srv| {
+                    let app_state = middleware_app_data.clone();
+                    let response = actix_web::dev::Service::call(&srv, req);
+                    async move {
+                        let response = response.await?;
+                        record_request(&app_state, &response, Duration::from_millis(2));
+                        Ok(response)
+                    }
+                })
+                .app_data(app_data.clone())
+                .app_data(web::Data::new(opts.clone()))
+                .route("/metrics", web::get().to(handler_metrics))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        let raw_logs = "first log line\nsecond log line";
+        for content_type in [
+            "application/x-www-form-urlencoded",
+            "application/x-www-form-urlencoded",
+            "text/plain",
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/receiver/v1/http/token")
+                .set_payload(raw_logs)
+                .insert_header(("Content-Type", content_type))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        // every request is dropped with the drop rate of 100%
+        opts.drop_rate = 100;
+        assert!(try_dropping_data(
+            &web::Data::new(opts),
+            &app_data,
+            &test::TestRequest::post().to_http_request(),
+            "application/vnd.sumologic.carbon2"
+        )
+        .is_some());
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        let expected_lines = [
+            "# TYPE sumologic_mock_http_requests_count counter",
+            "sumologic_mock_http_requests_count{route=\"default\",content_type=\"application/x-www-form-urlencoded\",status=\"200\"} 2",
+            "sumologic_mock_http_requests_count{route=\"default\",content_type=\"text/plain\",status=\"400\"} 1",
+            "# TYPE sumologic_mock_http_request_size_bytes histogram",
+            "sumologic_mock_http_request_size_bytes_bucket{route=\"default\",le=\"256\"} 3",
+            "sumologic_mock_http_request_size_bytes_sum{route=\"default\"} 90",
+            "sumologic_mock_http_request_size_bytes_count{route=\"default\"} 3",
+            "# TYPE sumologic_mock_http_request_duration_seconds histogram",
+            "sumologic_mock_http_request_duration_seconds_bucket{route=\"default\",le=\"0.001\"} 0",
+            "sumologic_mock_http_request_duration_seconds_bucket{route=\"default\",le=\"0.005\"} 3",
+            "sumologic_mock_http_request_duration_seconds_count{route=\"default\"} 3",
+            "# TYPE sumologic_mock_request_items histogram",
+            "sumologic_mock_request_items_bucket{signal=\"logs\",le=\"1\"} 0",
+            "sumologic_mock_request_items_bucket{signal=\"logs\",le=\"5\"} 2",
+            "sumologic_mock_request_items_bucket{signal=\"logs\",le=\"+Inf\"} 2",
+            "sumologic_mock_request_items_sum{signal=\"logs\"} 4",
+            "# TYPE sumologic_mock_dropped_requests_count counter",
+            "sumologic_mock_dropped_requests_count{content_type=\"application/vnd.sumologic.carbon2\"} 1",
+        ];
+        for line in expected_lines.iter() {
+            assert!(body.lines().any(|l| l == *line), "missing {} in {}", line, body);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_handler_reset() {
+        let opts = options::Options {
+            store_traces: true,
+            store_metrics: true,
+            store_logs: true,
+            ..Default::default()
+        };
+        let app_data = web::Data::new(AppState::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .app_data(web::Data::new(opts.clone()))
+                .route("/logs-reset", web::post().to(handler_logs_reset))
+                .route(
+                    "/traces-reset",
+                    web::post().to(traces_data::handler_traces_reset),
+                )
+                .route("/reset", web::post().to(handler_reset))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        for namespace in ["default", "sumologic"] {
+            for (content_type, body) in [
+                (
+                    "application/x-www-form-urlencoded",
+                    String::from("first log\nsecond log"),
+                ),
+                (
+                    "application/vnd.sumologic.prometheus",
+                    format!("up{{namespace=\"{}\"}} 1 1638873379541", namespace),
+                ),
+            ] {
+                let req = test::TestRequest::post()
+                    .uri("/")
+                    .set_payload(body)
+                    .insert_header(("Content-Type", content_type))
+                    .insert_header(("X-Sumo-Fields", format!("namespace={}", namespace)))
+                    .to_request();
+                let resp = test::call_service(&app, req).await;
+                assert_eq!(resp.status(), 200);
+            }
+
+            let mut result = traces::TracesHandleResult::new();
+            for (id, kind) in [("1", "SPAN_KIND_SERVER"), ("2", "SPAN_KIND_CLIENT")] {
+                result.handle_span(traces::Span {
+                    id: format!("{}-{}", namespace, id),
+                    trace_id: namespace.to_string(),
+                    kind: kind.to_string(),
+                    attributes: HashMap::from([(String::from("namespace"), namespace.to_string())]),
+                    ..Default::default()
+                });
+            }
+            app_data.add_traces_result(result, &opts);
+        }
+
+        {
+            let req = test::TestRequest::post()
+                .uri("/logs-reset?namespace=default")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+            let response: LogsResetResponse = test::read_body_json(resp).await;
+            assert_eq!(response, LogsResetResponse { logs: 2 });
+            // the counters are kept when only some logs are removed
+            assert_eq!(app_data.log_stats.read().unwrap().total.message_count, 4);
+        }
+        {
+            let req = test::TestRequest::post()
+                .uri("/logs-reset?namespace=(")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400);
+        }
+        {
+            let req = test::TestRequest::post()
+                .uri("/traces-reset?kind=client&namespace=default")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+            let response: traces_data::TracesResetResponse = test::read_body_json(resp).await;
+            assert_eq!(
+                response,
+                traces_data::TracesResetResponse { traces: 1, spans: 2 }
+            );
+        }
+        {
+            let req = test::TestRequest::post()
+                .uri("/reset?namespace=sumologic")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+            let response: ResetResponse = test::read_body_json(resp).await;
+            assert_eq!(
+                response,
+                ResetResponse {
+                    logs: LogsResetResponse { logs: 2 },
+                    metrics: metrics_data::MetricsResetResponse { samples: 1, series: 1 },
+                    traces: traces_data::TracesResetResponse { traces: 1, spans: 2 },
+                }
+            );
+        }
+        {
+            let req = test::TestRequest::post().uri("/reset").to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+            let response: ResetResponse = test::read_body_json(resp).await;
+            assert_eq!(
+                response,
+                ResetResponse {
+                    logs: LogsResetResponse { logs: 0 },
+                    metrics: metrics_data::MetricsResetResponse { samples: 1, series: 1 },
+                    traces: traces_data::TracesResetResponse { traces: 0, spans: 0 },
+                }
+            );
+            // a full reset zeroes the counters too
+            assert_eq!(app_data.log_stats.read().unwrap().total.message_count, 0);
+            assert_eq!(*app_data.metrics.read().unwrap(), 0);
+            assert_eq!(app_data.spans.load(std::sync::atomic::Ordering::Relaxed), 0);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_handler_faults() {
+        let opts = options::Options::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new()))
+                .app_data(web::Data::new(opts))
+                .route("/faults", web::get().to(handler_faults))
+                .route("/faults", web::post().to(handler_faults_update))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        // invalid rules are rejected
+        let req = test::TestRequest::post()
+            .uri("/faults")
+            .set_json(serde_json::json!({"rules": [{"status": 200}]}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+        let req = test::TestRequest::post()
+            .uri("/faults")
+            .set_json(serde_json::json!({
+                "rules": [
+                    {"route": "default", "content_type": "application/x-www-form-urlencoded", "status": 429, "retry_after": 30, "count": 1},
+                    {"route": "/receiver/v1/logs", "status": 503},
+                ]
+            }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        let mut statuses = vec![];
+        for _ in 0..2 {
+            let req = test::TestRequest::post()
+                .uri("/receiver/v1/http/token")
+                .insert_header(("Content-Type", "application/x-www-form-urlencoded"))
+                .set_payload("log line")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            let retry_after = resp
+                .headers()
+                .get("Retry-After")
+                .map(|value| value.to_str().unwrap().to_string());
+            statuses.push((resp.status().as_u16(), retry_after));
+        }
+        assert_eq!(statuses, vec![(429, Some(String::from("30"))), (200, None)]);
+
+        let req = test::TestRequest::get().uri("/faults").to_request();
+        let status: faults::FaultProfileStatus = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(status.rules.len(), 2);
+        assert_eq!((status.rules[0].matched, status.rules[0].injected), (2, 1));
+        assert_eq!((status.rules[1].matched, status.rules[1].injected), (0, 0));
+    }
 }
@@ -0,0 +1,442 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Instant;
+
+use crate::faults::Fault;
+use crate::options;
+use crate::request_stats::RequestKey;
+use crate::router::otlp::{handle_resource_logs, handle_resource_metrics, handle_resource_spans};
+use crate::router::{get_fault, should_drop_data, AppState};
+use actix_web::http::StatusCode;
+use actix_web::web;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use log::{debug, info};
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::{LogsService, LogsServiceServer};
+use opentelemetry_proto::tonic::collector::logs::v1::{ExportLogsServiceRequest, ExportLogsServiceResponse};
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::{
+    MetricsService, MetricsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::{ExportMetricsServiceRequest, ExportMetricsServiceResponse};
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::{TraceService, TraceServiceServer};
+use opentelemetry_proto::tonic::collector::trace::v1::{ExportTraceServiceRequest, ExportTraceServiceResponse};
+use prost::Message;
+use tokio::net::TcpListener;
+use tonic::codec::CompressionEncoding;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Code, Request, Response, Status};
+
+// gRPC requests are counted under this content type in the request stats
+const GRPC_CONTENT_TYPE: &str = "application/grpc";
+
+// Routes of the gRPC methods in fault rules
+const LOGS_ROUTE: &str = "/opentelemetry.proto.collector.logs.v1.LogsService/Export";
+const METRICS_ROUTE: &str = "/opentelemetry.proto.collector.metrics.v1.MetricsService/Export";
+const TRACES_ROUTE: &str = "/opentelemetry.proto.collector.trace.v1.TraceService/Export";
+
+// google.rpc messages sent as the details of a status, e.g. to tell the client when to retry.
+// Only the fields the mock sets are defined.
+// Reference: https://github.com/googleapis/googleapis/blob/master/google/rpc/error_details.proto
+#[derive(Clone, PartialEq, prost::Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<ProtoAny>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ProtoAny {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<ProtoDuration>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ProtoDuration {
+    #[prost(int64, tag = "1")]
+    seconds: i64,
+    #[prost(int32, tag = "2")]
+    nanos: i32,
+}
+
+const RETRY_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+// gRPC status corresponding to the HTTP status of an injected fault, following the OTLP retry semantics.
+// The retry delay is sent as RetryInfo, without it exporters don't retry RESOURCE_EXHAUSTED.
+fn get_fault_status(fault: &Fault, msg: String) -> Status {
+    let code = match fault.status.as_u16() {
+        400 => Code::InvalidArgument,
+        401 => Code::Unauthenticated,
+        403 => Code::PermissionDenied,
+        404 => Code::Unimplemented,
+        413 | 429 => Code::ResourceExhausted,
+        500 => Code::Internal,
+        502..=504 => Code::Unavailable,
+        _ => Code::Unknown,
+    };
+    let Some(retry_after) = fault.retry_after else {
+        return Status::new(code, msg);
+    };
+
+    let retry_info = RetryInfo {
+        retry_delay: Some(ProtoDuration {
+            seconds: i64::try_from(retry_after).unwrap_or(i64::MAX),
+            nanos: 0,
+        }),
+    };
+    let details = RpcStatus {
+        code: code as i32,
+        message: msg.clone(),
+        details: vec![ProtoAny {
+            type_url: String::from(RETRY_INFO_TYPE_URL),
+            value: retry_info.encode_to_vec(),
+        }],
+    };
+    Status::with_details(code, msg, Bytes::from(details.encode_to_vec()))
+}
+
+// Receiver implementing the OTLP/gRPC collector services.
+// Received data goes through the same paths as data received over OTLP/HTTP.
+#[derive(Clone)]
+pub struct OtlpGrpcReceiver {
+    app_state: web::Data<AppState>,
+    opts: options::Options,
+}
+
+impl OtlpGrpcReceiver {
+    pub fn new(app_state: web::Data<AppState>, opts: options::Options) -> Self {
+        Self { app_state, opts }
+    }
+
+    // Emulate the processing delay, faults and dropping of data the same way the HTTP server does, handle the data
+    // if the request doesn't fail and record it in the request stats.
+    async fn handle_export<T, F>(&self, signal: &str, route: &str, handle: F) -> Result<Response<T>, Status>
+    where
+        F: FnOnce() -> T + Send,
+    {
+        if !self.opts.delay_time.is_zero() {
+            actix_rt::time::sleep(self.opts.delay_time).await;
+        }
+
+        // Like for HTTP, the latency doesn't include the delay
+        let start = Instant::now();
+        let (status, result) = match self.emulate_failure(signal, route) {
+            None => (StatusCode::OK, Ok(Response::new(handle()))),
+            Some((status, e)) => (status, Err(e)),
+        };
+        self.app_state.request_stats.write().unwrap().add_request(
+            RequestKey {
+                route: route.to_string(),
+                content_type: GRPC_CONTENT_TYPE.to_string(),
+                status: status.as_u16(),
+            },
+            start.elapsed(),
+        );
+        result
+    }
+
+    // Get the error failing the request according to the fault profile or the drop rate, with the HTTP status for
+    // the request stats. Dropped data is reported as UNAVAILABLE, which OTLP exporters treat as retryable.
+    fn emulate_failure(&self, signal: &str, route: &str) -> Option<(StatusCode, Status)> {
+        if let Some(fault) = get_fault(&self.app_state, route, GRPC_CONTENT_TYPE) {
+            let msg = format!("Injecting fault {} for {}", fault.status.as_u16(), signal);
+            debug!("{}", msg);
+            return Some((fault.status, get_fault_status(&fault, msg)));
+        }
+
+        if should_drop_data(&self.opts) {
+            self.app_state
+                .request_stats
+                .write()
+                .unwrap()
+                .add_dropped(GRPC_CONTENT_TYPE);
+            let msg = format!("Dropping data for {}", signal);
+            debug!("{}", msg);
+            return Some((StatusCode::SERVICE_UNAVAILABLE, Status::unavailable(msg)));
+        }
+
+        None
+    }
+}
+
+fn get_grpc_address<T>(request: &Request<T>) -> IpAddr {
+    // Same as for HTTP, default to localhost when the remote address is unknown
+    request
+        .remote_addr()
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
+}
+
+#[tonic::async_trait]
+impl LogsService for OtlpGrpcReceiver {
+    async fn export(
+        &self,
+        request: Request<ExportLogsServiceRequest>,
+    ) -> Result<Response<ExportLogsServiceResponse>, Status> {
+        let remote_address = get_grpc_address(&request);
+        self.handle_export("otlp logs", LOGS_ROUTE, || {
+            handle_resource_logs(
+                request.into_inner().resource_logs,
+                remote_address,
+                &self.app_state,
+                &self.opts,
+            );
+            ExportLogsServiceResponse { partial_success: None }
+        })
+        .await
+    }
+}
+
+#[tonic::async_trait]
+impl MetricsService for OtlpGrpcReceiver {
+    async fn export(
+        &self,
+        request: Request<ExportMetricsServiceRequest>,
+    ) -> Result<Response<ExportMetricsServiceResponse>, Status> {
+        let remote_address = get_grpc_address(&request);
+        self.handle_export("otlp metrics", METRICS_ROUTE, || {
+            handle_resource_metrics(
+                request.into_inner().resource_metrics,
+                remote_address,
+                &self.app_state,
+                &self.opts,
+            );
+            ExportMetricsServiceResponse { partial_success: None }
+        })
+        .await
+    }
+}
+
+#[tonic::async_trait]
+impl TraceService for OtlpGrpcReceiver {
+    async fn export(
+        &self,
+        request: Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        self.handle_export("otlp traces", TRACES_ROUTE, || {
+            handle_resource_spans(request.into_inner().resource_spans, &self.app_state, &self.opts);
+            ExportTraceServiceResponse { partial_success: None }
+        })
+        .await
+    }
+}
+
+// Serve the OTLP/gRPC collector services on the given port.
+// Like the HTTP server, try to bind to [::] first and fall back to IPv4.
+pub async fn run_grpc_server(port: u16, app_state: web::Data<AppState>, opts: options::Options) -> Result<()> {
+    let receiver = OtlpGrpcReceiver::new(app_state, opts);
+
+    // The listener is handed over to tonic, so the port is bound only once
+    let listener = match TcpListener::bind((Ipv6Addr::UNSPECIFIED, port)).await {
+        Ok(listener) => listener,
+        Err(_) => TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await?,
+    };
+    info!(
+        "Sumo Logic Mock OTLP/gRPC receiver is listening on {}!",
+        listener.local_addr()?
+    );
+    let incoming = TcpIncoming::from_listener(listener, false, None).map_err(|e| anyhow!(e))?;
+
+    tonic::transport::Server::builder()
+        .add_service(LogsServiceServer::new(receiver.clone()).accept_compressed(CompressionEncoding::Gzip))
+        .add_service(MetricsServiceServer::new(receiver.clone()).accept_compressed(CompressionEncoding::Gzip))
+        .add_service(TraceServiceServer::new(receiver).accept_compressed(CompressionEncoding::Gzip))
+        .serve_with_incoming(incoming)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::faults::{FaultProfile, FaultProfileConfig, FaultRule};
+    use crate::time::get_now_ms;
+    use actix_web::http::StatusCode;
+    use opentelemetry_proto::tonic::common::v1::{any_value::Value, AnyValue};
+    use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+    use opentelemetry_proto::tonic::metrics::v1::{
+        metric, number_data_point, Gauge, Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics,
+    };
+    use opentelemetry_proto::tonic::resource::v1::Resource;
+    use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Span};
+
+    fn get_options(drop_rate: i64) -> options::Options {
+        options::Options {
+            drop_rate,
+            store_traces: true,
+            store_metrics: true,
+            store_logs: true,
+            ..Default::default()
+        }
+    }
+
+    fn get_logs_request() -> ExportLogsServiceRequest {
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource::default()),
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![LogRecord {
+                        body: Some(AnyValue {
+                            value: Some(Value::StringValue("grpc log line".to_string())),
+                        }),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[actix_rt::test]
+    async fn grpc_export_test() {
+        let app_state = web::Data::new(AppState::new());
+        let receiver = OtlpGrpcReceiver::new(app_state.clone(), get_options(0));
+
+        LogsService::export(&receiver, Request::new(get_logs_request()))
+            .await
+            .unwrap();
+        assert_eq!(app_state.log_stats.read().unwrap().total.message_count, 1);
+
+        let metrics_request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource::default()),
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![Metric {
+                        name: "grpc_gauge".to_string(),
+                        data: Some(metric::Data::Gauge(Gauge {
+                            data_points: vec![NumberDataPoint {
+                                value: Some(number_data_point::Value::AsDouble(1.5)),
+                                ..Default::default()
+                            }],
+                        })),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        MetricsService::export(&receiver, Request::new(metrics_request))
+            .await
+            .unwrap();
+        assert_eq!(*app_state.metrics.read().unwrap(), 1);
+        assert_eq!(app_state.metrics_samples.read().unwrap().len(), 1);
+
+        let trace_request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: Some(Resource::default()),
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        trace_id: vec![0xbb; 16],
+                        span_id: vec![0xaa; 8],
+                        name: "grpc span".to_string(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        TraceService::export(&receiver, Request::new(trace_request))
+            .await
+            .unwrap();
+        assert_eq!(app_state.spans.load(std::sync::atomic::Ordering::Relaxed), 1);
+        assert_eq!(app_state.spans_list.read().unwrap().len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn grpc_export_drop_test() {
+        let app_state = web::Data::new(AppState::new());
+        let receiver = OtlpGrpcReceiver::new(app_state.clone(), get_options(100));
+
+        let status = LogsService::export(&receiver, Request::new(get_logs_request()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert_eq!(status.message(), "Dropping data for otlp logs");
+        assert_eq!(app_state.log_stats.read().unwrap().total.message_count, 0);
+    }
+
+    #[actix_rt::test]
+    async fn grpc_export_fault_test() {
+        let app_state = web::Data::new(AppState::new());
+        let config = FaultProfileConfig {
+            rules: vec![FaultRule {
+                route: Some(String::from(LOGS_ROUTE)),
+                status: 429,
+                retry_after: Some(30),
+                count: Some(1),
+                ..Default::default()
+            }],
+        };
+        *app_state.faults.write().unwrap() = FaultProfile::new(config, get_now_ms()).unwrap();
+        let receiver = OtlpGrpcReceiver::new(app_state.clone(), get_options(0));
+
+        let status = LogsService::export(&receiver, Request::new(get_logs_request()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status.message(), "Injecting fault 429 for otlp logs");
+        let details = RpcStatus::decode(status.details()).unwrap();
+        assert_eq!(details.details.len(), 1);
+        assert_eq!(details.details[0].type_url, RETRY_INFO_TYPE_URL);
+        let retry_info = RetryInfo::decode(details.details[0].value.as_slice()).unwrap();
+        assert_eq!(retry_info.retry_delay.unwrap().seconds, 30);
+
+        // only the first request fails
+        LogsService::export(&receiver, Request::new(get_logs_request()))
+            .await
+            .unwrap();
+        assert_eq!(app_state.log_stats.read().unwrap().total.message_count, 1);
+
+        // both requests are in the request stats
+        let request_stats = app_state.request_stats.read().unwrap();
+        for status in [200, 429] {
+            let key = RequestKey {
+                route: String::from(LOGS_ROUTE),
+                content_type: String::from(GRPC_CONTENT_TYPE),
+                status,
+            };
+            assert_eq!(request_stats.requests.get(&key), Some(&1));
+        }
+        assert_eq!(request_stats.latencies[LOGS_ROUTE].count, 2);
+    }
+
+    #[test]
+    fn test_get_fault_status() {
+        let get_code = |status| {
+            get_fault_status(
+                &Fault {
+                    status,
+                    retry_after: None,
+                },
+                String::new(),
+            )
+            .code()
+        };
+        assert_eq!(get_code(StatusCode::BAD_REQUEST), Code::InvalidArgument);
+        assert_eq!(get_code(StatusCode::PAYLOAD_TOO_LARGE), Code::ResourceExhausted);
+        assert_eq!(get_code(StatusCode::INTERNAL_SERVER_ERROR), Code::Internal);
+        assert_eq!(get_code(StatusCode::SERVICE_UNAVAILABLE), Code::Unavailable);
+        assert_eq!(get_code(StatusCode::IM_A_TEAPOT), Code::Unknown);
+
+        let status = get_fault_status(
+            &Fault {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: None,
+            },
+            String::new(),
+        );
+        assert!(status.details().is_empty());
+    }
+}
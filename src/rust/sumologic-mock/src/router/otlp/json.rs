@@ -0,0 +1,695 @@
+// Decoding of OTLP/JSON payloads into the protobuf structures generated by opentelemetry-proto.
+//
+// The JSON encoding differs from the canonical proto3 JSON mapping in a couple of ways:
+// - trace and span ids are hex encoded instead of base64 encoded,
+// - 64 bit integers may be sent either as JSON numbers or as strings,
+// - field names are lowerCamelCase (we also accept the original snake_case names),
+// - enums are sent as integers.
+// ref: https://opentelemetry.io/docs/specs/otlp/#json-protobuf-encoding
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose as b64, Engine as _};
+use opentelemetry_proto::tonic::common::v1 as commonv1;
+use opentelemetry_proto::tonic::logs::v1 as logsv1;
+use opentelemetry_proto::tonic::metrics::v1 as metricsv1;
+use opentelemetry_proto::tonic::resource::v1 as resourcev1;
+use opentelemetry_proto::tonic::trace::v1 as tracev1;
+use serde_json::Value;
+
+pub fn decode_logs_data(body: &[u8]) -> Result<logsv1::LogsData> {
+    let root: Value = serde_json::from_slice(body)?;
+    Ok(logsv1::LogsData {
+        resource_logs: messages(&root, "resourceLogs", resource_logs)?,
+    })
+}
+
+pub fn decode_metrics_data(body: &[u8]) -> Result<metricsv1::MetricsData> {
+    let root: Value = serde_json::from_slice(body)?;
+    Ok(metricsv1::MetricsData {
+        resource_metrics: messages(&root, "resourceMetrics", resource_metrics)?,
+    })
+}
+
+pub fn decode_traces_data(body: &[u8]) -> Result<tracev1::TracesData> {
+    let root: Value = serde_json::from_slice(body)?;
+    Ok(tracev1::TracesData {
+        resource_spans: messages(&root, "resourceSpans", resource_spans)?,
+    })
+}
+
+// Field accessors
+
+// Get the value of a field, trying the lowerCamelCase name first and the snake_case one after it.
+// Explicit nulls are treated the same way as missing fields.
+fn field<'a>(obj: &'a Value, name: &str) -> Option<&'a Value> {
+    let value = match obj.get(name) {
+        Some(v) => Some(v),
+        None => obj.get(camel_to_snake(name)),
+    };
+    value.filter(|v| !v.is_null())
+}
+
+fn camel_to_snake(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('_');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn messages<T>(obj: &Value, name: &str, decode: fn(&Value) -> Result<T>) -> Result<Vec<T>> {
+    match field(obj, name) {
+        Some(Value::Array(values)) => values.iter().map(decode).collect(),
+        Some(_) => Err(anyhow!("Field {} is not an array", name)),
+        None => Ok(vec![]),
+    }
+}
+
+fn message<T>(obj: &Value, name: &str, decode: fn(&Value) -> Result<T>) -> Result<Option<T>> {
+    match field(obj, name) {
+        Some(value @ Value::Object(_)) => decode(value).map(Some),
+        Some(_) => Err(anyhow!("Field {} is not an object", name)),
+        None => Ok(None),
+    }
+}
+
+fn string(obj: &Value, name: &str) -> Result<String> {
+    match field(obj, name) {
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(_) => Err(anyhow!("Field {} is not a string", name)),
+        None => Ok(String::new()),
+    }
+}
+
+fn boolean(obj: &Value, name: &str) -> Result<bool> {
+    match field(obj, name) {
+        Some(Value::Bool(b)) => Ok(*b),
+        Some(_) => Err(anyhow!("Field {} is not a boolean", name)),
+        None => Ok(false),
+    }
+}
+
+// Integers can be sent either as JSON numbers or as strings containing a number.
+fn parse_integer<T: std::str::FromStr + TryFrom<i128>>(value: &Value, name: &str) -> Result<T> {
+    let parsed = match value {
+        Value::Number(n) => n
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| n.as_u64().map(i128::from))
+            .and_then(|n| T::try_from(n).ok()),
+        Value::String(s) => s.parse::<T>().ok(),
+        _ => None,
+    };
+    parsed.ok_or_else(|| anyhow!("Field {} is not a valid integer", name))
+}
+
+fn integer<T: std::str::FromStr + TryFrom<i128> + Default>(obj: &Value, name: &str) -> Result<T> {
+    match field(obj, name) {
+        Some(value) => parse_integer(value, name),
+        None => Ok(T::default()),
+    }
+}
+
+fn integers<T: std::str::FromStr + TryFrom<i128>>(obj: &Value, name: &str) -> Result<Vec<T>> {
+    match field(obj, name) {
+        Some(Value::Array(values)) => values.iter().map(|v| parse_integer(v, name)).collect(),
+        Some(_) => Err(anyhow!("Field {} is not an array", name)),
+        None => Ok(vec![]),
+    }
+}
+
+// Doubles can be sent as JSON numbers or as strings, the latter is needed for NaN and infinities.
+fn parse_double(value: &Value, name: &str) -> Result<f64> {
+    let parsed = match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            _ => s.parse::<f64>().ok(),
+        },
+        _ => None,
+    };
+    parsed.ok_or_else(|| anyhow!("Field {} is not a valid double", name))
+}
+
+fn optional_double(obj: &Value, name: &str) -> Result<Option<f64>> {
+    field(obj, name).map(|v| parse_double(v, name)).transpose()
+}
+
+fn double(obj: &Value, name: &str) -> Result<f64> {
+    Ok(optional_double(obj, name)?.unwrap_or_default())
+}
+
+fn doubles(obj: &Value, name: &str) -> Result<Vec<f64>> {
+    match field(obj, name) {
+        Some(Value::Array(values)) => values.iter().map(|v| parse_double(v, name)).collect(),
+        Some(_) => Err(anyhow!("Field {} is not an array", name)),
+        None => Ok(vec![]),
+    }
+}
+
+// Trace and span ids are hex encoded in OTLP/JSON
+fn hex_id(obj: &Value, name: &str) -> Result<Vec<u8>> {
+    let id = string(obj, name)?;
+    hex::decode(&id).map_err(|e| anyhow!("Field {} is not a valid hex string: {}", name, e))
+}
+
+// Common
+
+fn any_value(value: &Value) -> Result<commonv1::AnyValue> {
+    use commonv1::any_value::Value as V;
+
+    let inner = if let Some(s) = field(value, "stringValue") {
+        match s {
+            Value::String(s) => Some(V::StringValue(s.clone())),
+            _ => return Err(anyhow!("Field stringValue is not a string")),
+        }
+    } else if field(value, "boolValue").is_some() {
+        Some(V::BoolValue(boolean(value, "boolValue")?))
+    } else if field(value, "intValue").is_some() {
+        Some(V::IntValue(integer(value, "intValue")?))
+    } else if field(value, "doubleValue").is_some() {
+        Some(V::DoubleValue(double(value, "doubleValue")?))
+    } else if let Some(array) = field(value, "arrayValue") {
+        Some(V::ArrayValue(commonv1::ArrayValue {
+            values: messages(array, "values", any_value)?,
+        }))
+    } else if let Some(kvlist) = field(value, "kvlistValue") {
+        Some(V::KvlistValue(commonv1::KeyValueList {
+            values: messages(kvlist, "values", key_value)?,
+        }))
+    } else if field(value, "bytesValue").is_some() {
+        let bytes = b64::STANDARD
+            .decode(string(value, "bytesValue")?)
+            .map_err(|e| anyhow!("Field bytesValue is not valid base64: {}", e))?;
+        Some(V::BytesValue(bytes))
+    } else {
+        None
+    };
+
+    Ok(commonv1::AnyValue { value: inner })
+}
+
+fn key_value(value: &Value) -> Result<commonv1::KeyValue> {
+    Ok(commonv1::KeyValue {
+        key: string(value, "key")?,
+        value: message(value, "value", any_value)?,
+    })
+}
+
+fn attributes(obj: &Value, name: &str) -> Result<Vec<commonv1::KeyValue>> {
+    messages(obj, name, key_value)
+}
+
+fn resource(value: &Value) -> Result<resourcev1::Resource> {
+    Ok(resourcev1::Resource {
+        attributes: attributes(value, "attributes")?,
+        dropped_attributes_count: integer(value, "droppedAttributesCount")?,
+    })
+}
+
+fn scope(value: &Value) -> Result<commonv1::InstrumentationScope> {
+    Ok(commonv1::InstrumentationScope {
+        name: string(value, "name")?,
+        version: string(value, "version")?,
+        attributes: attributes(value, "attributes")?,
+        dropped_attributes_count: integer(value, "droppedAttributesCount")?,
+    })
+}
+
+// Logs
+
+fn resource_logs(value: &Value) -> Result<logsv1::ResourceLogs> {
+    Ok(logsv1::ResourceLogs {
+        resource: message(value, "resource", resource)?,
+        scope_logs: messages(value, "scopeLogs", scope_logs)?,
+        schema_url: string(value, "schemaUrl")?,
+    })
+}
+
+fn scope_logs(value: &Value) -> Result<logsv1::ScopeLogs> {
+    Ok(logsv1::ScopeLogs {
+        scope: message(value, "scope", scope)?,
+        log_records: messages(value, "logRecords", log_record)?,
+        schema_url: string(value, "schemaUrl")?,
+    })
+}
+
+fn log_record(value: &Value) -> Result<logsv1::LogRecord> {
+    Ok(logsv1::LogRecord {
+        time_unix_nano: integer(value, "timeUnixNano")?,
+        observed_time_unix_nano: integer(value, "observedTimeUnixNano")?,
+        severity_number: integer(value, "severityNumber")?,
+        severity_text: string(value, "severityText")?,
+        body: message(value, "body", any_value)?,
+        attributes: attributes(value, "attributes")?,
+        dropped_attributes_count: integer(value, "droppedAttributesCount")?,
+        flags: integer(value, "flags")?,
+        trace_id: hex_id(value, "traceId")?,
+        span_id: hex_id(value, "spanId")?,
+    })
+}
+
+// Metrics
+
+fn resource_metrics(value: &Value) -> Result<metricsv1::ResourceMetrics> {
+    Ok(metricsv1::ResourceMetrics {
+        resource: message(value, "resource", resource)?,
+        scope_metrics: messages(value, "scopeMetrics", scope_metrics)?,
+        schema_url: string(value, "schemaUrl")?,
+    })
+}
+
+fn scope_metrics(value: &Value) -> Result<metricsv1::ScopeMetrics> {
+    Ok(metricsv1::ScopeMetrics {
+        scope: message(value, "scope", scope)?,
+        metrics: messages(value, "metrics", metric)?,
+        schema_url: string(value, "schemaUrl")?,
+    })
+}
+
+fn metric(value: &Value) -> Result<metricsv1::Metric> {
+    use metricsv1::metric::Data;
+
+    let data = if let Some(gauge) = field(value, "gauge") {
+        Some(Data::Gauge(metricsv1::Gauge {
+            data_points: messages(gauge, "dataPoints", number_data_point)?,
+        }))
+    } else if let Some(sum) = field(value, "sum") {
+        Some(Data::Sum(metricsv1::Sum {
+            data_points: messages(sum, "dataPoints", number_data_point)?,
+            aggregation_temporality: integer(sum, "aggregationTemporality")?,
+            is_monotonic: boolean(sum, "isMonotonic")?,
+        }))
+    } else if let Some(histogram) = field(value, "histogram") {
+        Some(Data::Histogram(metricsv1::Histogram {
+            data_points: messages(histogram, "dataPoints", histogram_data_point)?,
+            aggregation_temporality: integer(histogram, "aggregationTemporality")?,
+        }))
+    } else if let Some(histogram) = field(value, "exponentialHistogram") {
+        Some(Data::ExponentialHistogram(metricsv1::ExponentialHistogram {
+            data_points: messages(histogram, "dataPoints", exponential_histogram_data_point)?,
+            aggregation_temporality: integer(histogram, "aggregationTemporality")?,
+        }))
+    } else if let Some(summary) = field(value, "summary") {
+        Some(Data::Summary(metricsv1::Summary {
+            data_points: messages(summary, "dataPoints", summary_data_point)?,
+        }))
+    } else {
+        None
+    };
+
+    Ok(metricsv1::Metric {
+        name: string(value, "name")?,
+        description: string(value, "description")?,
+        unit: string(value, "unit")?,
+        data,
+        metadata: attributes(value, "metadata")?,
+    })
+}
+
+fn number_data_point(value: &Value) -> Result<metricsv1::NumberDataPoint> {
+    use metricsv1::number_data_point::Value as V;
+
+    let dp_value = if field(value, "asDouble").is_some() {
+        Some(V::AsDouble(double(value, "asDouble")?))
+    } else if field(value, "asInt").is_some() {
+        Some(V::AsInt(integer(value, "asInt")?))
+    } else {
+        None
+    };
+
+    Ok(metricsv1::NumberDataPoint {
+        attributes: attributes(value, "attributes")?,
+        start_time_unix_nano: integer(value, "startTimeUnixNano")?,
+        time_unix_nano: integer(value, "timeUnixNano")?,
+        exemplars: messages(value, "exemplars", exemplar)?,
+        flags: integer(value, "flags")?,
+        value: dp_value,
+    })
+}
+
+fn histogram_data_point(value: &Value) -> Result<metricsv1::HistogramDataPoint> {
+    Ok(metricsv1::HistogramDataPoint {
+        attributes: attributes(value, "attributes")?,
+        start_time_unix_nano: integer(value, "startTimeUnixNano")?,
+        time_unix_nano: integer(value, "timeUnixNano")?,
+        count: integer(value, "count")?,
+        sum: optional_double(value, "sum")?,
+        bucket_counts: integers(value, "bucketCounts")?,
+        explicit_bounds: doubles(value, "explicitBounds")?,
+        exemplars: messages(value, "exemplars", exemplar)?,
+        flags: integer(value, "flags")?,
+        min: optional_double(value, "min")?,
+        max: optional_double(value, "max")?,
+    })
+}
+
+fn exponential_histogram_buckets(value: &Value) -> Result<metricsv1::exponential_histogram_data_point::Buckets> {
+    Ok(metricsv1::exponential_histogram_data_point::Buckets {
+        offset: integer(value, "offset")?,
+        bucket_counts: integers(value, "bucketCounts")?,
+    })
+}
+
+fn exponential_histogram_data_point(value: &Value) -> Result<metricsv1::ExponentialHistogramDataPoint> {
+    Ok(metricsv1::ExponentialHistogramDataPoint {
+        attributes: attributes(value, "attributes")?,
+        start_time_unix_nano: integer(value, "startTimeUnixNano")?,
+        time_unix_nano: integer(value, "timeUnixNano")?,
+        count: integer(value, "count")?,
+        sum: optional_double(value, "sum")?,
+        scale: integer(value, "scale")?,
+        zero_count: integer(value, "zeroCount")?,
+        positive: message(value, "positive", exponential_histogram_buckets)?,
+        negative: message(value, "negative", exponential_histogram_buckets)?,
+        flags: integer(value, "flags")?,
+        exemplars: messages(value, "exemplars", exemplar)?,
+        min: optional_double(value, "min")?,
+        max: optional_double(value, "max")?,
+        zero_threshold: double(value, "zeroThreshold")?,
+    })
+}
+
+fn value_at_quantile(value: &Value) -> Result<metricsv1::summary_data_point::ValueAtQuantile> {
+    Ok(metricsv1::summary_data_point::ValueAtQuantile {
+        quantile: double(value, "quantile")?,
+        value: double(value, "value")?,
+    })
+}
+
+fn summary_data_point(value: &Value) -> Result<metricsv1::SummaryDataPoint> {
+    Ok(metricsv1::SummaryDataPoint {
+        attributes: attributes(value, "attributes")?,
+        start_time_unix_nano: integer(value, "startTimeUnixNano")?,
+        time_unix_nano: integer(value, "timeUnixNano")?,
+        count: integer(value, "count")?,
+        sum: double(value, "sum")?,
+        quantile_values: messages(value, "quantileValues", value_at_quantile)?,
+        flags: integer(value, "flags")?,
+    })
+}
+
+fn exemplar(value: &Value) -> Result<metricsv1::Exemplar> {
+    use metricsv1::exemplar::Value as V;
+
+    let exemplar_value = if field(value, "asDouble").is_some() {
+        Some(V::AsDouble(double(value, "asDouble")?))
+    } else if field(value, "asInt").is_some() {
+        Some(V::AsInt(integer(value, "asInt")?))
+    } else {
+        None
+    };
+
+    Ok(metricsv1::Exemplar {
+        filtered_attributes: attributes(value, "filteredAttributes")?,
+        time_unix_nano: integer(value, "timeUnixNano")?,
+        span_id: hex_id(value, "spanId")?,
+        trace_id: hex_id(value, "traceId")?,
+        value: exemplar_value,
+    })
+}
+
+// Traces
+
+fn resource_spans(value: &Value) -> Result<tracev1::ResourceSpans> {
+    Ok(tracev1::ResourceSpans {
+        resource: message(value, "resource", resource)?,
+        scope_spans: messages(value, "scopeSpans", scope_spans)?,
+        schema_url: string(value, "schemaUrl")?,
+    })
+}
+
+fn scope_spans(value: &Value) -> Result<tracev1::ScopeSpans> {
+    Ok(tracev1::ScopeSpans {
+        scope: message(value, "scope", scope)?,
+        spans: messages(value, "spans", span)?,
+        schema_url: string(value, "schemaUrl")?,
+    })
+}
+
+fn span(value: &Value) -> Result<tracev1::Span> {
+    Ok(tracev1::Span {
+        trace_id: hex_id(value, "traceId")?,
+        span_id: hex_id(value, "spanId")?,
+        trace_state: string(value, "traceState")?,
+        parent_span_id: hex_id(value, "parentSpanId")?,
+        flags: integer(value, "flags")?,
+        name: string(value, "name")?,
+        kind: integer(value, "kind")?,
+        start_time_unix_nano: integer(value, "startTimeUnixNano")?,
+        end_time_unix_nano: integer(value, "endTimeUnixNano")?,
+        attributes: attributes(value, "attributes")?,
+        dropped_attributes_count: integer(value, "droppedAttributesCount")?,
+        events: messages(value, "events", span_event)?,
+        dropped_events_count: integer(value, "droppedEventsCount")?,
+        links: messages(value, "links", span_link)?,
+        dropped_links_count: integer(value, "droppedLinksCount")?,
+        status: message(value, "status", span_status)?,
+    })
+}
+
+fn span_event(value: &Value) -> Result<tracev1::span::Event> {
+    Ok(tracev1::span::Event {
+        time_unix_nano: integer(value, "timeUnixNano")?,
+        name: string(value, "name")?,
+        attributes: attributes(value, "attributes")?,
+        dropped_attributes_count: integer(value, "droppedAttributesCount")?,
+    })
+}
+
+fn span_link(value: &Value) -> Result<tracev1::span::Link> {
+    Ok(tracev1::span::Link {
+        trace_id: hex_id(value, "traceId")?,
+        span_id: hex_id(value, "spanId")?,
+        trace_state: string(value, "traceState")?,
+        attributes: attributes(value, "attributes")?,
+        dropped_attributes_count: integer(value, "droppedAttributesCount")?,
+        flags: integer(value, "flags")?,
+    })
+}
+
+fn span_status(value: &Value) -> Result<tracev1::Status> {
+    Ok(tracev1::Status {
+        message: string(value, "message")?,
+        code: integer(value, "code")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_logs_data() {
+        let body = r#"{
+            "resourceLogs": [{
+                "resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "svc"}}]},
+                "scopeLogs": [{
+                    "scope": {"name": "my.library", "version": "1.0.0"},
+                    "logRecords": [{
+                        "timeUnixNano": "1544712660300000000",
+                        "observedTimeUnixNano": 1544712660300000000,
+                        "severityNumber": 10,
+                        "severityText": "Information",
+                        "traceId": "5b8efff798038103d269b633813fc60c",
+                        "spanId": "eee19b7ec3c1b174",
+                        "body": {"stringValue": "Example log record"},
+                        "attributes": [
+                            {"key": "int.attribute", "value": {"intValue": "10"}},
+                            {"key": "double.attribute", "value": {"doubleValue": 637.704}},
+                            {"key": "bool.attribute", "value": {"boolValue": true}},
+                            {"key": "bytes.attribute", "value": {"bytesValue": "aGVsbG8="}},
+                            {"key": "array.attribute", "value": {"arrayValue": {"values": [{"stringValue": "many"}]}}},
+                            {"key": "map.attribute", "value": {"kvlistValue": {"values": [{"key": "some.map.key", "value": {"stringValue": "some value"}}]}}}
+                        ]
+                    }]
+                }]
+            }]
+        }"#;
+
+        let data = decode_logs_data(body.as_bytes()).unwrap();
+        assert_eq!(data.resource_logs.len(), 1);
+
+        let resource_logs = &data.resource_logs[0];
+        let resource = resource_logs.resource.as_ref().unwrap();
+        assert_eq!(resource.attributes[0].key, "service.name");
+
+        let scope_logs = &resource_logs.scope_logs[0];
+        assert_eq!(scope_logs.scope.as_ref().unwrap().name, "my.library");
+
+        let record = &scope_logs.log_records[0];
+        assert_eq!(record.time_unix_nano, 1544712660300000000);
+        assert_eq!(record.observed_time_unix_nano, 1544712660300000000);
+        assert_eq!(record.severity_number, 10);
+        assert_eq!(record.severity_text, "Information");
+        assert_eq!(
+            hex::encode(&record.trace_id),
+            "5b8efff798038103d269b633813fc60c"
+        );
+        assert_eq!(hex::encode(&record.span_id), "eee19b7ec3c1b174");
+        assert_eq!(
+            record.body.as_ref().unwrap().value,
+            Some(commonv1::any_value::Value::StringValue(
+                "Example log record".to_string()
+            ))
+        );
+
+        let values: Vec<_> = record
+            .attributes
+            .iter()
+            .map(|kv| kv.value.as_ref().unwrap().value.clone().unwrap())
+            .collect();
+        assert_eq!(values[0], commonv1::any_value::Value::IntValue(10));
+        assert_eq!(values[1], commonv1::any_value::Value::DoubleValue(637.704));
+        assert_eq!(values[2], commonv1::any_value::Value::BoolValue(true));
+        assert_eq!(
+            values[3],
+            commonv1::any_value::Value::BytesValue(b"hello".to_vec())
+        );
+        assert!(matches!(values[4], commonv1::any_value::Value::ArrayValue(_)));
+        assert!(matches!(values[5], commonv1::any_value::Value::KvlistValue(_)));
+    }
+
+    #[test]
+    fn test_decode_metrics_data() {
+        let body = r#"{
+            "resourceMetrics": [{
+                "resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "svc"}}]},
+                "scopeMetrics": [{
+                    "metrics": [
+                        {
+                            "name": "my.counter",
+                            "sum": {
+                                "aggregationTemporality": 1,
+                                "isMonotonic": true,
+                                "dataPoints": [{"asDouble": 5, "timeUnixNano": "1544712660300000000"}]
+                            }
+                        },
+                        {
+                            "name": "my.gauge",
+                            "gauge": {"dataPoints": [{"asInt": "10", "timeUnixNano": "1544712660300000000"}]}
+                        },
+                        {
+                            "name": "my.histogram",
+                            "histogram": {
+                                "aggregationTemporality": 1,
+                                "dataPoints": [{
+                                    "count": "2",
+                                    "sum": 2,
+                                    "bucketCounts": ["1", 1],
+                                    "explicitBounds": [1],
+                                    "min": 0,
+                                    "max": 2
+                                }]
+                            }
+                        }
+                    ]
+                }]
+            }]
+        }"#;
+
+        let data = decode_metrics_data(body.as_bytes()).unwrap();
+        let metrics = &data.resource_metrics[0].scope_metrics[0].metrics;
+        assert_eq!(metrics.len(), 3);
+
+        match metrics[0].data.as_ref().unwrap() {
+            metricsv1::metric::Data::Sum(sum) => {
+                assert!(sum.is_monotonic);
+                assert_eq!(sum.aggregation_temporality, 1);
+                assert_eq!(
+                    sum.data_points[0].value,
+                    Some(metricsv1::number_data_point::Value::AsDouble(5.0))
+                );
+                assert_eq!(sum.data_points[0].time_unix_nano, 1544712660300000000);
+            }
+            _ => panic!("Expected a sum"),
+        }
+
+        match metrics[1].data.as_ref().unwrap() {
+            metricsv1::metric::Data::Gauge(gauge) => assert_eq!(
+                gauge.data_points[0].value,
+                Some(metricsv1::number_data_point::Value::AsInt(10))
+            ),
+            _ => panic!("Expected a gauge"),
+        }
+
+        match metrics[2].data.as_ref().unwrap() {
+            metricsv1::metric::Data::Histogram(histogram) => {
+                let dp = &histogram.data_points[0];
+                assert_eq!(dp.count, 2);
+                assert_eq!(dp.sum, Some(2.0));
+                assert_eq!(dp.bucket_counts, vec![1, 1]);
+                assert_eq!(dp.explicit_bounds, vec![1.0]);
+                assert_eq!(dp.min, Some(0.0));
+                assert_eq!(dp.max, Some(2.0));
+            }
+            _ => panic!("Expected a histogram"),
+        }
+    }
+
+    #[test]
+    fn test_decode_traces_data() {
+        let body = r#"{
+            "resourceSpans": [{
+                "scopeSpans": [{
+                    "spans": [{
+                        "traceId": "5B8EFFF798038103D269B633813FC60C",
+                        "spanId": "eee19b7ec3c1b174",
+                        "parentSpanId": "eee19b7ec3c1b173",
+                        "name": "I'm a server span",
+                        "startTimeUnixNano": "1544712660000000000",
+                        "endTimeUnixNano": "1544712661000000000",
+                        "kind": 2,
+                        "status": {"code": 2, "message": "oops"},
+                        "events": [{"name": "exception", "timeUnixNano": "1544712660500000000"}],
+                        "links": [{"traceId": "5b8efff798038103d269b633813fc60c", "spanId": "eee19b7ec3c1b171"}]
+                    }]
+                }]
+            }]
+        }"#;
+
+        let data = decode_traces_data(body.as_bytes()).unwrap();
+        let span = &data.resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(hex::encode(&span.trace_id), "5b8efff798038103d269b633813fc60c");
+        assert_eq!(hex::encode(&span.span_id), "eee19b7ec3c1b174");
+        assert_eq!(hex::encode(&span.parent_span_id), "eee19b7ec3c1b173");
+        assert_eq!(span.kind, 2);
+        assert_eq!(span.start_time_unix_nano, 1544712660000000000);
+        assert_eq!(span.end_time_unix_nano, 1544712661000000000);
+        assert_eq!(span.status.as_ref().unwrap().code, 2);
+        assert_eq!(span.events[0].name, "exception");
+        assert_eq!(hex::encode(&span.links[0].span_id), "eee19b7ec3c1b171");
+    }
+
+    #[test]
+    fn test_decode_snake_case_fields() {
+        let body = r#"{"resource_logs": [{"scope_logs": [{"log_records": [{"time_unix_nano": 5}]}]}]}"#;
+
+        let data = decode_logs_data(body.as_bytes()).unwrap();
+        assert_eq!(
+            data.resource_logs[0].scope_logs[0].log_records[0].time_unix_nano,
+            5
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let invalid_bodies = [
+            "not json at all",
+            r#"{"resourceLogs": {}}"#,
+            r#"{"resourceLogs": [{"scopeLogs": [{"logRecords": [{"traceId": "not hex"}]}]}]}"#,
+            r#"{"resourceLogs": [{"scopeLogs": [{"logRecords": [{"timeUnixNano": "soon"}]}]}]}"#,
+            r#"{"resourceLogs": [{"scopeLogs": [{"logRecords": [{"timeUnixNano": -1}]}]}]}"#,
+        ];
+        for body in invalid_bodies {
+            assert!(decode_logs_data(body.as_bytes()).is_err(), "{}", body);
+        }
+    }
+}
@@ -0,0 +1,2114 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::iter::FromIterator;
+use std::net::IpAddr;
+
+use crate::metadata::Metadata;
+use crate::metrics::MetricsHandleResult;
+use crate::options;
+use crate::request_stats;
+use crate::router::*;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use base64::{engine::general_purpose as b64, Engine as _};
+use log::debug;
+use log::warn;
+use opentelemetry_proto::tonic::common::v1 as commonv1;
+use opentelemetry_proto::tonic::logs::v1 as logsv1;
+use opentelemetry_proto::tonic::metrics::v1 as metricsv1;
+use opentelemetry_proto::tonic::trace::v1 as tracev1;
+use prost::Message;
+
+pub mod grpc;
+mod json;
+
+const OTLP_PROTOBUF_FORMAT_CONTENT_TYPE: &str = "application/x-protobuf";
+const OTLP_JSON_FORMAT_CONTENT_TYPE: &str = "application/json";
+const NANOS_IN_MILLIS: u64 = 1_000_000;
+
+pub async fn handler_receiver_otlp_logs(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    let remote_address = get_address(&req);
+    let content_type = match get_content_type(&req) {
+        Ok(x) => x,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+
+    if let Some(response) = try_dropping_data(&opts, &app_state, &req, &content_type) {
+        return response;
+    }
+
+    let body = match get_decompressed_body(&req, body, &app_state) {
+        Ok(body) => body,
+        Err(e) => return e.error_response(),
+    };
+
+    let log_data: logsv1::LogsData = match request_stats::normalize_content_type(&content_type).as_str() {
+        OTLP_PROTOBUF_FORMAT_CONTENT_TYPE => match logsv1::LogsData::decode(&mut Cursor::new(body)) {
+            Ok(data) => data,
+            Err(_) => return HttpResponse::BadRequest().body("Unable to parse body"),
+        },
+        OTLP_JSON_FORMAT_CONTENT_TYPE => match json::decode_logs_data(&body) {
+            Ok(data) => data,
+            Err(e) => return HttpResponse::BadRequest().body(format!("Unable to parse body: {}", e)),
+        },
+        &_ => {
+            return get_invalid_header_response(&content_type);
+        }
+    };
+
+    handle_resource_logs(log_data.resource_logs, remote_address, &app_state, &opts);
+
+    HttpResponse::Ok().body("")
+}
+
+// Store the received logs. Shared by the OTLP/HTTP and OTLP/gRPC receivers.
+fn handle_resource_logs(
+    resource_logs: Vec<logsv1::ResourceLogs>,
+    remote_address: IpAddr,
+    app_state: &AppState,
+    opts: &options::Options,
+) {
+    // Logs of all the resources are added at once, so they're counted as a single request
+    let mut logs = vec![];
+    for resource_logs in resource_logs {
+        let metadata = get_otlp_metadata_from_logs(&resource_logs);
+        let resource_logs = get_otlp_logs(&resource_logs, &metadata);
+
+        if opts.print.logs {
+            for log in resource_logs.iter() {
+                debug!("log => {}", log.body);
+            }
+        }
+
+        logs.extend(resource_logs);
+    }
+
+    app_state.add_received_logs(logs, remote_address, opts);
+}
+
+fn get_otlp_metadata_from_logs(resource_logs: &logsv1::ResourceLogs) -> Metadata {
+    match &resource_logs.resource {
+        Some(resource) => HashMap::from_iter(resource.attributes.iter().map(|kv| {
+            (
+                kv.key.clone(),
+                match &kv.value {
+                    Some(value) => anyvalue_to_string(value),
+                    None => String::new(),
+                },
+            )
+        })),
+        None => Metadata::new(),
+    }
+}
+
+// Get the log records with their metadata: the resource attributes, the record fields which are set,
+// e.g. `severity_text` or `trace_id`, and the record attributes. On conflicting keys the record attributes
+// take precedence over the fields, which take precedence over the resource attributes.
+// Records are indexed by `time_unix_nano`, or `observed_time_unix_nano` if the former isn't set.
+fn get_otlp_logs(resource_logs: &logsv1::ResourceLogs, resource_metadata: &Metadata) -> Vec<logs::ReceivedLog> {
+    let mut logs = Vec::new();
+    for scope_logs in resource_logs.scope_logs.iter() {
+        let scope_name = match &scope_logs.scope {
+            Some(scope) => scope.name.as_str(),
+            None => "",
+        };
+        for log_record in scope_logs.log_records.iter() {
+            // Only the fields which are set. They take precedence over the resource attributes,
+            // but not over the record attributes.
+            let mut fields = vec![];
+            if log_record.severity_number != 0 {
+                fields.push(("severity_number", log_record.severity_number.to_string()));
+            }
+            if !log_record.severity_text.is_empty() {
+                fields.push(("severity_text", log_record.severity_text.clone()));
+            }
+            if log_record.time_unix_nano != 0 {
+                fields.push(("time_unix_nano", log_record.time_unix_nano.to_string()));
+            }
+            if log_record.observed_time_unix_nano != 0 {
+                fields.push((
+                    "observed_time_unix_nano",
+                    log_record.observed_time_unix_nano.to_string(),
+                ));
+            }
+            if !log_record.trace_id.is_empty() {
+                fields.push(("trace_id", hex::encode(&log_record.trace_id)));
+            }
+            if !log_record.span_id.is_empty() {
+                fields.push(("span_id", hex::encode(&log_record.span_id)));
+            }
+            if !scope_name.is_empty() {
+                fields.push(("scope_name", scope_name.to_string()));
+            }
+            let mut metadata = resource_metadata.clone();
+            metadata.extend(fields.into_iter().map(|(key, value)| (key.to_string(), value)));
+
+            for kv in log_record.attributes.iter() {
+                let value = match &kv.value {
+                    Some(value) => anyvalue_to_string(value),
+                    None => String::new(),
+                };
+                metadata.insert(kv.key.clone(), value);
+            }
+
+            let timestamp = if log_record.time_unix_nano != 0 {
+                Some((
+                    log_record.time_unix_nano / NANOS_IN_MILLIS,
+                    String::from("otlp:time_unix_nano"),
+                ))
+            } else if log_record.observed_time_unix_nano != 0 {
+                Some((
+                    log_record.observed_time_unix_nano / NANOS_IN_MILLIS,
+                    String::from("otlp:observed_time_unix_nano"),
+                ))
+            } else {
+                None
+            };
+
+            logs.push(logs::ReceivedLog {
+                body: match &log_record.body {
+                    Some(body) => anyvalue_to_string(body),
+                    None => String::new(),
+                },
+                metadata,
+                timestamp,
+            });
+        }
+    }
+    logs
+}
+
+// Scalar values are rendered as they are. Key-value lists and arrays are rendered as canonical JSON
+// (with sorted keys), so structured bodies can still be queried by path. Bytes are base64 encoded.
+fn anyvalue_to_string(anyvalue: &commonv1::AnyValue) -> String {
+    let value = match &anyvalue.value {
+        Some(v) => v,
+        None => return String::new(),
+    };
+    match value {
+        commonv1::any_value::Value::StringValue(inner) => inner.clone(),
+        commonv1::any_value::Value::BoolValue(inner) => inner.to_string(),
+        commonv1::any_value::Value::IntValue(inner) => inner.to_string(),
+        commonv1::any_value::Value::DoubleValue(inner) => inner.to_string(),
+        commonv1::any_value::Value::BytesValue(inner) => b64::STANDARD.encode(inner),
+        commonv1::any_value::Value::KvlistValue(_) | commonv1::any_value::Value::ArrayValue(_) => {
+            anyvalue_to_json(anyvalue).to_string()
+        }
+    }
+}
+
+fn anyvalue_to_json(anyvalue: &commonv1::AnyValue) -> serde_json::Value {
+    let value = match &anyvalue.value {
+        Some(v) => v,
+        None => return serde_json::Value::Null,
+    };
+    match value {
+        commonv1::any_value::Value::StringValue(inner) => serde_json::Value::from(inner.as_str()),
+        commonv1::any_value::Value::BoolValue(inner) => serde_json::Value::from(*inner),
+        commonv1::any_value::Value::IntValue(inner) => serde_json::Value::from(*inner),
+        // JSON has no NaN or infinity, so these are kept as strings
+        commonv1::any_value::Value::DoubleValue(inner) => match serde_json::Number::from_f64(*inner) {
+            Some(number) => serde_json::Value::Number(number),
+            None => serde_json::Value::from(inner.to_string()),
+        },
+        commonv1::any_value::Value::BytesValue(inner) => serde_json::Value::from(b64::STANDARD.encode(inner)),
+        commonv1::any_value::Value::ArrayValue(inner) => inner.values.iter().map(anyvalue_to_json).collect(),
+        commonv1::any_value::Value::KvlistValue(inner) => inner
+            .values
+            .iter()
+            .map(|kv| {
+                let value = match &kv.value {
+                    Some(value) => anyvalue_to_json(value),
+                    None => serde_json::Value::Null,
+                };
+                (kv.key.clone(), value)
+            })
+            .collect(),
+    }
+}
+
+pub async fn handler_receiver_otlp_metrics(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    let remote_address = get_address(&req);
+    let content_type = match get_content_type(&req) {
+        Ok(x) => x,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+
+    if let Some(response) = try_dropping_data(&opts, &app_state, &req, &content_type) {
+        return response;
+    }
+
+    let body = match get_decompressed_body(&req, body, &app_state) {
+        Ok(body) => body,
+        Err(e) => return e.error_response(),
+    };
+
+    let metrics_data: metricsv1::MetricsData = match request_stats::normalize_content_type(&content_type).as_str() {
+        OTLP_PROTOBUF_FORMAT_CONTENT_TYPE => match metricsv1::MetricsData::decode(&mut Cursor::new(body)) {
+            Ok(data) => data,
+            Err(_) => return HttpResponse::BadRequest().body("Unable to parse body"),
+        },
+        OTLP_JSON_FORMAT_CONTENT_TYPE => match json::decode_metrics_data(&body) {
+            Ok(data) => data,
+            Err(e) => return HttpResponse::BadRequest().body(format!("Unable to parse body: {}", e)),
+        },
+        &_ => {
+            return get_invalid_header_response(&content_type);
+        }
+    };
+
+    handle_resource_metrics(metrics_data.resource_metrics, remote_address, &app_state, &opts);
+
+    HttpResponse::Ok().body("")
+}
+
+// Count and store the received metrics. Shared by the OTLP/HTTP and OTLP/gRPC receivers.
+fn handle_resource_metrics(
+    resource_metrics: Vec<metricsv1::ResourceMetrics>,
+    remote_address: IpAddr,
+    app_state: &AppState,
+    opts: &options::Options,
+) {
+    let mut result = MetricsHandleResult::new();
+
+    // TODO: Consider giving it some basic capacity to avoid too many allocations.
+    let mut samples = vec![];
+    for resource_metrics in resource_metrics {
+        if resource_metrics.resource.is_none() {
+            warn!("resource is none for resource metrics");
+            continue;
+        }
+
+        let resource_attributes = &resource_metrics.resource.unwrap().attributes;
+        for instrumentation_lib_metrics in resource_metrics.scope_metrics {
+            for metric in instrumentation_lib_metrics.metrics {
+                let metric_sample_vec = sample::otlp_metric_to_samples(&metric, resource_attributes);
+
+                if opts.print.metrics {
+                    for m in &metric_sample_vec {
+                        debug!("metrics => {:?}", m);
+                    }
+                }
+                if opts.store_metrics {
+                    samples.extend(metric_sample_vec);
+                }
+
+                for series_name in sample::otlp_metric_series_names(&metric) {
+                    result.handle_metric(series_name);
+                    result.handle_ip(remote_address);
+                }
+            }
+        }
+    }
+
+    if opts.store_metrics {
+        result.metrics_samples = samples.into_iter().collect();
+    }
+
+    app_state.add_metrics_result(result, remote_address, opts);
+}
+
+pub async fn handler_receiver_otlp_traces(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    let _remote_address = get_address(&req);
+    let content_type = match get_content_type(&req) {
+        Ok(x) => x,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+
+    if let Some(response) = try_dropping_data(&opts, &app_state, &req, &content_type) {
+        return response;
+    }
+
+    let body = match get_decompressed_body(&req, body, &app_state) {
+        Ok(body) => body,
+        Err(e) => return e.error_response(),
+    };
+
+    let traces_data: tracev1::TracesData = match request_stats::normalize_content_type(&content_type).as_str() {
+        OTLP_PROTOBUF_FORMAT_CONTENT_TYPE => match tracev1::TracesData::decode(&mut Cursor::new(body)) {
+            Ok(data) => data,
+            Err(_) => return HttpResponse::BadRequest().body("Unable to parse body"),
+        },
+        OTLP_JSON_FORMAT_CONTENT_TYPE => match json::decode_traces_data(&body) {
+            Ok(data) => data,
+            Err(e) => return HttpResponse::BadRequest().body(format!("Unable to parse body: {}", e)),
+        },
+        &_ => {
+            return get_invalid_header_response(&content_type);
+        }
+    };
+
+    handle_resource_spans(traces_data.resource_spans, &app_state, &opts);
+
+    HttpResponse::Ok().body("")
+}
+
+// Count and store the received spans. Shared by the OTLP/HTTP and OTLP/gRPC receivers.
+fn handle_resource_spans(resource_spans: Vec<tracev1::ResourceSpans>, app_state: &AppState, opts: &options::Options) {
+    let mut result = traces::TracesHandleResult::new();
+
+    for resource_spans in resource_spans {
+        if resource_spans.resource.is_none() {
+            warn!("resource is none for resource spans");
+            continue;
+        }
+
+        let resource_attrs = resource_spans.resource.unwrap().attributes;
+        for instrumentation_lib_spans in resource_spans.scope_spans {
+            let scope = instrumentation_lib_spans.scope.as_ref();
+            for span in instrumentation_lib_spans.spans.iter() {
+                let storage_span = otlp_span_to_span(span, &resource_attrs, scope);
+                if opts.print.spans {
+                    debug!("Span => {}", storage_span);
+                }
+
+                result.handle_span(storage_span);
+            }
+        }
+    }
+
+    app_state.add_traces_result(result, opts);
+}
+
+// TODO: Move this to Sample module and rename that module.
+pub fn otlp_span_to_span(
+    otlp_span: &tracev1::Span,
+    resource_attrs: &[commonv1::KeyValue],
+    scope: Option<&commonv1::InstrumentationScope>,
+) -> traces::Span {
+    let attributes = sample::tags_to_map(&otlp_span.attributes, resource_attrs);
+    // Unknown enum values are reported as unspecified/unset
+    let status = match &otlp_span.status {
+        Some(status) => traces::SpanStatus {
+            code: status.code().as_str_name().to_string(),
+            message: status.message.clone(),
+        },
+        None => traces::SpanStatus {
+            code: tracev1::status::StatusCode::Unset.as_str_name().to_string(),
+            message: String::new(),
+        },
+    };
+
+    traces::Span {
+        name: otlp_span.name.clone(),
+        id: hex::encode(&otlp_span.span_id),
+        trace_id: hex::encode(&otlp_span.trace_id),
+        parent_span_id: hex::encode(&otlp_span.parent_span_id),
+        attributes,
+        resource_attributes: sample::tags_to_map(&[], resource_attrs),
+        span_attributes: sample::tags_to_map(&otlp_span.attributes, &[]),
+        kind: otlp_span.kind().as_str_name().to_string(),
+        status,
+        start_time_unix_nano: otlp_span.start_time_unix_nano,
+        end_time_unix_nano: otlp_span.end_time_unix_nano,
+        trace_state: otlp_span.trace_state.clone(),
+        scope: traces::InstrumentationScope {
+            name: scope.map(|s| s.name.clone()).unwrap_or_default(),
+            version: scope.map(|s| s.version.clone()).unwrap_or_default(),
+        },
+        events: otlp_span
+            .events
+            .iter()
+            .map(|event| traces::SpanEvent {
+                name: event.name.clone(),
+                time_unix_nano: event.time_unix_nano,
+                attributes: sample::tags_to_map(&event.attributes, &[]),
+            })
+            .collect(),
+        links: otlp_span
+            .links
+            .iter()
+            .map(|link| traces::SpanLink {
+                trace_id: hex::encode(&link.trace_id),
+                span_id: hex::encode(&link.span_id),
+                trace_state: link.trace_state.clone(),
+                attributes: sample::tags_to_map(&link.attributes, &[]),
+            })
+            .collect(),
+    }
+}
+
+mod sample {
+    use log::warn;
+    use metricsv1::number_data_point;
+    use metricsv1::{ExponentialHistogram, ExponentialHistogramDataPoint, Gauge, Histogram, Sum, Summary};
+    use opentelemetry_proto::tonic::common::v1 as commonv1;
+    use opentelemetry_proto::tonic::metrics::v1 as metricsv1;
+    use std::collections::HashMap;
+
+    use crate::metrics::sample::{format_float_label, Sample};
+
+    use super::NANOS_IN_MILLIS;
+
+    // Range of the exponential histogram scale allowed by the OTLP spec
+    const MIN_EXPONENTIAL_SCALE: i32 = -10;
+    const MAX_EXPONENTIAL_SCALE: i32 = 20;
+
+    type Attributes = [commonv1::KeyValue];
+
+    pub fn otlp_metric_to_samples(metric: &metricsv1::Metric, attributes: &Attributes) -> Vec<Sample> {
+        if let Some(data) = &metric.data {
+            match data {
+                metricsv1::metric::Data::Gauge(g) => gauge_to_samples(g, &metric.name, attributes),
+                metricsv1::metric::Data::Sum(s) => sum_to_samples(s, &metric.name, attributes),
+                metricsv1::metric::Data::Histogram(h) => histogram_to_samples(h, &metric.name, attributes),
+                metricsv1::metric::Data::ExponentialHistogram(h) => {
+                    exponential_histogram_to_samples(h, &metric.name, attributes)
+                }
+                metricsv1::metric::Data::Summary(s) => summary_to_samples(s, &metric.name, attributes),
+            }
+        } else {
+            vec![]
+        }
+    }
+
+    // Names of the series a metric is flattened into, the same way Prometheus does it
+    pub fn otlp_metric_series_names(metric: &metricsv1::Metric) -> Vec<String> {
+        match &metric.data {
+            Some(metricsv1::metric::Data::Histogram(_)) | Some(metricsv1::metric::Data::ExponentialHistogram(_)) => {
+                vec![
+                    format!("{}_bucket", metric.name),
+                    format!("{}_sum", metric.name),
+                    format!("{}_count", metric.name),
+                ]
+            }
+            Some(metricsv1::metric::Data::Summary(_)) => vec![
+                metric.name.clone(),
+                format!("{}_sum", metric.name),
+                format!("{}_count", metric.name),
+            ],
+            _ => vec![metric.name.clone()],
+        }
+    }
+
+    fn gauge_to_samples(gauge: &Gauge, name: &str, attributes: &Attributes) -> Vec<Sample> {
+        gauge
+            .data_points
+            .iter()
+            .map(|dp| number_datapoint_to_sample(dp, name, attributes))
+            .collect()
+    }
+
+    fn sum_to_samples(sum: &Sum, name: &str, attributes: &Attributes) -> Vec<Sample> {
+        sum.data_points
+            .iter()
+            .map(|dp| number_datapoint_to_sample(dp, name, attributes))
+            .collect()
+    }
+
+    fn number_datapoint_to_sample(dp: &metricsv1::NumberDataPoint, name: &str, attributes: &Attributes) -> Sample {
+        if let Some(val) = &dp.value {
+            let labels = tags_to_map(&dp.attributes, attributes);
+            let timestamp = get_number_datapoint_timestamp_millis(dp);
+            match val {
+                number_data_point::Value::AsDouble(x) => create_sample(name, labels, *x, timestamp),
+                number_data_point::Value::AsInt(x) => create_sample(name, labels, *x as f64, timestamp),
+            }
+        } else {
+            Sample {
+                metric: String::new(),
+                value: 0.0,
+                labels: HashMap::new(),
+                timestamp: 0,
+                meta_labels: HashMap::new(),
+            }
+        }
+    }
+
+    fn histogram_to_samples(histogram: &Histogram, name: &str, attributes: &Attributes) -> Vec<Sample> {
+        let mut samples = vec![];
+        for dp in histogram.data_points.iter() {
+            let labels = tags_to_map(&dp.attributes, attributes);
+            let timestamp = dp.time_unix_nano / NANOS_IN_MILLIS;
+
+            // OTLP bucket counts aren't cumulative, Prometheus ones are.
+            // The last bucket has no explicit bound and ends at +Inf.
+            let mut cumulative_count: u64 = 0;
+            let mut buckets = vec![];
+            for (i, count) in dp.bucket_counts.iter().enumerate() {
+                cumulative_count = cumulative_count.saturating_add(*count);
+                let upper_bound = dp.explicit_bounds.get(i).copied().unwrap_or(f64::INFINITY);
+                buckets.push((upper_bound, cumulative_count));
+            }
+            if buckets.last().map(|(upper_bound, _)| *upper_bound) != Some(f64::INFINITY) {
+                buckets.push((f64::INFINITY, dp.count));
+            }
+
+            samples.extend(buckets_to_samples(name, &labels, buckets, timestamp));
+            if let Some(sum) = dp.sum {
+                samples.push(create_sample(
+                    &format!("{}_sum", name),
+                    labels.clone(),
+                    sum,
+                    timestamp,
+                ));
+            }
+            samples.push(create_sample(
+                &format!("{}_count", name),
+                labels,
+                dp.count as f64,
+                timestamp,
+            ));
+        }
+        samples
+    }
+
+    fn exponential_histogram_to_samples(
+        histogram: &ExponentialHistogram,
+        name: &str,
+        attributes: &Attributes,
+    ) -> Vec<Sample> {
+        let mut samples = vec![];
+        for dp in histogram.data_points.iter() {
+            let labels = tags_to_map(&dp.attributes, attributes);
+            let timestamp = dp.time_unix_nano / NANOS_IN_MILLIS;
+
+            let Some(buckets) = exponential_histogram_buckets(dp) else {
+                warn!(
+                    "Skipping exponential histogram data point of {} with invalid scale {} or bucket offsets",
+                    name, dp.scale
+                );
+                continue;
+            };
+
+            samples.extend(buckets_to_samples(name, &labels, buckets, timestamp));
+            if let Some(sum) = dp.sum {
+                samples.push(create_sample(
+                    &format!("{}_sum", name),
+                    labels.clone(),
+                    sum,
+                    timestamp,
+                ));
+            }
+            samples.push(create_sample(
+                &format!("{}_count", name),
+                labels,
+                dp.count as f64,
+                timestamp,
+            ));
+        }
+        samples
+    }
+
+    // Cumulative buckets of an exponential histogram data point with their upper bounds,
+    // None when the scale or the bucket indexes are out of range
+    fn exponential_histogram_buckets(dp: &ExponentialHistogramDataPoint) -> Option<Vec<(f64, u64)>> {
+        if !(MIN_EXPONENTIAL_SCALE..=MAX_EXPONENTIAL_SCALE).contains(&dp.scale) {
+            return None;
+        }
+
+        // Bucket with index i covers the range (base^i, base^(i+1)], where base = 2^(2^-scale).
+        // Negative buckets mirror the positive ones, so go through them from the highest index
+        // to get increasing upper bounds, then the zero bucket and finally the positive buckets.
+        let base = 2_f64.powf(2_f64.powi(-dp.scale));
+        let bucket_index = |offset: i32, i: usize| offset.checked_add(i32::try_from(i).ok()?);
+        let mut cumulative_count: u64 = 0;
+        let mut buckets = vec![];
+        if let Some(negative) = &dp.negative {
+            for (i, count) in negative.bucket_counts.iter().enumerate().rev() {
+                cumulative_count = cumulative_count.saturating_add(*count);
+                buckets.push((-base.powi(bucket_index(negative.offset, i)?), cumulative_count));
+            }
+        }
+        cumulative_count = cumulative_count.saturating_add(dp.zero_count);
+        buckets.push((dp.zero_threshold, cumulative_count));
+        if let Some(positive) = &dp.positive {
+            for (i, count) in positive.bucket_counts.iter().enumerate() {
+                cumulative_count = cumulative_count.saturating_add(*count);
+                let index = bucket_index(positive.offset, i)?.checked_add(1)?;
+                buckets.push((base.powi(index), cumulative_count));
+            }
+        }
+        buckets.push((f64::INFINITY, dp.count));
+        Some(buckets)
+    }
+
+    fn summary_to_samples(summary: &Summary, name: &str, attributes: &Attributes) -> Vec<Sample> {
+        let mut samples = vec![];
+        for dp in summary.data_points.iter() {
+            let labels = tags_to_map(&dp.attributes, attributes);
+            let timestamp = dp.time_unix_nano / NANOS_IN_MILLIS;
+
+            for quantile_value in dp.quantile_values.iter() {
+                let mut quantile_labels = labels.clone();
+                quantile_labels.insert(
+                    "quantile".to_string(),
+                    format_float_label(quantile_value.quantile),
+                );
+                samples.push(create_sample(
+                    name,
+                    quantile_labels,
+                    quantile_value.value,
+                    timestamp,
+                ));
+            }
+            samples.push(create_sample(
+                &format!("{}_sum", name),
+                labels.clone(),
+                dp.sum,
+                timestamp,
+            ));
+            samples.push(create_sample(
+                &format!("{}_count", name),
+                labels,
+                dp.count as f64,
+                timestamp,
+            ));
+        }
+        samples
+    }
+
+    // Create a `<name>_bucket` sample with the `le` label for every (upper bound, cumulative count) pair
+    fn buckets_to_samples(
+        name: &str,
+        labels: &HashMap<String, String>,
+        buckets: Vec<(f64, u64)>,
+        timestamp: u64,
+    ) -> Vec<Sample> {
+        let bucket_name = format!("{}_bucket", name);
+        buckets
+            .into_iter()
+            .map(|(upper_bound, count)| {
+                let mut bucket_labels = labels.clone();
+                bucket_labels.insert("le".to_string(), format_float_label(upper_bound));
+                create_sample(&bucket_name, bucket_labels, count as f64, timestamp)
+            })
+            .collect()
+    }
+
+    fn get_number_datapoint_timestamp_millis(dp: &metricsv1::NumberDataPoint) -> u64 {
+        dp.time_unix_nano / NANOS_IN_MILLIS
+    }
+
+    fn create_sample(name: &str, labels: HashMap<String, String>, value: f64, timestamp: u64) -> Sample {
+        Sample {
+            metric: name.to_string(),
+            value,
+            labels,
+            timestamp,
+            meta_labels: HashMap::new(),
+        }
+    }
+
+    pub fn tags_to_map(attrs: &Attributes, labels: &Attributes) -> HashMap<String, String> {
+        attrs
+            .iter()
+            .chain(labels.iter())
+            .map(|kv| {
+                (
+                    kv.key.clone(),
+                    // FIXME: An empty string is passed instead of panicking. Some custom error could be better for debug purposes.
+                    super::anyvalue_to_string(kv.value.as_ref().unwrap_or(&commonv1::AnyValue {
+                        value: Some(commonv1::any_value::Value::StringValue("".to_string())),
+                    })),
+                )
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::collections::HashMap;
+
+        use commonv1::{AnyValue, KeyValue};
+        use metricsv1::{Gauge, Metric, NumberDataPoint, Sum};
+        use opentelemetry_proto::tonic::common::v1 as commonv1;
+        use opentelemetry_proto::tonic::metrics::v1 as metricsv1;
+
+        use crate::metrics::sample::Sample;
+
+        fn get_string_anyvalue(string: &str) -> AnyValue {
+            AnyValue {
+                value: Some(commonv1::any_value::Value::StringValue(string.to_string())),
+            }
+        }
+
+        fn pairs_to_keyvalue(pairs: Vec<(&str, AnyValue)>) -> Vec<KeyValue> {
+            pairs
+                .into_iter()
+                .map(|(k, v)| KeyValue {
+                    key: k.to_string(),
+                    value: Some(v),
+                })
+                .collect()
+        }
+
+        fn get_sample_resource_attrs() -> Vec<KeyValue> {
+            pairs_to_keyvalue(vec![
+                ("key1", get_string_anyvalue("value1")),
+                ("key2", get_string_anyvalue("value2")),
+            ])
+        }
+
+        fn get_sample_dp_attrs() -> Vec<KeyValue> {
+            pairs_to_keyvalue(vec![
+                ("key2", get_string_anyvalue("surprise")),
+                ("key3", get_string_anyvalue("value3")),
+            ])
+        }
+
+        fn get_expected_labels() -> HashMap<String, String> {
+            vec![
+                ("key1".to_string(), "value1".to_string()),
+                ("key2".to_string(), "value2".to_string()),
+                ("key3".to_string(), "value3".to_string()),
+            ]
+            .into_iter()
+            .collect()
+        }
+
+        #[test]
+        fn otlp_format_tags_to_string_test() {
+            let attrs = get_sample_dp_attrs();
+            let labels = get_sample_resource_attrs();
+
+            let expected = get_expected_labels();
+
+            assert_eq!(super::tags_to_map(&attrs, &labels), expected);
+        }
+
+        fn get_sample_number_dp(value: i64, timestamp_ms: u64) -> NumberDataPoint {
+            NumberDataPoint {
+                attributes: get_sample_dp_attrs(),
+                start_time_unix_nano: 161078,
+                time_unix_nano: timestamp_ms * super::NANOS_IN_MILLIS,
+                exemplars: vec![],
+                flags: 0,
+                value: Some(metricsv1::number_data_point::Value::AsInt(value)),
+            }
+        }
+
+        fn get_sample_sample(name: &str, value: f64, timestamp: u64) -> Sample {
+            Sample {
+                metric: name.to_string(),
+                value: value,
+                labels: get_expected_labels(),
+                timestamp,
+                meta_labels: HashMap::new(),
+            }
+        }
+
+        #[test]
+        fn otlp_format_number_datapoint_to_sample_test() {
+            let name = "metr";
+            let attrs = get_sample_resource_attrs();
+
+            let dp = get_sample_number_dp(7312, 2042005);
+            let expected = get_sample_sample(name, 7312_f64, 2042005);
+
+            assert_eq!(super::number_datapoint_to_sample(&dp, name, &attrs), expected)
+        }
+
+        pub fn get_sample_gauge() -> Gauge {
+            Gauge {
+                data_points: vec![get_sample_number_dp(78, 1400), get_sample_number_dp(500, 45000)],
+            }
+        }
+
+        fn get_sample_sum() -> Sum {
+            Sum {
+                data_points: vec![get_sample_number_dp(78, 1400), get_sample_number_dp(500, 45000)],
+                aggregation_temporality: 0,
+                is_monotonic: true,
+            }
+        }
+
+        fn get_sample_metric(name: &str, data: metricsv1::metric::Data) -> Metric {
+            Metric {
+                name: name.to_string(),
+                description: String::new(),
+                unit: String::new(),
+                data: Some(data),
+                metadata: vec![],
+            }
+        }
+
+        #[test]
+        fn otlp_format_gauge_to_string_test() {
+            let gauge = metricsv1::metric::Data::Gauge(get_sample_gauge());
+            let metric = get_sample_metric("eguag", gauge);
+            let attrs = get_sample_resource_attrs();
+
+            assert_eq!(
+                super::otlp_metric_to_samples(&metric, &attrs),
+                vec![
+                    get_sample_sample("eguag", 78_f64, 1400),
+                    get_sample_sample("eguag", 500_f64, 45000),
+                ],
+            )
+        }
+
+        #[test]
+        fn otlp_format_sum_to_string_test() {
+            let sum = metricsv1::metric::Data::Sum(get_sample_sum());
+            let metric = get_sample_metric("mus", sum);
+            let attrs = get_sample_resource_attrs();
+
+            assert_eq!(
+                super::otlp_metric_to_samples(&metric, &attrs),
+                vec![
+                    get_sample_sample("mus", 78_f64, 1400),
+                    get_sample_sample("mus", 500_f64, 45000),
+                ],
+            )
+        }
+
+        // Sample equality ignores values, so compare names, the given label and values instead
+        fn get_series(samples: &[Sample], label: &str) -> Vec<(String, Option<String>, f64, u64)> {
+            samples
+                .iter()
+                .map(|s| {
+                    (
+                        s.metric.clone(),
+                        s.labels.get(label).cloned(),
+                        s.value,
+                        s.timestamp,
+                    )
+                })
+                .collect()
+        }
+
+        fn series(name: &str, label: Option<&str>, value: f64) -> (String, Option<String>, f64, u64) {
+            (name.to_string(), label.map(|l| l.to_string()), value, 1400)
+        }
+
+        #[test]
+        fn otlp_format_histogram_to_samples_test() {
+            let histogram = metricsv1::metric::Data::Histogram(metricsv1::Histogram {
+                data_points: vec![metricsv1::HistogramDataPoint {
+                    attributes: get_sample_dp_attrs(),
+                    time_unix_nano: 1400 * super::NANOS_IN_MILLIS,
+                    count: 10,
+                    sum: Some(42.5),
+                    bucket_counts: vec![1, 2, 3, 4],
+                    explicit_bounds: vec![0.5, 1.0, 2.5],
+                    ..Default::default()
+                }],
+                aggregation_temporality: 2,
+            });
+            let metric = get_sample_metric("latency", histogram);
+            let attrs = get_sample_resource_attrs();
+
+            let samples = super::otlp_metric_to_samples(&metric, &attrs);
+            assert_eq!(
+                get_series(&samples, "le"),
+                vec![
+                    series("latency_bucket", Some("0.5"), 1.0),
+                    series("latency_bucket", Some("1"), 3.0),
+                    series("latency_bucket", Some("2.5"), 6.0),
+                    series("latency_bucket", Some("+Inf"), 10.0),
+                    series("latency_sum", None, 42.5),
+                    series("latency_count", None, 10.0),
+                ]
+            );
+            for sample in samples.iter() {
+                assert_eq!(sample.labels.get("key3"), Some(&"value3".to_string()));
+            }
+            assert_eq!(
+                super::otlp_metric_series_names(&metric),
+                vec!["latency_bucket", "latency_sum", "latency_count"]
+            );
+        }
+
+        #[test]
+        fn otlp_format_histogram_overflow_test() {
+            // cumulative bucket counts saturate instead of overflowing
+            let histogram = metricsv1::metric::Data::Histogram(metricsv1::Histogram {
+                data_points: vec![metricsv1::HistogramDataPoint {
+                    time_unix_nano: 1400 * super::NANOS_IN_MILLIS,
+                    count: u64::MAX,
+                    bucket_counts: vec![u64::MAX, 1],
+                    explicit_bounds: vec![1.0],
+                    ..Default::default()
+                }],
+                aggregation_temporality: 2,
+            });
+            let metric = get_sample_metric("latency", histogram);
+
+            let samples = super::otlp_metric_to_samples(&metric, &[]);
+            assert_eq!(
+                get_series(&samples, "le"),
+                vec![
+                    series("latency_bucket", Some("1"), u64::MAX as f64),
+                    series("latency_bucket", Some("+Inf"), u64::MAX as f64),
+                    series("latency_count", None, u64::MAX as f64),
+                ]
+            );
+        }
+
+        #[test]
+        fn otlp_format_exponential_histogram_to_samples_test() {
+            // With scale 0 the base is 2, so bucket i covers (2^i, 2^(i+1)]
+            let histogram = metricsv1::metric::Data::ExponentialHistogram(metricsv1::ExponentialHistogram {
+                data_points: vec![metricsv1::ExponentialHistogramDataPoint {
+                    attributes: get_sample_dp_attrs(),
+                    time_unix_nano: 1400 * super::NANOS_IN_MILLIS,
+                    count: 9,
+                    sum: Some(20.0),
+                    scale: 0,
+                    zero_count: 1,
+                    positive: Some(metricsv1::exponential_histogram_data_point::Buckets {
+                        offset: 1,
+                        bucket_counts: vec![3, 4],
+                    }),
+                    negative: Some(metricsv1::exponential_histogram_data_point::Buckets {
+                        offset: 0,
+                        bucket_counts: vec![1],
+                    }),
+                    ..Default::default()
+                }],
+                aggregation_temporality: 2,
+            });
+            let metric = get_sample_metric("size", histogram);
+            let attrs = get_sample_resource_attrs();
+
+            let samples = super::otlp_metric_to_samples(&metric, &attrs);
+            assert_eq!(
+                get_series(&samples, "le"),
+                vec![
+                    series("size_bucket", Some("-1"), 1.0),
+                    series("size_bucket", Some("0"), 2.0),
+                    series("size_bucket", Some("4"), 5.0),
+                    series("size_bucket", Some("8"), 9.0),
+                    series("size_bucket", Some("+Inf"), 9.0),
+                    series("size_sum", None, 20.0),
+                    series("size_count", None, 9.0),
+                ]
+            );
+            assert_eq!(
+                super::otlp_metric_series_names(&metric),
+                vec!["size_bucket", "size_sum", "size_count"]
+            );
+        }
+
+        #[test]
+        fn otlp_format_exponential_histogram_invalid_test() {
+            // data points with the scale out of range or overflowing bucket indexes are skipped
+            let get_data_point = |scale, offset| metricsv1::ExponentialHistogramDataPoint {
+                time_unix_nano: 1400 * super::NANOS_IN_MILLIS,
+                count: 2,
+                scale,
+                positive: Some(metricsv1::exponential_histogram_data_point::Buckets {
+                    offset,
+                    bucket_counts: vec![1, 1],
+                }),
+                ..Default::default()
+            };
+            let histogram = metricsv1::metric::Data::ExponentialHistogram(metricsv1::ExponentialHistogram {
+                data_points: vec![
+                    get_data_point(i32::MIN, 0),
+                    get_data_point(21, 0),
+                    get_data_point(0, i32::MAX),
+                    get_data_point(0, 0),
+                ],
+                aggregation_temporality: 2,
+            });
+            let metric = get_sample_metric("size", histogram);
+
+            let samples = super::otlp_metric_to_samples(&metric, &[]);
+            assert_eq!(
+                get_series(&samples, "le"),
+                vec![
+                    series("size_bucket", Some("0"), 0.0),
+                    series("size_bucket", Some("2"), 1.0),
+                    series("size_bucket", Some("4"), 2.0),
+                    series("size_bucket", Some("+Inf"), 2.0),
+                    series("size_count", None, 2.0),
+                ]
+            );
+        }
+
+        #[test]
+        fn otlp_format_summary_to_samples_test() {
+            let summary = metricsv1::metric::Data::Summary(metricsv1::Summary {
+                data_points: vec![metricsv1::SummaryDataPoint {
+                    attributes: get_sample_dp_attrs(),
+                    time_unix_nano: 1400 * super::NANOS_IN_MILLIS,
+                    count: 100,
+                    sum: 250.0,
+                    quantile_values: vec![
+                        metricsv1::summary_data_point::ValueAtQuantile {
+                            quantile: 0.5,
+                            value: 2.0,
+                        },
+                        metricsv1::summary_data_point::ValueAtQuantile {
+                            quantile: 0.99,
+                            value: 7.5,
+                        },
+                    ],
+                    ..Default::default()
+                }],
+            });
+            let metric = get_sample_metric("duration", summary);
+            let attrs = get_sample_resource_attrs();
+
+            let samples = super::otlp_metric_to_samples(&metric, &attrs);
+            assert_eq!(
+                get_series(&samples, "quantile"),
+                vec![
+                    series("duration", Some("0.5"), 2.0),
+                    series("duration", Some("0.99"), 7.5),
+                    series("duration_sum", None, 250.0),
+                    series("duration_count", None, 100.0),
+                ]
+            );
+            assert_eq!(
+                super::otlp_metric_series_names(&metric),
+                vec!["duration", "duration_sum", "duration_count"]
+            );
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::metrics::sample::Sample;
+    use crate::router::otlp::*;
+    use actix_http::body::{BoxBody, MessageBody};
+    use actix_web::test as actix_test;
+    use actix_web::{web, App};
+    use bytes::Bytes;
+    use opentelemetry_proto::tonic::metrics::v1::{Metric, ResourceMetrics, ScopeMetrics};
+    use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans};
+    use opentelemetry_proto::tonic::{
+        common::v1::{any_value::Value, AnyValue, InstrumentationScope, KeyValue},
+        logs::v1::{LogRecord, ScopeLogs},
+        resource::v1::Resource,
+    };
+
+    const NANOS_IN_MILLIS: u64 = 1_000_000;
+
+    fn get_default_options() -> options::Options {
+        options::Options {
+            store_traces: true,
+            store_metrics: true,
+            store_logs: true,
+            ..Default::default()
+        }
+    }
+
+    fn get_default_app_data() -> web::Data<AppState> {
+        web::Data::new(AppState::new())
+    }
+
+    fn get_body_str(body: BoxBody) -> String {
+        std::str::from_utf8(&body.try_into_bytes().unwrap())
+            .unwrap()
+            .to_string()
+    }
+
+    fn get_sample_log_record(body: &str) -> LogRecord {
+        #[allow(deprecated)]
+        LogRecord {
+            time_unix_nano: 21,
+            observed_time_unix_nano: 99,
+            severity_number: 20000,
+            severity_text: "warning".to_string(),
+            body: Some(AnyValue {
+                value: Some(Value::StringValue(body.to_string())),
+            }),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            flags: 0b101010,
+            trace_id: vec![],
+            span_id: vec![],
+        }
+    }
+
+    fn get_sample_resource() -> Resource {
+        Resource {
+            attributes: vec![
+                KeyValue {
+                    key: "some-key".to_string(),
+                    value: Some(AnyValue {
+                        value: Some(Value::StringValue("blep".to_string())),
+                    }),
+                },
+                KeyValue {
+                    key: "another-key".to_string(),
+                    value: Some(AnyValue {
+                        value: Some(Value::StringValue("qwerty".to_string())),
+                    }),
+                },
+            ],
+            dropped_attributes_count: 0,
+        }
+    }
+
+    fn get_sample_instr_library() -> InstrumentationScope {
+        InstrumentationScope {
+            name: "the best library".to_string(),
+            version: "v2.1.5".to_string(),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+        }
+    }
+
+    fn get_sample_logs_data() -> logsv1::LogsData {
+        let resource = get_sample_resource();
+
+        let instr = vec![ScopeLogs {
+            scope: Some(get_sample_instr_library()),
+            log_records: vec![
+                get_sample_log_record("warning: the temperature is too low"),
+                get_sample_log_record("killing child with a fork"),
+            ],
+            schema_url: String::new(),
+        }];
+
+        let resource_logs_1 = logsv1::ResourceLogs {
+            resource: Some(resource.clone()),
+            scope_logs: instr,
+            schema_url: String::new(),
+        };
+
+        let resource_logs_2 = resource_logs_1.clone();
+        logsv1::LogsData {
+            resource_logs: vec![resource_logs_1, resource_logs_2],
+        }
+    }
+
+    fn get_sample_logs_request_body() -> impl Into<web::Bytes> {
+        let logs = get_sample_logs_data();
+        logs.encode_to_vec()
+    }
+
+    #[test]
+    fn otlp_logs_get_metadata_test() {
+        let logs = &get_sample_logs_data().resource_logs[0];
+        let metadata = get_otlp_metadata_from_logs(logs);
+
+        let mut expected = HashMap::new();
+        expected.insert("some-key".to_string(), "blep".to_string());
+        expected.insert("another-key".to_string(), "qwerty".to_string());
+
+        assert_eq!(metadata, expected)
+    }
+
+    #[test]
+    fn otlp_logs_get_logs_test() {
+        let logs = &get_sample_logs_data().resource_logs[0];
+        let logs = get_otlp_logs(logs, &get_otlp_metadata_from_logs(logs));
+
+        let bodies: Vec<&str> = logs.iter().map(|log| log.body.as_str()).collect();
+        let expected = vec!["warning: the temperature is too low", "killing child with a fork"];
+        assert_eq!(bodies, expected);
+
+        let mut expected_metadata = HashMap::new();
+        expected_metadata.insert("some-key".to_string(), "blep".to_string());
+        expected_metadata.insert("another-key".to_string(), "qwerty".to_string());
+        expected_metadata.insert("severity_number".to_string(), "20000".to_string());
+        expected_metadata.insert("severity_text".to_string(), "warning".to_string());
+        expected_metadata.insert("time_unix_nano".to_string(), "21".to_string());
+        expected_metadata.insert("observed_time_unix_nano".to_string(), "99".to_string());
+        expected_metadata.insert("scope_name".to_string(), "the best library".to_string());
+        assert_eq!(logs[0].metadata, expected_metadata);
+        assert_eq!(
+            logs[0].timestamp,
+            Some((0, String::from("otlp:time_unix_nano")))
+        );
+    }
+
+    #[test]
+    fn otlp_anyvalue_to_string_test() {
+        use opentelemetry_proto::tonic::common::v1::{ArrayValue, KeyValueList};
+
+        let kvlist = AnyValue {
+            value: Some(Value::KvlistValue(KeyValueList {
+                values: vec![
+                    KeyValue {
+                        key: "log".to_string(),
+                        value: Some(AnyValue {
+                            value: Some(Value::KvlistValue(KeyValueList {
+                                values: vec![KeyValue {
+                                    key: "level".to_string(),
+                                    value: Some(AnyValue {
+                                        value: Some(Value::StringValue("error".to_string())),
+                                    }),
+                                }],
+                            })),
+                        }),
+                    },
+                    KeyValue {
+                        key: "count".to_string(),
+                        value: Some(AnyValue {
+                            value: Some(Value::IntValue(3)),
+                        }),
+                    },
+                    KeyValue {
+                        key: "data".to_string(),
+                        value: Some(AnyValue {
+                            value: Some(Value::BytesValue(b"hello".to_vec())),
+                        }),
+                    },
+                ],
+            })),
+        };
+        // keys are sorted
+        assert_eq!(
+            anyvalue_to_string(&kvlist),
+            r#"{"count":3,"data":"aGVsbG8=","log":{"level":"error"}}"#
+        );
+
+        let array = AnyValue {
+            value: Some(Value::ArrayValue(ArrayValue {
+                values: vec![
+                    AnyValue {
+                        value: Some(Value::StringValue("a".to_string())),
+                    },
+                    AnyValue {
+                        value: Some(Value::DoubleValue(1.5)),
+                    },
+                    AnyValue {
+                        value: Some(Value::BoolValue(true)),
+                    },
+                    AnyValue { value: None },
+                ],
+            })),
+        };
+        assert_eq!(anyvalue_to_string(&array), r#"["a",1.5,true,null]"#);
+
+        let bytes = AnyValue {
+            value: Some(Value::BytesValue(vec![0, 1, 2])),
+        };
+        assert_eq!(anyvalue_to_string(&bytes), "AAEC");
+    }
+
+    #[actix_rt::test]
+    async fn otlp_logs_structured_body_test() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(get_default_options()))
+                .app_data(get_default_app_data())
+                .service(web::scope("/v1").route("/logs", web::post().to(handler_receiver_otlp_logs)))
+                .route("/logs/count", web::get().to(handler_logs_count)),
+        )
+        .await;
+
+        let request = actix_test::TestRequest::post()
+            .uri("/v1/logs")
+            .insert_header(("Content-Type", OTLP_JSON_FORMAT_CONTENT_TYPE))
+            .set_payload(
+                r#"{"resourceLogs": [{
+                    "scopeLogs": [{"logRecords": [
+                        {"body": {"kvlistValue": {"values": [
+                            {"key": "log", "value": {"kvlistValue": {"values": [
+                                {"key": "level", "value": {"stringValue": "error"}}
+                            ]}}}
+                        ]}}},
+                        {"body": {"kvlistValue": {"values": [
+                            {"key": "log", "value": {"kvlistValue": {"values": [
+                                {"key": "level", "value": {"stringValue": "info"}}
+                            ]}}}
+                        ]}}}
+                    ]}]
+                }]}"#,
+            )
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let req = actix_test::TestRequest::get()
+            .uri("/logs/count?body.log.level=error")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        let response_body: LogsCountResponse = actix_test::read_body_json(resp).await;
+        assert_eq!(response_body.count, 1);
+    }
+
+    #[test]
+    fn otlp_logs_record_fields_test() {
+        let mut log_record = get_sample_log_record("record with attributes");
+        log_record.time_unix_nano = 0;
+        log_record.observed_time_unix_nano = 1638873379541000000;
+        log_record.trace_id = vec![0xbb; 16];
+        log_record.span_id = vec![0xaa; 8];
+        log_record.attributes = vec![
+            KeyValue {
+                key: "some-key".to_string(),
+                value: Some(AnyValue {
+                    value: Some(Value::StringValue("from-record".to_string())),
+                }),
+            },
+            KeyValue {
+                key: "log.file.path".to_string(),
+                value: Some(AnyValue {
+                    value: Some(Value::StringValue("/var/log/app.log".to_string())),
+                }),
+            },
+            KeyValue {
+                key: "severity_text".to_string(),
+                value: Some(AnyValue {
+                    value: Some(Value::StringValue("from-record".to_string())),
+                }),
+            },
+        ];
+        log_record.severity_number = 0;
+        let resource_logs = logsv1::ResourceLogs {
+            resource: Some(get_sample_resource()),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![log_record],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        };
+
+        let logs = get_otlp_logs(&resource_logs, &get_otlp_metadata_from_logs(&resource_logs));
+
+        assert_eq!(logs.len(), 1);
+        let metadata = &logs[0].metadata;
+        // record attributes take precedence over resource attributes
+        assert_eq!(metadata["some-key"], "from-record");
+        assert_eq!(metadata["another-key"], "qwerty");
+        assert_eq!(metadata["log.file.path"], "/var/log/app.log");
+        assert_eq!(metadata["trace_id"], "bb".repeat(16));
+        assert_eq!(metadata["span_id"], "aa".repeat(8));
+        // record attributes take precedence over record fields
+        assert_eq!(metadata["severity_text"], "from-record");
+        // fields which aren't set are skipped
+        assert!(!metadata.contains_key("scope_name"));
+        assert!(!metadata.contains_key("severity_number"));
+        assert!(!metadata.contains_key("time_unix_nano"));
+        assert_eq!(
+            logs[0].timestamp,
+            Some((1638873379541, String::from("otlp:observed_time_unix_nano")))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn otlp_logs_drop_test() {
+        let mut opts = get_default_options();
+        opts.drop_rate = 100;
+
+        let mut app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts.clone()))
+                .app_data(get_default_app_data())
+                .service(web::scope("/v1").route("/logs", web::post().to(handler_receiver_otlp_logs)))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            // Test logs route.
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/logs")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            let body = response.into_body();
+            assert_eq!(
+                get_body_str(body),
+                format!("Dropping data for {}", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE)
+            );
+        }
+
+        {
+            // Test metrics route.
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/metrics")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            let body = response.into_body();
+            assert_eq!(
+                get_body_str(body),
+                format!("Dropping data for {}", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE)
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn otlp_unrelated_content_type_test() {
+        let content_type = "unrelated/type";
+        let opts = get_default_options();
+        let mut app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts.clone()))
+                .app_data(get_default_app_data())
+                .service(
+                    web::scope("/v1")
+                        .route("/logs", web::post().to(handler_receiver_otlp_logs))
+                        .route("/metrics", web::post().to(handler_receiver_otlp_metrics)),
+                )
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+        {
+            // Test logs route.
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/logs")
+                .insert_header(("Content-Type", content_type))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        {
+            // Test metrics route.
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/metrics")
+                .insert_header(("Content-Type", content_type))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn otlp_logs_store_test() {
+        let opts = get_default_options();
+        let mut app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts.clone()))
+                .app_data(get_default_app_data())
+                .service(web::scope("/v1").route("/logs", web::post().to(handler_receiver_otlp_logs)))
+                .route("/logs/count", web::get().to(handler_logs_count))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/logs")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .set_payload(get_sample_logs_request_body())
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // count all the logs
+        {
+            let req = actix_test::TestRequest::get().uri("/logs/count").to_request();
+            let resp = actix_test::call_service(&mut app, req).await;
+
+            let response_body: LogsCountResponse = actix_test::read_body_json(resp).await;
+
+            assert_eq!(response_body.count, 4);
+        }
+
+        // count the logs by record fields
+        {
+            let req = actix_test::TestRequest::get()
+                .uri("/logs/count?severity_text=warning&scope_name=the%20best%20library&some-key=blep")
+                .to_request();
+            let resp = actix_test::call_service(&mut app, req).await;
+
+            let response_body: LogsCountResponse = actix_test::read_body_json(resp).await;
+
+            assert_eq!(response_body.count, 4);
+        }
+        {
+            // records are indexed by time_unix_nano, which is 21ns
+            let req = actix_test::TestRequest::get()
+                .uri("/logs/count?from_ts=1")
+                .to_request();
+            let resp = actix_test::call_service(&mut app, req).await;
+
+            let response_body: LogsCountResponse = actix_test::read_body_json(resp).await;
+
+            assert_eq!(response_body.count, 0);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn otlp_json_content_type_parameters_test() {
+        let opts = get_default_options();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts))
+                .app_data(get_default_app_data())
+                .route("/v1/logs", web::post().to(handler_receiver_otlp_logs))
+                .route("/logs/count", web::get().to(handler_logs_count)),
+        )
+        .await;
+
+        let request = actix_test::TestRequest::post()
+            .uri("/v1/logs")
+            .insert_header(("Content-Type", "Application/JSON; charset=utf-8"))
+            .set_payload(r#"{"resourceLogs": [{"scopeLogs": [{"logRecords": [{"body": {"stringValue": "first"}}]}]}]}"#)
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let req = actix_test::TestRequest::get().uri("/logs/count").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        let response_body: LogsCountResponse = actix_test::read_body_json(resp).await;
+        assert_eq!(response_body.count, 1);
+    }
+
+    #[actix_rt::test]
+    async fn otlp_json_store_test() {
+        let opts = get_default_options();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts))
+                .app_data(get_default_app_data())
+                .service(
+                    web::scope("/v1")
+                        .route("/logs", web::post().to(handler_receiver_otlp_logs))
+                        .route("/metrics", web::post().to(handler_receiver_otlp_metrics))
+                        .route("/traces", web::post().to(handler_receiver_otlp_traces)),
+                )
+                .route("/logs/count", web::get().to(handler_logs_count))
+                .route(
+                    "/metrics-samples",
+                    web::get().to(metrics_data::handler_metrics_samples),
+                )
+                .route("/spans-list", web::get().to(traces_data::handler_get_spans))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/logs")
+                .insert_header(("Content-Type", OTLP_JSON_FORMAT_CONTENT_TYPE))
+                .set_payload(
+                    r#"{"resourceLogs": [{
+                        "resource": {"attributes": [{"key": "namespace", "value": {"stringValue": "default"}}]},
+                        "scopeLogs": [{"logRecords": [
+                            {"body": {"stringValue": "first"}},
+                            {"body": {"stringValue": "second"}}
+                        ]}]
+                    }]}"#,
+                )
+                .to_request();
+
+            let response = actix_test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let req = actix_test::TestRequest::get()
+                .uri("/logs/count?namespace=default")
+                .to_request();
+            let resp = actix_test::call_service(&app, req).await;
+            let response_body: LogsCountResponse = actix_test::read_body_json(resp).await;
+            assert_eq!(response_body.count, 2);
+        }
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/metrics")
+                .insert_header(("Content-Type", OTLP_JSON_FORMAT_CONTENT_TYPE))
+                .set_payload(
+                    r#"{"resourceMetrics": [{
+                        "resource": {"attributes": [{"key": "cluster", "value": {"stringValue": "kind"}}]},
+                        "scopeMetrics": [{"metrics": [{
+                            "name": "requests",
+                            "sum": {"dataPoints": [{"asInt": "42", "timeUnixNano": "1638873379541000000"}]}
+                        }]}]
+                    }]}"#,
+                )
+                .to_request();
+
+            let response = actix_test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let request = actix_test::TestRequest::get()
+                .uri("/metrics-samples?__name__=requests&cluster=kind")
+                .to_request();
+            let response = actix_test::call_service(&app, request).await;
+            let result: Vec<Sample> = actix_test::read_body_json(response).await;
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].value, 42.0);
+            assert_eq!(result[0].timestamp, 1638873379541);
+        }
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/traces")
+                .insert_header(("Content-Type", OTLP_JSON_FORMAT_CONTENT_TYPE))
+                .set_payload(
+                    r#"{"resourceSpans": [{
+                        "resource": {"attributes": []},
+                        "scopeSpans": [{"spans": [{
+                            "traceId": "5b8efff798038103d269b633813fc60c",
+                            "spanId": "eee19b7ec3c1b174",
+                            "name": "json-span"
+                        }]}]
+                    }]}"#,
+                )
+                .to_request();
+
+            let response = actix_test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let request = actix_test::TestRequest::get()
+                .uri("/spans-list?__name__=json-span")
+                .to_request();
+            let response = actix_test::call_service(&app, request).await;
+            let result: Vec<traces::Span> = actix_test::read_body_json(response).await;
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].id, "eee19b7ec3c1b174");
+            assert_eq!(result[0].trace_id, "5b8efff798038103d269b633813fc60c");
+        }
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/logs")
+                .insert_header(("Content-Type", OTLP_JSON_FORMAT_CONTENT_TYPE))
+                .set_payload("{not json")
+                .to_request();
+
+            let response = actix_test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    fn get_string_anyvalue(string: &str) -> AnyValue {
+        AnyValue {
+            value: Some(commonv1::any_value::Value::StringValue(string.to_string())),
+        }
+    }
+
+    fn pairs_to_keyvalue(pairs: Vec<(&str, AnyValue)>) -> Vec<KeyValue> {
+        pairs
+            .into_iter()
+            .map(|(k, v)| KeyValue {
+                key: k.to_string(),
+                value: Some(v),
+            })
+            .collect()
+    }
+
+    fn get_sample_dp_attrs() -> Vec<KeyValue> {
+        pairs_to_keyvalue(vec![
+            ("key2", get_string_anyvalue("surprise")),
+            ("key3", get_string_anyvalue("value3")),
+        ])
+    }
+
+    fn get_sample_number_dp(value: i64, timestamp_ms: u64) -> metricsv1::NumberDataPoint {
+        metricsv1::NumberDataPoint {
+            attributes: get_sample_dp_attrs(),
+            start_time_unix_nano: 161078,
+            time_unix_nano: timestamp_ms * NANOS_IN_MILLIS,
+            exemplars: vec![],
+            flags: 0,
+            value: Some(metricsv1::number_data_point::Value::AsInt(value)),
+        }
+    }
+
+    pub fn get_sample_gauge() -> metricsv1::Gauge {
+        metricsv1::Gauge {
+            data_points: vec![get_sample_number_dp(78, 1400), get_sample_number_dp(500, 45000)],
+        }
+    }
+
+    fn get_sample_metric(name: &str) -> Metric {
+        Metric {
+            name: name.to_string(),
+            description: "a test metric".to_string(),
+            unit: "petaweber".to_string(),
+            data: Some(metricsv1::metric::Data::Gauge(get_sample_gauge())),
+            metadata: vec![],
+        }
+    }
+
+    fn get_sample_metrics_data() -> metricsv1::MetricsData {
+        let resource = get_sample_resource();
+        let instr = vec![ScopeMetrics {
+            scope: Some(get_sample_instr_library()),
+            metrics: vec![get_sample_metric("length"), get_sample_metric("breath")],
+            schema_url: "".to_string(),
+        }];
+        let resource_metrics_1 = ResourceMetrics {
+            resource: Some(resource),
+            scope_metrics: instr,
+            schema_url: "".to_string(),
+        };
+        let resource_metrics_2 = resource_metrics_1.clone();
+
+        metricsv1::MetricsData {
+            resource_metrics: vec![resource_metrics_1, resource_metrics_2],
+        }
+    }
+
+    fn get_sample_metrics_request_body() -> impl Into<web::Bytes> {
+        let metrics = get_sample_metrics_data();
+        metrics.encode_to_vec()
+    }
+
+    #[actix_rt::test]
+    async fn otlp_metrics_store_test() {
+        let opts = get_default_options();
+        let mut app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts.clone()))
+                .app_data(get_default_app_data())
+                .service(web::scope("/v1").route("/metrics", web::post().to(handler_receiver_otlp_metrics)))
+                .route(
+                    "/metrics-list",
+                    web::get().to(metrics_data::handler_metrics_list),
+                )
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/metrics")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .set_payload(get_sample_metrics_request_body())
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        {
+            let request = actix_test::TestRequest::get().uri("/metrics-list").to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = actix_test::read_body(response).await;
+
+            assert!(body.eq(&Bytes::from("length: 2\nbreath: 2\n")) || body.eq(&Bytes::from("breath: 2\nlength: 2\n")));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn otlp_metrics_store_samples_test() {
+        let opts = get_default_options();
+        let mut app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts.clone()))
+                .app_data(get_default_app_data())
+                .service(web::scope("/v1").route("/metrics", web::post().to(handler_receiver_otlp_metrics)))
+                .route(
+                    "/metrics-samples",
+                    web::get().to(metrics_data::handler_metrics_samples),
+                )
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/metrics")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .set_payload(get_sample_metrics_request_body())
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        {
+            let request = actix_test::TestRequest::get()
+                .uri("/metrics-samples")
+                .set_payload(get_sample_metrics_request_body())
+                .to_request();
+            let response = actix_test::call_service(&mut app, request).await;
+
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let result: Vec<Sample> = actix_test::read_body_json(response).await;
+            assert_eq!(result.len(), 2);
+        }
+    }
+
+    fn get_sample_span(name: &str, span_id: &str, parent_id: &str, trace_id: &str) -> tracev1::Span {
+        tracev1::Span {
+            trace_id: hex::decode(trace_id).unwrap(),
+            span_id: hex::decode(span_id).unwrap(),
+            trace_state: String::new(),
+            parent_span_id: hex::decode(parent_id).unwrap(),
+            name: name.to_string(),
+            kind: 42,
+            start_time_unix_nano: 12,
+            end_time_unix_nano: 73,
+            attributes: pairs_to_keyvalue(vec![
+                ("common", get_string_anyvalue("value")),
+                ("unique", get_string_anyvalue(&(name.to_string() + span_id))),
+            ]),
+            dropped_attributes_count: 0,
+            events: vec![],
+            dropped_events_count: 0,
+            links: vec![],
+            dropped_links_count: 0,
+            status: None,
+            flags: 0,
+        }
+    }
+
+    fn get_sample_spans_data() -> tracev1::TracesData {
+        let resource = get_sample_resource();
+        let instr = vec![ScopeSpans {
+            scope: Some(get_sample_instr_library()),
+            spans: vec![
+                tracev1::Span {
+                    kind: tracev1::span::SpanKind::Server as i32,
+                    ..get_sample_span("parent", "aaaa", "", "bbbb")
+                },
+                tracev1::Span {
+                    kind: tracev1::span::SpanKind::Client as i32,
+                    trace_state: "vendor=value".to_string(),
+                    start_time_unix_nano: 1_000_000,
+                    end_time_unix_nano: 251_000_000,
+                    events: vec![tracev1::span::Event {
+                        time_unix_nano: 2_000_000,
+                        name: "exception".to_string(),
+                        attributes: pairs_to_keyvalue(vec![("exception.message", get_string_anyvalue("timeout"))]),
+                        dropped_attributes_count: 0,
+                    }],
+                    links: vec![tracev1::span::Link {
+                        trace_id: hex::decode("dddd").unwrap(),
+                        span_id: hex::decode("eeee").unwrap(),
+                        ..Default::default()
+                    }],
+                    status: Some(tracev1::Status {
+                        message: "request timed out".to_string(),
+                        code: tracev1::status::StatusCode::Error as i32,
+                    }),
+                    ..get_sample_span("child", "cccc", "aaaa", "bbbb")
+                },
+            ],
+            schema_url: "".to_string(),
+        }];
+        let resource_spans_1 = ResourceSpans {
+            resource: Some(resource),
+            scope_spans: instr,
+            schema_url: "".to_string(),
+        };
+        tracev1::TracesData {
+            resource_spans: vec![resource_spans_1],
+        }
+    }
+
+    fn get_sample_spans_request_body() -> impl Into<web::Bytes> {
+        let spans = get_sample_spans_data();
+        spans.encode_to_vec()
+    }
+
+    #[actix_rt::test]
+    async fn otlp_traces_store_spans_test() {
+        let opts = get_default_options();
+        let mut app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts.clone()))
+                .app_data(get_default_app_data())
+                .service(web::scope("/v1").route("/traces", web::post().to(handler_receiver_otlp_traces)))
+                .route("/spans-list", web::get().to(traces_data::handler_get_spans))
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/traces")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .set_payload(get_sample_spans_request_body())
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        {
+            let request = actix_test::TestRequest::get()
+                .uri("/spans-list?common=value")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: Vec<traces::Span> = actix_test::read_body_json(response).await;
+            assert_eq!(2, result.len());
+        }
+
+        {
+            let request = actix_test::TestRequest::get()
+                .uri("/spans-list?unique=childcccc&__name__=child")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: Vec<traces::Span> = actix_test::read_body_json(response).await;
+            assert_eq!(1, result.len());
+            assert_eq!("bbbb".to_string(), result[0].trace_id);
+
+            let span = &result[0];
+            assert_eq!(span.kind, "SPAN_KIND_CLIENT");
+            assert_eq!(span.status.code, "STATUS_CODE_ERROR");
+            assert_eq!(span.status.message, "request timed out");
+            assert_eq!(span.duration_nanos(), 250_000_000);
+            assert_eq!(span.trace_state, "vendor=value");
+            assert_eq!(span.scope.name, "the best library");
+            assert_eq!(span.scope.version, "v2.1.5");
+            assert_eq!(span.events.len(), 1);
+            assert_eq!(span.events[0].name, "exception");
+            assert_eq!(span.events[0].attributes["exception.message"], "timeout");
+            assert_eq!(span.links.len(), 1);
+            assert_eq!(span.links[0].trace_id, "dddd");
+            assert_eq!(span.links[0].span_id, "eeee");
+            assert_eq!(span.resource_attributes["some-key"], "blep");
+            assert!(!span.resource_attributes.contains_key("unique"));
+            assert_eq!(span.span_attributes["unique"], "childcccc");
+            assert!(!span.span_attributes.contains_key("some-key"));
+        }
+
+        // (query, expected span names)
+        let cases = [
+            ("status_code=error", vec!["child"]),
+            ("status_code=STATUS_CODE_UNSET", vec!["parent"]),
+            ("kind=server", vec!["parent"]),
+            ("kind=SPAN_KIND_CLIENT&common=value", vec!["child"]),
+            ("kind=client&status_code=ok", vec![]),
+            ("min_duration_ms=100", vec!["child"]),
+            ("max_duration_ms=0.5", vec!["parent"]),
+            ("min_duration_ms=250&max_duration_ms=250", vec!["child"]),
+        ];
+        for (query, expected) in cases {
+            let request = actix_test::TestRequest::get()
+                .uri(&format!("/spans-list?{}", query))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: Vec<traces::Span> = actix_test::read_body_json(response).await;
+            let names: Vec<&str> = result.iter().map(|span| span.name.as_str()).collect();
+            assert_eq!(names, expected, "query: {}", query);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn otlp_trace_store_traces_test() {
+        let opts = get_default_options();
+        let mut app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(opts.clone()))
+                .app_data(get_default_app_data())
+                .service(web::scope("/v1").route("/traces", web::post().to(handler_receiver_otlp_traces)))
+                .route("/traces-list", web::get().to(traces_data::handler_get_traces))
+                .route(
+                    "/traces/{trace_id}",
+                    web::get().to(traces_data::handler_get_trace),
+                )
+                .route(
+                    "/traces-summary",
+                    web::get().to(traces_data::handler_traces_summary),
+                )
+                .default_service(web::get().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            let request = actix_test::TestRequest::post()
+                .uri("/v1/traces")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .set_payload(get_sample_spans_request_body())
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        {
+            let request = actix_test::TestRequest::get()
+                .uri("/traces-list?common=value")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: Vec<Vec<traces::Span>> = actix_test::read_body_json(response).await;
+            assert_eq!(1, result.len());
+            assert_eq!(2, result[0].len())
+        }
+
+        {
+            // Current behavior is that a trace is returned if any span matches.
+            let request = actix_test::TestRequest::get()
+                .uri("/traces-list?unique=childcccc")
+                .insert_header(("Content-Type", OTLP_PROTOBUF_FORMAT_CONTENT_TYPE))
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: Vec<Vec<traces::Span>> = actix_test::read_body_json(response).await;
+            assert_eq!(1, result.len());
+            assert_eq!(2, result[0].len())
+        }
+
+        {
+            let request = actix_test::TestRequest::get()
+                .uri("/traces-list?status_code=error&min_duration_ms=200")
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: Vec<Vec<traces::Span>> = actix_test::read_body_json(response).await;
+            assert_eq!(1, result.len());
+            assert_eq!(2, result[0].len())
+        }
+
+        {
+            let request = actix_test::TestRequest::get()
+                .uri("/traces-list?status_code=error&kind=server")
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: Vec<Vec<traces::Span>> = actix_test::read_body_json(response).await;
+            assert_eq!(0, result.len());
+        }
+
+        {
+            let request = actix_test::TestRequest::get().uri("/traces/bbbb").to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: serde_json::Value = actix_test::read_body_json(response).await;
+            assert_eq!(result["span_count"], 2);
+            assert_eq!(result["roots"][0]["id"], "aaaa");
+            assert_eq!(result["roots"][0]["depth"], 0);
+            assert_eq!(result["roots"][0]["children"][0]["id"], "cccc");
+            assert_eq!(result["roots"][0]["children"][0]["depth"], 1);
+            assert_eq!(result["orphans"], serde_json::json!([]));
+        }
+
+        {
+            let request = actix_test::TestRequest::get().uri("/traces/ffff").to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        {
+            let request = actix_test::TestRequest::get()
+                .uri("/traces-summary")
+                .to_request();
+
+            let response = actix_test::call_service(&mut app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result: traces::TracesSummary = actix_test::read_body_json(response).await;
+            assert_eq!(result.traces_count, 1);
+            assert!(result.broken_traces.is_empty());
+        }
+    }
+}
@@ -0,0 +1,724 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Cursor;
+
+use actix_web::{guard, web, HttpRequest, HttpResponse, Responder};
+use anyhow::{anyhow, Result};
+use prost::Message;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::metadata::get_metrics_metadata_from_headers;
+use crate::metrics::promql;
+use crate::metrics::remote_write::{handle_remote_write, WriteRequest};
+use crate::metrics::sample::format_float_label;
+use crate::options;
+use crate::router::*;
+use crate::time::get_now_ms;
+
+const REMOTE_WRITE_CONTENT_TYPE: &str = "application/x-protobuf";
+const REMOTE_WRITE_CONTENT_ENCODING: &str = "snappy";
+// `/prometheus.metrics`, optionally followed by a suffix, e.g. `/prometheus.metrics.node`
+const REMOTE_WRITE_PATH: &str = r"/prometheus.metrics{suffix:(\.[^/]*)?}";
+
+// Remote write receiver. Only requests with the remote write content type are routed to it, the other ones,
+// e.g. in the Prometheus text format, go to the default receiver. Remote write 2.0 isn't supported, it uses
+// `application/x-protobuf;proto=io.prometheus.write.v2.Request`.
+pub fn remote_write_service() -> actix_web::Resource {
+    web::resource(REMOTE_WRITE_PATH)
+        .guard(guard::Header("content-type", REMOTE_WRITE_CONTENT_TYPE))
+        .route(web::post().to(handler_prometheus_remote_write))
+}
+
+// Metrics sent with Prometheus remote write, e.g. to `/prometheus.metrics.node`.
+// The body is a snappy compressed WriteRequest protobuf message.
+pub async fn handler_prometheus_remote_write(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    let remote_address = get_address(&req);
+    let content_type = match get_content_type(&req) {
+        Ok(x) => x,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    // The remote write spec requires the body to be snappy compressed
+    let content_encoding = req
+        .headers()
+        .get("content-encoding")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !content_encoding.eq_ignore_ascii_case(REMOTE_WRITE_CONTENT_ENCODING) {
+        return HttpResponse::BadRequest().body(format!(
+            "Invalid Content-Encoding header value: '{}', expected '{}'",
+            content_encoding, REMOTE_WRITE_CONTENT_ENCODING
+        ));
+    }
+
+    let metrics_metadata = match get_metrics_metadata_from_headers(req.headers()) {
+        Ok(metrics_metadata) => metrics_metadata,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+
+    if let Some(response) = try_dropping_data(&opts, &app_state, &req, &content_type) {
+        return response;
+    }
+
+    let body = match get_decompressed_body(&req, body, &app_state) {
+        Ok(body) => body,
+        Err(e) => return e.error_response(),
+    };
+
+    let write_request = match WriteRequest::decode(&mut Cursor::new(body)) {
+        Ok(data) => data,
+        Err(_) => return HttpResponse::BadRequest().body("Unable to parse body"),
+    };
+
+    let mut result = handle_remote_write(write_request, remote_address, &opts);
+    result.add_metadata(&metrics_metadata);
+    app_state.add_metrics_result(result, remote_address, &opts);
+
+    HttpResponse::NoContent().finish()
+}
+
+// Subset of the Prometheus HTTP API, over the stored samples and series.
+// Parameters can be sent in the URL query or as a form in the body, as Grafana does.
+// Reference: https://prometheus.io/docs/prometheus/latest/querying/api/
+
+// The same limit as in Prometheus
+const MAX_QUERY_RANGE_POINTS: u64 = 11000;
+
+#[derive(Serialize)]
+struct ApiResponse<T: Serialize> {
+    status: &'static str,
+    data: T,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiErrorResponse {
+    status: &'static str,
+    error_type: &'static str,
+    error: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct QueryData {
+    result_type: &'static str,
+    result: Vec<serde_json::Value>,
+}
+
+fn api_success<T: Serialize>(data: T) -> HttpResponse {
+    HttpResponse::Ok().json(ApiResponse {
+        status: "success",
+        data,
+    })
+}
+
+fn api_error(error: anyhow::Error) -> HttpResponse {
+    HttpResponse::BadRequest().json(ApiErrorResponse {
+        status: "error",
+        error_type: "bad_data",
+        error: error.to_string(),
+    })
+}
+
+struct ApiParams(Vec<(String, String)>);
+
+impl ApiParams {
+    fn new(req: &HttpRequest, body: &web::Bytes) -> Result<Self> {
+        let mut params = web::Query::<Vec<(String, String)>>::from_query(req.query_string())?.into_inner();
+        if get_content_type(req).is_ok_and(|content_type| content_type.starts_with("application/x-www-form-urlencoded"))
+        {
+            let body = std::str::from_utf8(body)?;
+            params.extend(web::Query::<Vec<(String, String)>>::from_query(body)?.into_inner());
+        }
+        Ok(Self(params))
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn get_required(&self, name: &str) -> Result<&str> {
+        self.get(name)
+            .ok_or_else(|| anyhow!("Missing the '{}' parameter", name))
+    }
+
+    // Get the `match[]` selectors
+    fn get_selectors(&self) -> Result<Vec<promql::Selector>> {
+        self.0
+            .iter()
+            .filter(|(key, _)| key == "match[]")
+            .map(|(_, value)| promql::Selector::parse(value))
+            .collect()
+    }
+
+    // Get a time parameter in milliseconds, it can be a RFC 3339 date or a unix timestamp in seconds
+    fn get_time(&self, name: &str) -> Result<Option<u64>> {
+        let value = match self.get(name) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        if let Ok(seconds) = value.parse::<f64>() {
+            if seconds.is_finite() && seconds >= 0.0 {
+                return Ok(Some((seconds * 1000.0).round() as u64));
+            }
+        }
+        match chrono::DateTime::parse_from_rfc3339(value) {
+            Ok(time) => match u64::try_from(time.timestamp_millis()) {
+                Ok(time) => Ok(Some(time)),
+                Err(_) => Err(anyhow!("Invalid '{}' parameter: {}", name, value)),
+            },
+            Err(_) => Err(anyhow!("Invalid '{}' parameter: {}", name, value)),
+        }
+    }
+}
+
+// Timestamps are returned in seconds and values as strings
+fn get_api_point(timestamp: u64, value: f64) -> serde_json::Value {
+    json!([timestamp as f64 / 1000.0, format_float_label(value)])
+}
+
+// Series labels of all the stored samples matching any of the selectors, or all of them
+fn get_matching_labels(app_state: &AppState, selectors: &[promql::Selector]) -> Vec<promql::Labels> {
+    let samples = app_state.metrics_samples.read().unwrap();
+    let mut result: Vec<promql::Labels> = samples
+        .iter()
+        .map(promql::get_sample_labels)
+        .filter(|labels| selectors.is_empty() || selectors.iter().any(|selector| selector.matches(labels)))
+        .collect();
+    result.sort();
+    result
+}
+
+// `/api/v1/series` - the label sets of the series matching the `match[]` selectors
+pub async fn handler_prometheus_series(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_metrics {
+        return HttpResponse::NotImplemented().body("Use the --store-metrics flag to enable this endpoint");
+    }
+    let selectors = match ApiParams::new(&req, &body).and_then(|params| params.get_selectors()) {
+        Ok(selectors) => selectors,
+        Err(e) => return api_error(e),
+    };
+    if selectors.is_empty() {
+        return api_error(anyhow!("No match[] parameter provided"));
+    }
+
+    api_success(get_matching_labels(&app_state, &selectors))
+}
+
+// `/api/v1/labels` - the label names of the series matching the optional `match[]` selectors
+pub async fn handler_prometheus_labels(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_metrics {
+        return HttpResponse::NotImplemented().body("Use the --store-metrics flag to enable this endpoint");
+    }
+    let selectors = match ApiParams::new(&req, &body).and_then(|params| params.get_selectors()) {
+        Ok(selectors) => selectors,
+        Err(e) => return api_error(e),
+    };
+
+    let names: BTreeSet<String> = get_matching_labels(&app_state, &selectors)
+        .into_iter()
+        .flat_map(|labels| labels.into_keys())
+        .collect();
+    api_success(names)
+}
+
+// `/api/v1/label/{name}/values` - the values of a label in the series matching the optional `match[]` selectors
+pub async fn handler_prometheus_label_values(
+    req: HttpRequest,
+    body: web::Bytes,
+    name: web::Path<String>,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_metrics {
+        return HttpResponse::NotImplemented().body("Use the --store-metrics flag to enable this endpoint");
+    }
+    let selectors = match ApiParams::new(&req, &body).and_then(|params| params.get_selectors()) {
+        Ok(selectors) => selectors,
+        Err(e) => return api_error(e),
+    };
+
+    let values: BTreeSet<String> = get_matching_labels(&app_state, &selectors)
+        .into_iter()
+        .filter_map(|mut labels| labels.remove(name.as_str()))
+        .collect();
+    api_success(values)
+}
+
+// `/api/v1/query` - evaluate an instant query at `time`, now by default
+pub async fn handler_prometheus_query(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_metrics {
+        return HttpResponse::NotImplemented().body("Use the --store-metrics flag to enable this endpoint");
+    }
+
+    match ApiParams::new(&req, &body).and_then(|params| evaluate_query(&params, &app_state)) {
+        Ok(data) => api_success(data),
+        Err(e) => api_error(e),
+    }
+}
+
+fn evaluate_query(params: &ApiParams, app_state: &AppState) -> Result<QueryData> {
+    let expr = promql::parse(params.get_required("query")?)?;
+    let time = params.get_time("time")?.unwrap_or_else(get_now_ms);
+
+    let series = app_state.metrics_series.read().unwrap();
+    Ok(match promql::evaluate(&expr, &series, time)? {
+        promql::Value::Vector(vector) => QueryData {
+            result_type: "vector",
+            result: vector
+                .into_iter()
+                .map(|sample| json!({"metric": sample.labels, "value": get_api_point(time, sample.value)}))
+                .collect(),
+        },
+        promql::Value::Matrix(matrix) => QueryData {
+            result_type: "matrix",
+            result: matrix
+                .into_iter()
+                .map(|series| {
+                    let values: Vec<serde_json::Value> = series
+                        .points
+                        .iter()
+                        .map(|point| get_api_point(point.timestamp, point.value))
+                        .collect();
+                    json!({"metric": series.labels, "values": values})
+                })
+                .collect(),
+        },
+    })
+}
+
+// `/api/v1/query_range` - evaluate a query at every `step` between `start` and `end`
+pub async fn handler_prometheus_query_range(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_metrics {
+        return HttpResponse::NotImplemented().body("Use the --store-metrics flag to enable this endpoint");
+    }
+
+    match ApiParams::new(&req, &body).and_then(|params| evaluate_range_query(&params, &app_state)) {
+        Ok(data) => api_success(data),
+        Err(e) => api_error(e),
+    }
+}
+
+fn evaluate_range_query(params: &ApiParams, app_state: &AppState) -> Result<QueryData> {
+    let expr = promql::parse(params.get_required("query")?)?;
+    let start = params
+        .get_time("start")?
+        .ok_or_else(|| anyhow!("Missing the 'start' parameter"))?;
+    let end = params
+        .get_time("end")?
+        .ok_or_else(|| anyhow!("Missing the 'end' parameter"))?;
+    // The step is a duration or a number of seconds
+    let step = params.get_required("step")?;
+    let step = match step.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds > 0.0 => (seconds * 1000.0).round() as u64,
+        Ok(_) => 0,
+        Err(_) => promql::parse_duration(step)?,
+    };
+    if step == 0 {
+        return Err(anyhow!("The 'step' parameter has to be positive"));
+    }
+    if end < start {
+        return Err(anyhow!(
+            "The 'end' timestamp must not be before the 'start' time"
+        ));
+    }
+    if (end - start) / step >= MAX_QUERY_RANGE_POINTS {
+        return Err(anyhow!(
+            "Exceeded the maximum resolution of {} points per time series",
+            MAX_QUERY_RANGE_POINTS
+        ));
+    }
+
+    let series = app_state.metrics_series.read().unwrap();
+    let mut matrix: BTreeMap<promql::Labels, Vec<serde_json::Value>> = BTreeMap::new();
+    for time in (start..=end).step_by(step as usize) {
+        match promql::evaluate(&expr, &series, time)? {
+            promql::Value::Vector(vector) => {
+                for sample in vector {
+                    matrix
+                        .entry(sample.labels)
+                        .or_default()
+                        .push(get_api_point(time, sample.value));
+                }
+            }
+            promql::Value::Matrix(_) => {
+                return Err(anyhow!("Range vectors are not supported in range queries"));
+            }
+        }
+    }
+
+    Ok(QueryData {
+        result_type: "matrix",
+        result: matrix
+            .into_iter()
+            .map(|(labels, values)| json!({"metric": labels, "values": values}))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::remote_write::{Label, RemoteSample, TimeSeries};
+    use crate::metrics::sample::Sample;
+    use crate::metrics::series::Series;
+    use actix_web::{test, App};
+
+    fn get_write_request_body() -> Vec<u8> {
+        get_encoded_write_request(vec![RemoteSample {
+            value: 0.25,
+            timestamp: 1638873379541,
+        }])
+    }
+
+    fn get_encoded_write_request(samples: Vec<RemoteSample>) -> Vec<u8> {
+        let request = WriteRequest {
+            timeseries: vec![TimeSeries {
+                labels: vec![
+                    Label {
+                        name: String::from("__name__"),
+                        value: String::from("node_load1"),
+                    },
+                    Label {
+                        name: String::from("instance"),
+                        value: String::from("node-1"),
+                    },
+                ],
+                samples,
+            }],
+        };
+        snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn test_handler_prometheus_remote_write() {
+        let opts = options::Options {
+            store_metrics: true,
+            ..Default::default()
+        };
+        let app_data = web::Data::new(AppState::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data.clone())
+                .app_data(web::Data::new(opts))
+                .service(remote_write_service())
+                .route(
+                    "/metrics-samples",
+                    web::get().to(metrics_data::handler_metrics_samples),
+                )
+                .default_service(web::post().to(handler_receiver)),
+        )
+        .await;
+
+        {
+            let req = test::TestRequest::post()
+                .uri("/prometheus.metrics.node")
+                .insert_header(("Content-Type", "application/x-protobuf"))
+                .insert_header(("Content-Encoding", "snappy"))
+                .insert_header(("X-Sumo-Category", "prometheus/node"))
+                .set_payload(get_write_request_body())
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 204);
+        }
+        {
+            // the body has to be compressed
+            let req = test::TestRequest::post()
+                .uri("/prometheus.metrics")
+                .insert_header(("Content-Type", "application/x-protobuf"))
+                .set_payload(get_write_request_body())
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400);
+        }
+        {
+            // other paths starting with the prefix aren't remote write
+            let req = test::TestRequest::post()
+                .uri("/prometheus.metricsnode")
+                .insert_header(("Content-Type", "application/x-protobuf"))
+                .insert_header(("Content-Encoding", "snappy"))
+                .set_payload(get_write_request_body())
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400);
+        }
+        assert_eq!(*app_data.metrics.read().unwrap(), 1);
+        assert_eq!(
+            app_data.metrics_list.read().unwrap().get("node_load1"),
+            Some(&1)
+        );
+
+        {
+            // the Prometheus text format goes to the receiver
+            let req = test::TestRequest::post()
+                .uri("/prometheus.metrics")
+                .insert_header(("Content-Type", "application/vnd.sumologic.prometheus"))
+                .set_payload("node_load1 0.5")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+        }
+
+        assert_eq!(*app_data.metrics.read().unwrap(), 2);
+
+        {
+            let req = test::TestRequest::get()
+                .uri("/metrics-samples?instance=node-1&_sourceCategory=prometheus/node")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let result: Vec<Sample> = test::read_body_json(resp).await;
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].metric, "node_load1");
+            assert_eq!(result[0].value, 0.25);
+            assert_eq!(result[0].timestamp, 1638873379541);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_metrics_series() {
+        let opts = options::Options {
+            store_metrics: true,
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new()))
+                .app_data(web::Data::new(opts))
+                .service(remote_write_service())
+                .route(
+                    "/metrics-series",
+                    web::get().to(metrics_data::handler_metrics_series),
+                ),
+        )
+        .await;
+
+        let requests = [
+            vec![
+                RemoteSample {
+                    value: 1.0,
+                    timestamp: 1000,
+                },
+                RemoteSample {
+                    value: 2.0,
+                    timestamp: 2000,
+                },
+            ],
+            vec![RemoteSample {
+                value: 3.0,
+                timestamp: 3000,
+            }],
+        ];
+        for samples in requests {
+            let req = test::TestRequest::post()
+                .uri("/prometheus.metrics")
+                .insert_header(("Content-Type", "application/x-protobuf"))
+                .insert_header(("Content-Encoding", "snappy"))
+                .set_payload(get_encoded_write_request(samples))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 204);
+        }
+
+        // (query, expected data point values)
+        let cases = [
+            ("", vec![1.0, 2.0, 3.0]),
+            (
+                "?__name__=node_load1&instance=node-1&from_ts=2000",
+                vec![2.0, 3.0],
+            ),
+            ("?to_ts=2000", vec![1.0]),
+            ("?instance=node-2", vec![]),
+        ];
+        for (query, expected) in cases {
+            let req = test::TestRequest::get()
+                .uri(&format!("/metrics-series{}", query))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200);
+
+            let result: Vec<Series> = test::read_body_json(resp).await;
+            let values: Vec<f64> = result
+                .iter()
+                .flat_map(|series| series.points.iter().map(|point| point.value))
+                .collect();
+            assert_eq!(values, expected, "query: {}", query);
+        }
+    }
+
+    fn get_api_options() -> options::Options {
+        options::Options {
+            store_metrics: true,
+            ..Default::default()
+        }
+    }
+
+    // node_cpu_seconds_total for two instances, with data points every 30 seconds from 1000 seconds
+    fn get_api_app_state(opts: &options::Options) -> AppState {
+        let timeseries = [("node-1", [10.0, 40.0, 70.0]), ("node-2", [5.0, 5.0, 35.0])]
+            .iter()
+            .map(|(instance, values)| TimeSeries {
+                labels: vec![
+                    Label {
+                        name: String::from("__name__"),
+                        value: String::from("node_cpu_seconds_total"),
+                    },
+                    Label {
+                        name: String::from("instance"),
+                        value: instance.to_string(),
+                    },
+                ],
+                samples: values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| RemoteSample {
+                        value: *value,
+                        timestamp: 1_000_000 + i as i64 * 30_000,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let app_state = AppState::new();
+        let address = std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1));
+        let result = handle_remote_write(WriteRequest { timeseries }, address, opts);
+        app_state.add_metrics_result(result, address, opts);
+        app_state
+    }
+
+    #[actix_rt::test]
+    async fn test_prometheus_http_api() {
+        let opts = get_api_options();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(get_api_app_state(&opts)))
+                .app_data(web::Data::new(opts))
+                .service(
+                    web::scope("/api/v1")
+                        .route("/series", web::route().to(handler_prometheus_series))
+                        .route("/labels", web::route().to(handler_prometheus_labels))
+                        .route(
+                            "/label/{name}/values",
+                            web::get().to(handler_prometheus_label_values),
+                        )
+                        .route("/query", web::route().to(handler_prometheus_query))
+                        .route("/query_range", web::route().to(handler_prometheus_query_range)),
+                ),
+        )
+        .await;
+
+        // (request, expected status, expected response)
+        let cases = [
+            (
+                test::TestRequest::get()
+                    .uri("/api/v1/series?match[]=node_cpu_seconds_total%7Binstance%3D%22node-1%22%7D"),
+                200,
+                json!({"status": "success", "data": [{"__name__": "node_cpu_seconds_total", "instance": "node-1"}]}),
+            ),
+            (
+                test::TestRequest::get().uri("/api/v1/series"),
+                400,
+                json!({"status": "error", "errorType": "bad_data", "error": "No match[] parameter provided"}),
+            ),
+            (
+                test::TestRequest::get().uri("/api/v1/labels"),
+                200,
+                json!({"status": "success", "data": ["__name__", "instance"]}),
+            ),
+            (
+                test::TestRequest::get().uri("/api/v1/label/instance/values?match[]=%7Binstance%3D~%22.*-2%22%7D"),
+                200,
+                json!({"status": "success", "data": ["node-2"]}),
+            ),
+            (
+                test::TestRequest::get().uri("/api/v1/label/__name__/values"),
+                200,
+                json!({"status": "success", "data": ["node_cpu_seconds_total"]}),
+            ),
+            (
+                test::TestRequest::get()
+                    .uri("/api/v1/query?query=node_cpu_seconds_total%7Binstance%21%3D%22node-1%22%7D&time=1030"),
+                200,
+                json!({"status": "success", "data": {"resultType": "vector", "result": [
+                    {"metric": {"__name__": "node_cpu_seconds_total", "instance": "node-2"}, "value": [1030.0, "5"]}
+                ]}}),
+            ),
+            (
+                // the same query sent as a form, the way Grafana does
+                test::TestRequest::post()
+                    .uri("/api/v1/query")
+                    .insert_header(("Content-Type", "application/x-www-form-urlencoded"))
+                    .set_payload("query=sum(rate(node_cpu_seconds_total[1m]))&time=1970-01-01T00:17:40Z"),
+                200,
+                json!({"status": "success", "data": {"resultType": "vector", "result": [
+                    {"metric": {}, "value": [1060.0, "2"]}
+                ]}}),
+            ),
+            (
+                test::TestRequest::get()
+                    .uri("/api/v1/query?query=node_cpu_seconds_total%7Binstance%3D%22node-1%22%7D%5B1m%5D&time=1060"),
+                200,
+                json!({"status": "success", "data": {"resultType": "matrix", "result": [
+                    {"metric": {"__name__": "node_cpu_seconds_total", "instance": "node-1"}, "values": [[1030.0, "40"], [1060.0, "70"]]}
+                ]}}),
+            ),
+            (
+                test::TestRequest::get()
+                    .uri("/api/v1/query_range?query=count(node_cpu_seconds_total)&start=990&end=1060&step=30s"),
+                200,
+                json!({"status": "success", "data": {"resultType": "matrix", "result": [
+                    {"metric": {}, "values": [[1020.0, "2"], [1050.0, "2"]]}
+                ]}}),
+            ),
+            (
+                test::TestRequest::get().uri("/api/v1/query?query=rate(node_cpu_seconds_total)"),
+                400,
+                json!({"status": "error", "errorType": "bad_data", "error": "Expected a range vector selector in Rate"}),
+            ),
+            (
+                test::TestRequest::get().uri("/api/v1/query_range?query=up&start=1&end=2"),
+                400,
+                json!({"status": "error", "errorType": "bad_data", "error": "Missing the 'step' parameter"}),
+            ),
+        ];
+        for (request, status, expected) in cases {
+            let req = request.to_request();
+            let uri = req.uri().to_string();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status, "{}", uri);
+
+            let result: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(result, expected, "{}", uri);
+        }
+    }
+}
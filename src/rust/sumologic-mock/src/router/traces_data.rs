@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
 
 use crate::{options, traces};
 
@@ -8,31 +9,109 @@ use super::AppState;
 
 pub async fn handler_get_spans(
     app_state: web::Data<AppState>,
-    web::Query(params): web::Query<HashMap<String, String>>,
+    web::Query(mut params): web::Query<HashMap<String, String>>,
+    web::Query(span_query): web::Query<traces::SpanQuery>,
     opts: web::Data<options::Options>,
 ) -> impl Responder {
     if !opts.store_traces {
         return HttpResponse::NotImplemented().body("");
     }
 
+    // The span query parameters are not attribute filters
+    params.retain(|key, _| !traces::SpanQuery::PARAMS.contains(&key.as_str()));
+
     let spans = &*app_state.spans_list.read().unwrap();
-    let response = traces::filter_spans(spans.values(), params);
+    let response = traces::filter_spans(spans.values(), params, &span_query);
 
     HttpResponse::Ok().json(response)
 }
 
 pub async fn handler_get_traces(
     app_state: web::Data<AppState>,
-    web::Query(params): web::Query<HashMap<String, String>>,
+    web::Query(mut params): web::Query<HashMap<String, String>>,
+    web::Query(span_query): web::Query<traces::SpanQuery>,
     opts: web::Data<options::Options>,
 ) -> impl Responder {
     if !opts.store_traces {
         return HttpResponse::NotImplemented().body("");
     }
 
+    params.retain(|key, _| !traces::SpanQuery::PARAMS.contains(&key.as_str()));
+
     let spans = &*app_state.spans_list.read().unwrap();
     let traces = &*app_state.traces_list.read().unwrap();
-    let response = traces::filter_traces(traces.values(), &spans, params);
+    let response = traces::filter_traces(traces.values(), spans, params, &span_query);
 
     HttpResponse::Ok().json(response)
 }
+
+pub async fn handler_get_trace(
+    app_state: web::Data<AppState>,
+    trace_id: web::Path<String>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_traces {
+        return HttpResponse::NotImplemented().body("");
+    }
+
+    let spans = &*app_state.spans_list.read().unwrap();
+    let traces = &*app_state.traces_list.read().unwrap();
+    match traces.get(trace_id.as_str()) {
+        Some(trace) => HttpResponse::Ok().json(traces::build_trace_tree(&trace_id, trace, spans)),
+        None => HttpResponse::NotFound().body(format!("Trace {} not found", trace_id)),
+    }
+}
+
+pub async fn handler_traces_summary(
+    app_state: web::Data<AppState>,
+    opts: web::Data<options::Options>,
+) -> impl Responder {
+    if !opts.store_traces {
+        return HttpResponse::NotImplemented().body("");
+    }
+
+    let spans = &*app_state.spans_list.read().unwrap();
+    let traces = &*app_state.traces_list.read().unwrap();
+
+    HttpResponse::Ok().json(traces::get_traces_summary(traces, spans))
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct TracesResetResponse {
+    pub traces: usize,
+    pub spans: usize,
+}
+
+// Remove the traces matching the filters, as in `/traces-list`.
+// Without filters the received spans counter is reset as well.
+pub(super) fn reset_traces(
+    app_state: &AppState,
+    params: HashMap<String, String>,
+    span_query: &traces::SpanQuery,
+) -> TracesResetResponse {
+    let reset_counter = params.is_empty() && span_query.is_empty();
+    let mut spans = app_state.spans_list.write().unwrap();
+    let mut traces = app_state.traces_list.write().unwrap();
+    let (traces_count, spans_count) = traces::remove_traces(&mut traces, &mut spans, params, span_query);
+    let mut trace_index = app_state.trace_index.write().unwrap();
+    trace_index.rebuild(&traces, &spans);
+
+    if reset_counter {
+        app_state.spans.store(0, std::sync::atomic::Ordering::Relaxed);
+        trace_index.evicted_count = 0;
+    }
+    TracesResetResponse {
+        traces: traces_count,
+        spans: spans_count,
+    }
+}
+
+pub async fn handler_traces_reset(
+    app_state: web::Data<AppState>,
+    web::Query(mut params): web::Query<HashMap<String, String>>,
+    web::Query(span_query): web::Query<traces::SpanQuery>,
+) -> impl Responder {
+    params.retain(|key, _| !traces::SpanQuery::PARAMS.contains(&key.as_str()));
+
+    HttpResponse::Ok().json(reset_traces(&app_state, params, &span_query))
+}
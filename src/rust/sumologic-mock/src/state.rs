@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::compression::EncodingStatsRepository;
+use crate::logs::{LogMessage, LogStatsRepository};
+use crate::metrics::sample::Sample;
+use crate::metrics::series::SeriesSnapshot;
+use crate::router::terraform::TerraformState;
+use crate::router::AppState;
+use crate::time::get_now_ms;
+use crate::traces::{Span, Trace, TraceId};
+
+// Version of the snapshot format, increase it on incompatible changes.
+// Snapshots with a different version are rejected rather than partially restored.
+pub const STATE_VERSION: u32 = 1;
+
+#[derive(Deserialize, Serialize)]
+pub struct Stats {
+    pub metrics: u64,
+    pub metrics_malformed: u64,
+    pub metrics_list: HashMap<String, u64>,
+    pub metrics_ip_list: HashMap<IpAddr, u64>,
+    pub logs: LogStatsRepository,
+    pub spans: u64,
+    pub encodings: EncodingStatsRepository,
+    // Items evicted because of the retention limits, per signal
+    #[serde(default)]
+    pub evicted: HashMap<String, u64>,
+}
+
+// Snapshot of the mock state. The request stats exported in /metrics describe the running process, so they're not included.
+#[derive(Deserialize, Serialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub timestamp: u64, // milliseconds epoch timestamp of when the snapshot was taken
+    pub stats: Stats,
+    pub logs: Vec<LogMessage>,
+    pub samples: Vec<Sample>,
+    pub series: Vec<SeriesSnapshot>,
+    pub spans: Vec<Span>,
+    pub traces: HashMap<TraceId, Trace>,
+    pub terraform_fields: HashMap<String, String>,
+}
+
+impl Snapshot {
+    pub fn take(app_state: &AppState, terraform_state: &TerraformState) -> Self {
+        Self {
+            version: STATE_VERSION,
+            timestamp: get_now_ms(),
+            stats: Stats {
+                metrics: *app_state.metrics.read().unwrap(),
+                metrics_malformed: *app_state.metrics_malformed.read().unwrap(),
+                metrics_list: app_state.metrics_list.read().unwrap().clone(),
+                metrics_ip_list: app_state.metrics_ip_list.read().unwrap().clone(),
+                logs: {
+                    let log_stats = app_state.log_stats.read().unwrap();
+                    LogStatsRepository {
+                        total: log_stats.total.clone(),
+                        ipaddr: log_stats.ipaddr.clone(),
+                    }
+                },
+                spans: app_state.spans.load(Ordering::Relaxed),
+                encodings: {
+                    let encoding_stats = app_state.encoding_stats.read().unwrap();
+                    EncodingStatsRepository {
+                        total: encoding_stats.total.clone(),
+                        encodings: encoding_stats.encodings.clone(),
+                    }
+                },
+                evicted: HashMap::from([
+                    (
+                        String::from("logs"),
+                        app_state.log_messages.read().unwrap().evicted_count,
+                    ),
+                    (
+                        String::from("metrics"),
+                        app_state.metrics_series.read().unwrap().evicted_count,
+                    ),
+                    (
+                        String::from("traces"),
+                        app_state.trace_index.read().unwrap().evicted_count,
+                    ),
+                ]),
+            },
+            logs: app_state.log_messages.read().unwrap().iter().cloned().collect(),
+            samples: app_state
+                .metrics_samples
+                .read()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect(),
+            series: app_state.metrics_series.read().unwrap().snapshot(),
+            spans: app_state.spans_list.read().unwrap().values().cloned().collect(),
+            traces: app_state.traces_list.read().unwrap().clone(),
+            terraform_fields: terraform_state.fields.lock().unwrap().clone(),
+        }
+    }
+
+    // Replace the state with the one from the snapshot
+    pub fn restore(self, app_state: &AppState, terraform_state: &TerraformState) {
+        *app_state.metrics.write().unwrap() = self.stats.metrics;
+        *app_state.metrics_malformed.write().unwrap() = self.stats.metrics_malformed;
+        *app_state.metrics_list.write().unwrap() = self.stats.metrics_list;
+        *app_state.metrics_ip_list.write().unwrap() = self.stats.metrics_ip_list;
+        *app_state.log_stats.write().unwrap() = self.stats.logs;
+        app_state.spans.store(self.stats.spans, Ordering::Relaxed);
+        *app_state.encoding_stats.write().unwrap() = self.stats.encodings;
+
+        let evicted_count = |signal: &str| self.stats.evicted.get(signal).copied().unwrap_or(0);
+        {
+            let mut log_messages = app_state.log_messages.write().unwrap();
+            log_messages.restore(self.logs);
+            log_messages.evicted_count = evicted_count("logs");
+        }
+        *app_state.metrics_samples.write().unwrap() = self.samples.into_iter().collect();
+        {
+            let mut series = app_state.metrics_series.write().unwrap();
+            series.restore(self.series);
+            series.evicted_count = evicted_count("metrics");
+        }
+        {
+            let mut spans = app_state.spans_list.write().unwrap();
+            let mut traces = app_state.traces_list.write().unwrap();
+            *spans = self
+                .spans
+                .into_iter()
+                .map(|span| (span.id.clone(), span))
+                .collect();
+            *traces = self.traces;
+            let mut trace_index = app_state.trace_index.write().unwrap();
+            trace_index.rebuild(&traces, &spans);
+            trace_index.evicted_count = evicted_count("traces");
+        }
+        *terraform_state.fields.lock().unwrap() = self.terraform_fields;
+    }
+}
+
+// Write the snapshot to a temporary file first, so a crash while writing doesn't corrupt the previous one
+pub fn save_snapshot(path: &Path, snapshot: &Snapshot) -> Result<()> {
+    let mut tmp_path = PathBuf::from(path);
+    tmp_path.as_mut_os_string().push(".tmp");
+
+    let content = serde_json::to_vec(snapshot)?;
+    fs::write(&tmp_path, content).with_context(|| format!("Unable to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Unable to rename {} to {}", tmp_path.display(), path.display()))?;
+    Ok(())
+}
+
+// Load the snapshot, a missing file means there's nothing to restore
+pub fn load_snapshot(path: &Path) -> Result<Option<Snapshot>> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(anyhow!("Unable to read {}: {}", path.display(), e)),
+    };
+
+    // Check the version before parsing the rest, as other versions can have a different structure
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+    let version: Version =
+        serde_json::from_slice(&content).with_context(|| format!("Unable to parse {}", path.display()))?;
+    if version.version != STATE_VERSION {
+        return Err(anyhow!(
+            "Unsupported state file version {} in {}, expected {}",
+            version.version,
+            path.display(),
+            STATE_VERSION
+        ));
+    }
+
+    let snapshot = serde_json::from_slice(&content).with_context(|| format!("Unable to parse {}", path.display()))?;
+    Ok(Some(snapshot))
+}
+
+// Restore the state from the file if it exists
+pub fn restore_state(path: &Path, app_state: &AppState, terraform_state: &TerraformState) -> Result<()> {
+    match load_snapshot(path)? {
+        Some(snapshot) => {
+            info!(
+                "Restoring the state from {}, saved at {}",
+                path.display(),
+                snapshot.timestamp
+            );
+            snapshot.restore(app_state, terraform_state);
+        }
+        None => info!(
+            "State file {} doesn't exist, starting with an empty state",
+            path.display()
+        ),
+    }
+    Ok(())
+}
+
+pub fn save_state(path: &Path, app_state: &AppState, terraform_state: &TerraformState) {
+    let snapshot = Snapshot::take(app_state, terraform_state);
+    if let Err(e) = save_snapshot(path, &snapshot) {
+        error!("Unable to save the state: {:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Metadata;
+    use crate::metrics::MetricsHandleResult;
+    use crate::options;
+    use crate::traces::TracesHandleResult;
+    use std::sync::Mutex;
+
+    fn get_options() -> options::Options {
+        options::Options {
+            store_traces: true,
+            store_metrics: true,
+            store_logs: true,
+            ..Default::default()
+        }
+    }
+
+    fn get_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sumologic-mock-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_save_and_restore_state() {
+        let opts = get_options();
+        let address = IpAddr::from([10, 0, 0, 1]);
+        let app_state = AppState::new();
+        let terraform_state = TerraformState {
+            fields: Mutex::new(HashMap::from([(String::from("id"), String::from("cluster"))])),
+        };
+
+        let logs = ["first log", "second log"];
+        app_state.add_log_lines(logs.into_iter(), Metadata::new(), address, &opts);
+
+        let mut result = MetricsHandleResult::new();
+        for (value, timestamp) in [(1.0, 1000), (2.0, 2000)] {
+            result.handle_metric(String::from("up"));
+            result.metrics_samples.push(Sample {
+                metric: String::from("up"),
+                value,
+                labels: HashMap::new(),
+                timestamp,
+                meta_labels: HashMap::new(),
+            });
+        }
+        app_state.add_metrics_result(result, address, &opts);
+
+        let mut result = TracesHandleResult::new();
+        result.handle_span(Span {
+            id: String::from("span"),
+            trace_id: String::from("trace"),
+            ..Default::default()
+        });
+        app_state.add_traces_result(result, &opts);
+
+        let path = get_path("state");
+        save_state(&path, &app_state, &terraform_state);
+
+        let restored_state = AppState::new();
+        let restored_terraform_state = TerraformState {
+            fields: Mutex::new(HashMap::new()),
+        };
+        restore_state(&path, &restored_state, &restored_terraform_state).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(*restored_state.metrics.read().unwrap(), 2);
+        assert_eq!(restored_state.metrics_list.read().unwrap()["up"], 2);
+        assert_eq!(restored_state.metrics_samples.read().unwrap().len(), 1);
+        let series = restored_state
+            .metrics_series
+            .read()
+            .unwrap()
+            .get_series(&HashMap::new(), 0, u64::MAX);
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].points.len(), 2);
+
+        assert_eq!(restored_state.log_stats.read().unwrap().total.message_count, 2);
+        assert_eq!(
+            restored_state
+                .log_stats
+                .read()
+                .unwrap()
+                .get_stats_for_ipaddr(address),
+            app_state
+                .log_stats
+                .read()
+                .unwrap()
+                .get_stats_for_ipaddr(address)
+        );
+        let bodies: Vec<String> = restored_state
+            .log_messages
+            .read()
+            .unwrap()
+            .iter()
+            .map(|log| log.body.clone())
+            .collect();
+        assert_eq!(bodies, logs);
+
+        assert_eq!(restored_state.spans.load(Ordering::Relaxed), 1);
+        assert!(restored_state.spans_list.read().unwrap().contains_key("span"));
+        assert_eq!(
+            restored_state.traces_list.read().unwrap()["trace"].span_ids,
+            vec!["span"]
+        );
+        assert_eq!(restored_terraform_state.fields.lock().unwrap()["id"], "cluster");
+    }
+
+    #[test]
+    fn test_load_snapshot() {
+        assert!(load_snapshot(&get_path("missing")).unwrap().is_none());
+
+        let path = get_path("version");
+        fs::write(&path, r#"{"version": 0}"#).unwrap();
+        let result = load_snapshot(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("Unsupported state file version 0"));
+    }
+}
@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 use log::warn;
 use serde::{Deserialize, Serialize};
 
+use crate::options::RetentionLimits;
+use crate::time::get_now_ms;
+
 pub struct TracesHandleResult {
     pub spans_count: u64,
     pub spans: Vec<Span>,
@@ -25,27 +28,183 @@ impl TracesHandleResult {
 pub type TraceId = String;
 pub type SpanId = String;
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+// Span kinds and status codes are stored using the OTLP enum names,
+// e.g. `SPAN_KIND_SERVER` and `STATUS_CODE_ERROR`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Span {
     pub name: String,
     pub id: SpanId,
     pub trace_id: TraceId,
     pub parent_span_id: SpanId,
+    // Resource and span attributes merged together, resource attributes take precedence
+    pub attributes: HashMap<String, String>,
+    #[serde(default)]
+    pub resource_attributes: HashMap<String, String>,
+    #[serde(default)]
+    pub span_attributes: HashMap<String, String>,
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub status: SpanStatus,
+    #[serde(default)]
+    pub start_time_unix_nano: u64,
+    #[serde(default)]
+    pub end_time_unix_nano: u64,
+    #[serde(default)]
+    pub trace_state: String,
+    #[serde(default)]
+    pub scope: InstrumentationScope,
+    #[serde(default)]
+    pub events: Vec<SpanEvent>,
+    #[serde(default)]
+    pub links: Vec<SpanLink>,
+}
+
+impl Span {
+    pub fn duration_nanos(&self) -> u64 {
+        self.end_time_unix_nano
+            .saturating_sub(self.start_time_unix_nano)
+    }
+
+    // Approximate size of the span, its identifiers, names and attributes
+    pub fn byte_count(&self) -> u64 {
+        let attributes_byte_count = |attributes: &HashMap<String, String>| -> usize {
+            attributes
+                .iter()
+                .map(|(key, value)| key.len() + value.len())
+                .sum()
+        };
+        let events_byte_count: usize = self
+            .events
+            .iter()
+            .map(|event| event.name.len() + attributes_byte_count(&event.attributes))
+            .sum();
+        let links_byte_count: usize = self
+            .links
+            .iter()
+            .map(|link| {
+                link.trace_id.len()
+                    + link.span_id.len()
+                    + link.trace_state.len()
+                    + attributes_byte_count(&link.attributes)
+            })
+            .sum();
+        (self.name.len()
+            + self.id.len()
+            + self.trace_id.len()
+            + self.parent_span_id.len()
+            + attributes_byte_count(&self.attributes)
+            + attributes_byte_count(&self.resource_attributes)
+            + attributes_byte_count(&self.span_attributes)
+            + self.kind.len()
+            + self.status.code.len()
+            + self.status.message.len()
+            + self.trace_state.len()
+            + self.scope.name.len()
+            + self.scope.version.len()
+            + events_byte_count
+            + links_byte_count) as u64
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SpanStatus {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct InstrumentationScope {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SpanEvent {
+    pub name: String,
+    pub time_unix_nano: u64,
+    pub attributes: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SpanLink {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub trace_state: String,
     pub attributes: HashMap<String, String>,
 }
 
+// Span filters which are not attributes.
+// Kinds and status codes can be given with or without the enum prefix and in any case,
+// e.g. `error`, `ERROR` and `STATUS_CODE_ERROR` are equivalent.
+#[derive(Debug, Default, Deserialize)]
+pub struct SpanQuery {
+    pub status_code: Option<String>,
+    pub kind: Option<String>,
+    pub min_duration_ms: Option<f64>,
+    pub max_duration_ms: Option<f64>,
+}
+
+impl SpanQuery {
+    // Names of the query parameters, these are not used as attribute filters
+    pub const PARAMS: [&'static str; 4] = ["status_code", "kind", "min_duration_ms", "max_duration_ms"];
+
+    pub fn is_empty(&self) -> bool {
+        self.status_code.is_none()
+            && self.kind.is_none()
+            && self.min_duration_ms.is_none()
+            && self.max_duration_ms.is_none()
+    }
+
+    fn matches(&self, span: &Span) -> bool {
+        if let Some(status_code) = &self.status_code {
+            if !enum_name_matches(&span.status.code, "STATUS_CODE_", status_code) {
+                return false;
+            }
+        }
+        if let Some(kind) = &self.kind {
+            if !enum_name_matches(&span.kind, "SPAN_KIND_", kind) {
+                return false;
+            }
+        }
+
+        let duration_ms = span.duration_nanos() as f64 / NANOS_IN_MILLIS;
+        if self.min_duration_ms.is_some_and(|min| duration_ms < min) {
+            return false;
+        }
+        if self.max_duration_ms.is_some_and(|max| duration_ms > max) {
+            return false;
+        }
+
+        true
+    }
+}
+
+const NANOS_IN_MILLIS: f64 = 1_000_000.0;
+
+fn enum_name_matches(name: &str, prefix: &str, expected: &str) -> bool {
+    name.eq_ignore_ascii_case(expected)
+        || name
+            .strip_prefix(prefix)
+            .is_some_and(|short| short.eq_ignore_ascii_case(expected))
+}
+
 impl std::fmt::Display for Span {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // TODO: Consider adding more info, eg. service name
         write!(
             f,
-            "name: {}, span_id: {}, parent_span_id: {}, trace_id: {}",
-            self.name, self.id, self.parent_span_id, self.trace_id,
+            "name: {}, span_id: {}, parent_span_id: {}, trace_id: {}, kind: {}, status: {}",
+            self.name, self.id, self.parent_span_id, self.trace_id, self.kind, self.status.code,
         )
     }
 }
 
-fn is_span_ok(span: &Span, params: &HashMap<String, String>) -> bool {
+fn is_span_ok(span: &Span, params: &HashMap<String, String>, query: &SpanQuery) -> bool {
+    if !query.matches(span) {
+        return false;
+    }
+
     for (key, value) in params.iter() {
         // Identically as in the metric's case,
         // we use "__name__" as key for span's name
@@ -71,38 +230,534 @@ fn is_span_ok(span: &Span, params: &HashMap<String, String>) -> bool {
     true
 }
 
-pub fn filter_spans<'a>(spans: impl Iterator<Item = &'a Span>, params: HashMap<String, String>) -> Vec<&'a Span> {
-    spans.filter(|span| is_span_ok(span, &params)).collect()
+pub fn filter_spans<'a>(
+    spans: impl Iterator<Item = &'a Span>,
+    params: HashMap<String, String>,
+    query: &SpanQuery,
+) -> Vec<&'a Span> {
+    spans.filter(|span| is_span_ok(span, &params, query)).collect()
 }
 
 pub fn filter_traces<'a>(
     traces: impl Iterator<Item = &'a Trace>,
     spans: &'a HashMap<SpanId, Span>,
     params: HashMap<String, String>,
+    query: &SpanQuery,
 ) -> Vec<Vec<&'a Span>> {
     traces
-        .map(|trace| {
-            // Doing this functionally would be a mess if we want to handle bugs without panicking.
-            let mut spans_vec = Vec::with_capacity(trace.span_ids.len());
-            for span_id in &trace.span_ids {
-                if let Some(span) = spans.get(span_id) {
-                    spans_vec.push(span);
-                } else {
-                    warn!("Span with id {} not found", span_id);
+        .map(|trace| get_trace_spans(trace, spans))
+        .filter(|spans_vec| spans_vec.iter().any(|&span| is_span_ok(span, &params, query)))
+        .collect()
+}
+
+// Remove the traces returned by filter_traces for the same filters, with all their spans.
+// Returns the number of removed traces and spans.
+pub fn remove_traces(
+    traces: &mut HashMap<TraceId, Trace>,
+    spans: &mut HashMap<SpanId, Span>,
+    params: HashMap<String, String>,
+    query: &SpanQuery,
+) -> (usize, usize) {
+    let trace_ids: Vec<TraceId> = traces
+        .iter()
+        .filter(|(_, trace)| {
+            get_trace_spans(trace, spans)
+                .iter()
+                .any(|&span| is_span_ok(span, &params, query))
+        })
+        .map(|(trace_id, _)| trace_id.clone())
+        .collect();
+
+    let mut spans_count = 0;
+    for trace_id in trace_ids.iter() {
+        if let Some(trace) = traces.remove(trace_id) {
+            for span_id in trace.span_ids.iter() {
+                if spans.remove(span_id).is_some() {
+                    spans_count += 1;
                 }
             }
-            spans_vec
+        }
+    }
+    (trace_ids.len(), spans_count)
+}
+
+fn get_trace_spans<'a>(trace: &Trace, spans: &'a HashMap<SpanId, Span>) -> Vec<&'a Span> {
+    // Doing this functionally would be a mess if we want to handle bugs without panicking.
+    let mut spans_vec = Vec::with_capacity(trace.span_ids.len());
+    let mut seen = HashSet::with_capacity(trace.span_ids.len());
+    for span_id in &trace.span_ids {
+        // The same span can be received more than once
+        if !seen.insert(span_id) {
+            continue;
+        }
+        if let Some(span) = spans.get(span_id) {
+            spans_vec.push(span);
+        } else {
+            warn!("Span with id {} not found", span_id);
+        }
+    }
+    spans_vec
+}
+
+#[derive(Debug, Serialize)]
+pub struct TraceTreeNode<'a> {
+    #[serde(flatten)]
+    pub span: &'a Span,
+    pub depth: usize,
+    pub children: Vec<TraceTreeNode<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TraceTree<'a> {
+    pub trace_id: TraceId,
+    pub span_count: usize,
+    // Spans without a parent
+    pub roots: Vec<TraceTreeNode<'a>>,
+    // Spans with a parent which is not a part of the trace, the depth is counted from them
+    pub orphans: Vec<TraceTreeNode<'a>>,
+    // Spans in a cycle of parents, e.g. their own parents, and their descendants. They can't be placed in the tree.
+    pub unreachable: Vec<&'a Span>,
+}
+
+// Build the parent/child tree of a trace.
+// Siblings are sorted by their start time, so the order doesn't depend on the order of receiving.
+pub fn build_trace_tree<'a>(trace_id: &str, trace: &Trace, spans: &'a HashMap<SpanId, Span>) -> TraceTree<'a> {
+    let trace_spans = get_trace_spans(trace, spans);
+    let span_ids: HashSet<&str> = trace_spans.iter().map(|span| span.id.as_str()).collect();
+
+    let mut roots = vec![];
+    let mut orphans = vec![];
+    let mut children: HashMap<&str, Vec<&Span>> = HashMap::new();
+    for &span in trace_spans.iter() {
+        if span.parent_span_id.is_empty() {
+            roots.push(span);
+        } else if !span_ids.contains(span.parent_span_id.as_str()) {
+            orphans.push(span);
+        } else {
+            children
+                .entry(span.parent_span_id.as_str())
+                .or_default()
+                .push(span);
+        }
+    }
+
+    TraceTree {
+        trace_id: trace_id.to_string(),
+        span_count: trace_spans.len(),
+        roots: build_tree_nodes(roots, 0, &children),
+        orphans: build_tree_nodes(orphans, 0, &children),
+        unreachable: get_unreachable_spans(&trace_spans),
+    }
+}
+
+// Spans which can't be reached from a root or an orphan, because they or their ancestors are in a cycle of parents.
+// Sorted by their start time, like the siblings in the tree.
+fn get_unreachable_spans<'a>(trace_spans: &[&'a Span]) -> Vec<&'a Span> {
+    let span_ids: HashSet<&str> = trace_spans.iter().map(|span| span.id.as_str()).collect();
+    let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut pending = vec![];
+    for span in trace_spans.iter() {
+        if span.parent_span_id.is_empty() || !span_ids.contains(span.parent_span_id.as_str()) {
+            pending.push(span.id.as_str());
+        } else {
+            children
+                .entry(span.parent_span_id.as_str())
+                .or_default()
+                .push(span.id.as_str());
+        }
+    }
+
+    let mut reached = HashSet::new();
+    while let Some(span_id) = pending.pop() {
+        if reached.insert(span_id) {
+            pending.extend(children.get(span_id).into_iter().flatten());
+        }
+    }
+
+    let mut unreachable: Vec<&Span> = trace_spans
+        .iter()
+        .copied()
+        .filter(|span| !reached.contains(span.id.as_str()))
+        .collect();
+    unreachable.sort_by(|a, b| (a.start_time_unix_nano, &a.id).cmp(&(b.start_time_unix_nano, &b.id)));
+    unreachable
+}
+
+// Spans which are their own ancestors are never reached from a root or an orphan,
+// so the recursion always ends.
+fn build_tree_nodes<'a>(
+    mut spans: Vec<&'a Span>,
+    depth: usize,
+    children: &HashMap<&str, Vec<&'a Span>>,
+) -> Vec<TraceTreeNode<'a>> {
+    spans.sort_by(|a, b| (a.start_time_unix_nano, &a.id).cmp(&(b.start_time_unix_nano, &b.id)));
+    spans
+        .into_iter()
+        .map(|span| TraceTreeNode {
+            span,
+            depth,
+            children: build_tree_nodes(
+                children.get(span.id.as_str()).cloned().unwrap_or_default(),
+                depth + 1,
+                children,
+            ),
         })
-        .filter(|spans_vec| spans_vec.iter().any(|&span| is_span_ok(span, &params)))
         .collect()
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TraceIssues {
+    pub trace_id: TraceId,
+    pub span_count: usize,
+    pub root_span_ids: Vec<SpanId>,
+    // None of the spans is a root
+    pub missing_root: bool,
+    pub multiple_roots: bool,
+    // Span ids mapped to the ids of their parents, which are not a part of the trace
+    pub unknown_parents: BTreeMap<SpanId, SpanId>,
+    // Some of the spans are in a cycle of parents, so they and their descendants aren't a part of the tree
+    pub parent_cycle: bool,
+    pub unreachable_span_ids: Vec<SpanId>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TracesSummary {
+    pub traces_count: usize,
+    pub broken_traces: Vec<TraceIssues>,
+}
+
+// List the traces which are not a single complete tree.
+// This is what happens when the spans of a trace are split between different collectors.
+pub fn get_traces_summary(traces: &HashMap<TraceId, Trace>, spans: &HashMap<SpanId, Span>) -> TracesSummary {
+    let mut broken_traces: Vec<TraceIssues> = traces
+        .iter()
+        .filter_map(|(trace_id, trace)| {
+            let trace_spans = get_trace_spans(trace, spans);
+            let span_ids: HashSet<&str> = trace_spans.iter().map(|span| span.id.as_str()).collect();
+
+            let mut root_span_ids: Vec<SpanId> = trace_spans
+                .iter()
+                .filter(|span| span.parent_span_id.is_empty())
+                .map(|span| span.id.clone())
+                .collect();
+            root_span_ids.sort();
+            let unknown_parents: BTreeMap<SpanId, SpanId> = trace_spans
+                .iter()
+                .filter(|span| !span.parent_span_id.is_empty() && !span_ids.contains(span.parent_span_id.as_str()))
+                .map(|span| (span.id.clone(), span.parent_span_id.clone()))
+                .collect();
+            let mut unreachable_span_ids: Vec<SpanId> = get_unreachable_spans(&trace_spans)
+                .into_iter()
+                .map(|span| span.id.clone())
+                .collect();
+            unreachable_span_ids.sort();
+
+            let issues = TraceIssues {
+                trace_id: trace_id.clone(),
+                span_count: trace_spans.len(),
+                missing_root: root_span_ids.is_empty(),
+                multiple_roots: root_span_ids.len() > 1,
+                root_span_ids,
+                unknown_parents,
+                parent_cycle: !unreachable_span_ids.is_empty(),
+                unreachable_span_ids,
+            };
+            if issues.missing_root || issues.multiple_roots || !issues.unknown_parents.is_empty() || issues.parent_cycle
+            {
+                Some(issues)
+            } else {
+                None
+            }
+        })
+        .collect();
+    broken_traces.sort_by(|a, b| a.trace_id.cmp(&b.trace_id));
+
+    TracesSummary {
+        traces_count: traces.len(),
+        broken_traces,
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Trace {
     pub span_ids: Vec<SpanId>,
+    // milliseconds epoch timestamp of when the first span was received
+    #[serde(default)]
+    pub received_timestamp: u64,
 }
 
 impl Trace {
     pub fn new() -> Self {
-        Trace { span_ids: vec![] }
+        Trace {
+            span_ids: vec![],
+            received_timestamp: get_now_ms(),
+        }
+    }
+}
+
+// Order in which the traces were received and the size of their spans, used to evict whole traces,
+// the oldest ones first
+pub struct TraceIndex {
+    order: VecDeque<TraceId>,
+    byte_count: u64,
+    pub evicted_count: u64,
+}
+
+impl TraceIndex {
+    pub fn new() -> Self {
+        Self {
+            order: VecDeque::new(),
+            byte_count: 0,
+            evicted_count: 0,
+        }
+    }
+
+    // Store the span, adding its trace if it's a new one
+    pub fn add_span(&mut self, traces: &mut HashMap<TraceId, Trace>, spans: &mut HashMap<SpanId, Span>, span: Span) {
+        let trace = traces.entry(span.trace_id.clone()).or_insert_with(|| {
+            self.order.push_back(span.trace_id.clone());
+            Trace::new()
+        });
+        trace.span_ids.push(span.id.clone());
+        self.byte_count += span.byte_count();
+        // The same span can be received more than once
+        if let Some(previous) = spans.insert(span.id.clone(), span) {
+            self.byte_count -= previous.byte_count();
+        }
+    }
+
+    // Approximate size of the stored spans
+    pub fn byte_count(&self) -> u64 {
+        self.byte_count
+    }
+
+    // Evict the oldest traces with all their spans until the limits are met. Returns the number of evicted spans.
+    pub fn evict(
+        &mut self,
+        traces: &mut HashMap<TraceId, Trace>,
+        spans: &mut HashMap<SpanId, Span>,
+        limits: &RetentionLimits,
+        now_ms: u64,
+    ) -> u64 {
+        let mut evicted_count = 0;
+        while let Some(trace_id) = self.order.front() {
+            let Some(trace) = traces.get(trace_id) else {
+                self.order.pop_front();
+                continue;
+            };
+            if !limits.is_exceeded(spans.len(), self.byte_count) && !limits.is_expired(trace.received_timestamp, now_ms)
+            {
+                break;
+            }
+
+            let trace_id = self.order.pop_front().unwrap();
+            let trace = traces.remove(&trace_id).unwrap();
+            for span_id in trace.span_ids.iter() {
+                if let Some(span) = spans.remove(span_id) {
+                    self.byte_count -= span.byte_count();
+                    evicted_count += 1;
+                }
+            }
+        }
+        self.evicted_count += evicted_count;
+        evicted_count
+    }
+
+    // Rebuild the order and the size after the traces were removed or replaced
+    pub fn rebuild(&mut self, traces: &HashMap<TraceId, Trace>, spans: &HashMap<SpanId, Span>) {
+        let mut order: Vec<(&TraceId, &Trace)> = traces.iter().collect();
+        order.sort_by_key(|(_, trace)| trace.received_timestamp);
+        self.order = order
+            .into_iter()
+            .map(|(trace_id, _)| trace_id.clone())
+            .collect();
+        self.byte_count = spans.values().map(|span| span.byte_count()).sum();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_span(id: &str, parent_span_id: &str, start_time_unix_nano: u64) -> Span {
+        Span {
+            name: format!("span-{}", id),
+            id: id.to_string(),
+            trace_id: String::from("trace"),
+            parent_span_id: parent_span_id.to_string(),
+            start_time_unix_nano,
+            ..Default::default()
+        }
+    }
+
+    fn get_trace(spans: Vec<Span>) -> (Trace, HashMap<SpanId, Span>) {
+        let trace = Trace {
+            span_ids: spans.iter().map(|span| span.id.clone()).collect(),
+            received_timestamp: 0,
+        };
+        let spans = spans.into_iter().map(|span| (span.id.clone(), span)).collect();
+        (trace, spans)
+    }
+
+    #[test]
+    fn test_build_trace_tree() {
+        let (trace, spans) = get_trace(vec![
+            get_span("c2", "a", 30),
+            get_span("a", "", 10),
+            get_span("c1", "a", 20),
+            get_span("d", "c1", 25),
+            get_span("o", "unknown", 5),
+            get_span("self", "self", 1),
+        ]);
+
+        let tree = build_trace_tree("trace", &trace, &spans);
+        assert_eq!(tree.span_count, 6);
+        assert_eq!(tree.roots.len(), 1);
+        let root = &tree.roots[0];
+        assert_eq!(root.span.id, "a");
+        assert_eq!(root.depth, 0);
+        let children: Vec<&str> = root.children.iter().map(|node| node.span.id.as_str()).collect();
+        assert_eq!(children, vec!["c1", "c2"]);
+        assert_eq!(root.children[0].depth, 1);
+        assert_eq!(root.children[0].children[0].span.id, "d");
+        assert_eq!(root.children[0].children[0].depth, 2);
+
+        assert_eq!(tree.orphans.len(), 1);
+        assert_eq!(tree.orphans[0].span.id, "o");
+        assert_eq!(tree.orphans[0].depth, 0);
+
+        // counted, but not a part of the tree
+        let unreachable: Vec<&str> = tree.unreachable.iter().map(|span| span.id.as_str()).collect();
+        assert_eq!(unreachable, vec!["self"]);
+    }
+
+    #[test]
+    fn test_remove_traces() {
+        let mut traces = HashMap::new();
+        let mut spans = HashMap::new();
+        let cases = [
+            ("first", vec![get_span("a1", "", 0), get_span("a2", "a1", 0)]),
+            ("second", vec![get_span("b1", "", 0), get_span("b2", "b1", 0)]),
+        ];
+        for (trace_id, trace_spans) in cases {
+            let (trace, trace_spans) = get_trace(trace_spans);
+            traces.insert(trace_id.to_string(), trace);
+            spans.extend(trace_spans);
+        }
+
+        // the whole trace is removed when any of its spans matches
+        let params = HashMap::from([(String::from("__name__"), String::from("span-b2"))]);
+        assert_eq!(
+            remove_traces(&mut traces, &mut spans, params, &SpanQuery::default()),
+            (1, 2)
+        );
+        assert!(traces.contains_key("first"));
+        assert_eq!(spans.len(), 2);
+
+        assert_eq!(
+            remove_traces(&mut traces, &mut spans, HashMap::new(), &SpanQuery::default()),
+            (1, 2)
+        );
+        assert!(traces.is_empty());
+        assert!(spans.is_empty());
+    }
+
+    #[test]
+    fn test_trace_eviction() {
+        let mut index = TraceIndex::new();
+        let mut traces = HashMap::new();
+        let mut spans = HashMap::new();
+        for (trace_id, span_id) in [("first", "a1"), ("second", "b1"), ("first", "a2"), ("third", "c1")] {
+            let span = Span {
+                id: span_id.to_string(),
+                trace_id: trace_id.to_string(),
+                ..Default::default()
+            };
+            index.add_span(&mut traces, &mut spans, span);
+        }
+        let byte_count = index.byte_count();
+        assert_eq!(
+            byte_count,
+            spans.values().map(|span| span.byte_count()).sum::<u64>()
+        );
+
+        // the whole oldest trace is evicted, even if it had a span added later
+        let limits = RetentionLimits {
+            max_entries: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(index.evict(&mut traces, &mut spans, &limits, get_now_ms()), 2);
+        assert!(!traces.contains_key("first"));
+        assert_eq!(spans.len(), 2);
+        assert!(index.byte_count() < byte_count);
+
+        let limits = RetentionLimits {
+            max_age: Some(std::time::Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert_eq!(index.evict(&mut traces, &mut spans, &limits, get_now_ms()), 0);
+        assert_eq!(
+            index.evict(&mut traces, &mut spans, &limits, get_now_ms() + 61_000),
+            2
+        );
+        assert!(traces.is_empty());
+        assert_eq!(index.byte_count(), 0);
+        assert_eq!(index.evicted_count, 4);
+    }
+
+    #[test]
+    fn test_get_traces_summary() {
+        let mut traces = HashMap::new();
+        let mut spans = HashMap::new();
+        let cases = [
+            ("complete", vec![get_span("a1", "", 0), get_span("a2", "a1", 0)]),
+            (
+                "multiple-roots",
+                vec![get_span("b1", "", 0), get_span("b2", "", 0)],
+            ),
+            ("no-root", vec![get_span("c1", "x", 0), get_span("c2", "c1", 0)]),
+            (
+                "parent-cycle",
+                vec![
+                    get_span("d1", "", 0),
+                    get_span("d2", "d3", 0),
+                    get_span("d3", "d2", 0),
+                    get_span("d4", "d3", 0),
+                ],
+            ),
+        ];
+        for (trace_id, trace_spans) in cases {
+            let (trace, trace_spans) = get_trace(trace_spans);
+            traces.insert(trace_id.to_string(), trace);
+            spans.extend(trace_spans);
+        }
+
+        let summary = get_traces_summary(&traces, &spans);
+        assert_eq!(summary.traces_count, 4);
+        assert_eq!(summary.broken_traces.len(), 3);
+
+        let multiple_roots = &summary.broken_traces[0];
+        assert_eq!(multiple_roots.trace_id, "multiple-roots");
+        assert!(multiple_roots.multiple_roots);
+        assert!(!multiple_roots.missing_root);
+        assert_eq!(multiple_roots.root_span_ids, vec!["b1", "b2"]);
+        assert!(multiple_roots.unknown_parents.is_empty());
+        assert!(!multiple_roots.parent_cycle);
+
+        let no_root = &summary.broken_traces[1];
+        assert_eq!(no_root.trace_id, "no-root");
+        assert!(no_root.missing_root);
+        assert!(!no_root.multiple_roots);
+        assert_eq!(
+            no_root.unknown_parents,
+            BTreeMap::from([(String::from("c1"), String::from("x"))])
+        );
+
+        // listed even though it has a single root
+        let parent_cycle = &summary.broken_traces[2];
+        assert_eq!(parent_cycle.trace_id, "parent-cycle");
+        assert!(!parent_cycle.missing_root && !parent_cycle.multiple_roots);
+        assert!(parent_cycle.parent_cycle);
+        assert_eq!(parent_cycle.unreachable_span_ids, vec!["d2", "d3", "d4"]);
     }
 }